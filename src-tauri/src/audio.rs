@@ -0,0 +1,76 @@
+//! 本模块用于读取音频文件（WAV / OGG）的时长
+//!
+//! 只读取文件头部（OGG 额外读取末尾的一个页），不解码整个文件。
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// 查找最后一个 Ogg 页时从文件末尾读取的字节数（Ogg 页最大约 64 KiB）
+const OGG_TAIL_SIZE: u64 = 65_536 + 27 + 255;
+
+/// 读取音频文件的时长（毫秒），格式不支持或文件损坏时返回 `None`
+pub fn read_audio_duration_ms(path: &Path) -> Option<u32> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    let duration_ms = match ext.as_str() {
+        "wav" => wav_duration_ms(path)?,
+        "ogg" => ogg_duration_ms(path)?,
+        _ => return None,
+    };
+    Some(duration_ms.min(u32::MAX as u64) as u32)
+}
+
+/// 找到谱面中引用的音频文件
+///
+/// BMS 中写的扩展名常与实际文件不符（如写 `.wav` 实际为 `.ogg`），
+/// 找不到原文件时依次尝试其他支持的扩展名。
+pub fn resolve_audio_path(chart_path: &Path, file_name: &str) -> Option<PathBuf> {
+    let path = chart_path.parent()?.join(file_name);
+    if path.is_file() {
+        return Some(path);
+    }
+    ["ogg", "wav"]
+        .iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+// 辅助函数：由 fmt 块的采样率与数据块的帧数计算 WAV 时长
+fn wav_duration_ms(path: &Path) -> Option<u64> {
+    let reader = hound::WavReader::open(path).ok()?;
+    let sample_rate = reader.spec().sample_rate as u64;
+    if sample_rate == 0 {
+        return None;
+    }
+    // duration() 为每个声道的帧数
+    Some(reader.duration() as u64 * 1000 / sample_rate)
+}
+
+// 辅助函数：由识别头的采样率与最后一个 Ogg 页的 granule position 计算 OGG 时长
+fn ogg_duration_ms(path: &Path) -> Option<u64> {
+    let file = File::open(path).ok()?;
+    let stream = lewton::inside_ogg::OggStreamReader::new(file).ok()?;
+    let sample_rate = stream.ident_hdr.audio_sample_rate as u64;
+    if sample_rate == 0 {
+        return None;
+    }
+
+    let mut file = stream.into_inner().into_inner();
+    let len = file.seek(SeekFrom::End(0)).ok()?;
+    file.seek(SeekFrom::Start(len.saturating_sub(OGG_TAIL_SIZE)))
+        .ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+
+    // 页头：capture pattern "OggS"（4）、版本（1）、类型（1）、granule position（8，小端）
+    let start = tail.windows(4).rposition(|window| window == b"OggS")?;
+    let granule = tail.get(start + 6..start + 14)?;
+    let granule = u64::from_le_bytes(granule.try_into().ok()?);
+    // -1（u64::MAX）表示该页中没有结束的数据包
+    if granule == u64::MAX {
+        return None;
+    }
+    Some(granule.checked_mul(1000)? / sample_rate)
+}
@@ -0,0 +1,116 @@
+//! 本模块用于测量存储的读取与哈希吞吐量，为扫描配置提供参考
+//!
+//! 选取不超过 [`SAMPLE_BYTES`] 的谱面文件作为样本，在给定时长内反复读取并计算 SHA256。
+//! 重复读取会命中系统缓存，因此结果更接近上限，适合比较不同介质而非精确测速。
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::bms_scan::prelude::*;
+
+/// 样本文件的总大小上限（10 MB）
+const SAMPLE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// 读取吞吐量超过该值（MB/s）时按 SSD 配置
+const SSD_READ_THRESHOLD: f64 = 500.0;
+
+/// 读取吞吐量低于该值（MB/s）时只扫描元数据
+const SLOW_READ_THRESHOLD: f64 = 20.0;
+
+/// 基准测试结果
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkResult {
+    pub read_throughput_mb_s: f64,
+    pub hash_throughput_mb_s: f64,
+    pub files_per_second: f64,
+    pub recommended_config: ScanConfig,
+}
+
+/// 在 `duration` 内反复读取并哈希 `root` 下的样本谱面，至少完成一轮
+///
+/// 目前只支持 SHA256，哈希吞吐量仅作参考，不影响推荐配置中的算法。
+pub fn run_benchmark(
+    root: &Path,
+    duration: Duration,
+    base_config: ScanConfig,
+) -> Result<BenchmarkResult, String> {
+    let samples = collect_samples(root, &base_config);
+    if samples.is_empty() {
+        return Err("No chart files found".into());
+    }
+
+    let mut bytes = 0u64;
+    let mut files = 0u64;
+    let mut read_time = Duration::ZERO;
+    let mut hash_time = Duration::ZERO;
+    let start = Instant::now();
+    loop {
+        for path in &samples {
+            let read_start = Instant::now();
+            let content = std::fs::read(path).map_err(|e| e.to_string())?;
+            read_time += read_start.elapsed();
+
+            let hash_start = Instant::now();
+            let mut hasher = Sha256::new();
+            hasher.update(&content);
+            std::hint::black_box(hasher.finalize());
+            hash_time += hash_start.elapsed();
+
+            bytes += content.len() as u64;
+            files += 1;
+        }
+        if start.elapsed() >= duration {
+            break;
+        }
+    }
+
+    let read_throughput_mb_s = throughput_mb_s(bytes, read_time);
+    let hash_throughput_mb_s = throughput_mb_s(bytes, hash_time);
+    let files_per_second = files as f64 / (read_time + hash_time).as_secs_f64().max(f64::EPSILON);
+
+    let mut recommended_config = base_config;
+    if read_throughput_mb_s > SSD_READ_THRESHOLD {
+        recommended_config.hdd_permits = recommended_config.ssd_permits;
+    } else {
+        recommended_config.ssd_permits = recommended_config.hdd_permits.max(1);
+    }
+    if read_throughput_mb_s < SLOW_READ_THRESHOLD {
+        recommended_config.scan_mode = ScanMode::MetadataOnly;
+    }
+
+    Ok(BenchmarkResult {
+        read_throughput_mb_s,
+        hash_throughput_mb_s,
+        files_per_second,
+        recommended_config,
+    })
+}
+
+// 辅助函数：收集总大小不超过 SAMPLE_BYTES 的谱面文件（至少一个）
+fn collect_samples(root: &Path, config: &ScanConfig) -> Vec<PathBuf> {
+    let mut samples = Vec::new();
+    let mut total = 0u64;
+    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        if !config.is_target_file(entry.path()) {
+            continue;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        if !samples.is_empty() && total + size > SAMPLE_BYTES {
+            break;
+        }
+        total += size;
+        samples.push(entry.into_path());
+    }
+    samples
+}
+
+// 辅助函数：吞吐量（MB/s），用时为 0 时按极小值计算避免除以 0
+fn throughput_mb_s(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 / 1_000_000.0 / elapsed.as_secs_f64().max(f64::EPSILON)
+}
@@ -0,0 +1,1545 @@
+//! 本模块用于解析 BMS / BMSON 谱面文件中的元数据
+//!
+//! 解析头部字段（标题、艺术家、流派、BPM、难度）、两种格式统一的音符列表
+//! （[`NoteObject`]）以及 BGA 信息，用于统计音符数等谱面信息。
+
+use std::{collections::HashMap, fmt};
+
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// BMS 规范中未指定 `#BPM` 时的默认值
+pub const DEFAULT_BPM: f64 = 130.0;
+
+/// BMSON 未指定 `info.resolution` 时的默认值（每拍的脉冲数）
+const DEFAULT_BMSON_RESOLUTION: u64 = 240;
+
+/// 谱面头部信息，未出现的字段为 `None`
+#[derive(Debug, Clone, Default)]
+pub struct BmsHeader {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub genre: Option<String>,
+    pub bpm: Option<f64>,
+    pub play_level: Option<u8>,
+    /// `#BMPxx` 定义（BMSON 中为 `bga_header`）的数量
+    pub bmp_count: u32,
+    /// `#RANK` 判定等级（0 VERY HARD、1 HARD、2 NORMAL、3 EASY），BMSON 中为 `None`
+    pub rank: Option<u8>,
+    /// `#DIFFICULTY`（1-5，BMSON 中由 `chart_name` 推断）
+    pub difficulty: Option<u8>,
+    /// `#BPMxx` / `#EXBPMxx` 定义，供 08 通道引用
+    pub bpm_definitions: HashMap<u16, f64>,
+    /// `#STOPxx` 定义（单位为 1/192 小节），供 09 通道引用
+    pub stop_definitions: HashMap<u16, f64>,
+    /// `#SCROLLxx` 定义（滚动速度倍率），供 SC 通道引用
+    pub scroll_definitions: HashMap<u16, f64>,
+    /// BMSON 的 `info.mode_hint`
+    pub mode_hint: Option<String>,
+    /// 预览音频文件名（`#PREVIEW`，BMSON 中为 `info.preview_music`）
+    pub preview: Option<String>,
+    /// 音源素材的来源（`#MATERIAL`），BMSON 中为 `None`
+    pub material: Option<String>,
+    /// `#OCTFP`，BMSON 中为 `None`
+    pub octfp: Option<u8>,
+    /// 音高的八度偏移（`#OCTAVE`），BMSON 中为 `None`
+    pub octave: Option<i8>,
+    /// `#CDDA` 指定播放的 CD 音轨编号（按出现顺序，不重复），BMSON 中为空
+    pub cdda_tracks: Vec<u32>,
+    /// 是否存在 `#WAVCMD` 音源处理命令，BMSON 中为 `false`
+    pub has_wavcmd: bool,
+}
+
+/// 一次 BPM 变化
+#[derive(Debug, Clone, PartialEq)]
+pub struct BpmChange {
+    pub measure: u32,
+    pub fraction: f64,
+    pub bpm: f64,
+}
+
+/// 一次停止（谱面滚动暂停）
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopEvent {
+    pub measure: u32,
+    pub fraction: f64,
+    /// 停止的长度（拍）
+    pub beats: f64,
+}
+
+/// 一次滚动速度变化（SC 通道）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScrollEvent {
+    pub measure: u32,
+    /// 在数据行中的位置（第几个两位编号）
+    pub slot: u32,
+    /// 在小节内的位置（`0.0..1.0`）
+    pub fraction: f64,
+    /// 滚动速度倍率，负数表示音符反向移动
+    pub scroll_multiplier: f64,
+}
+
+/// 谱面的 BPM 变化时间线
+#[derive(Debug, Clone)]
+pub struct BpmTimeline {
+    pub initial_bpm: f64,
+    /// 按时间排序的 BPM 变化
+    pub changes: Vec<BpmChange>,
+}
+
+impl Default for BpmTimeline {
+    fn default() -> Self {
+        Self {
+            initial_bpm: DEFAULT_BPM,
+            changes: Vec::new(),
+        }
+    }
+}
+
+impl BpmTimeline {
+    /// 谱面中出现的最低 BPM
+    pub fn min_bpm(&self) -> f64 {
+        self.changes
+            .iter()
+            .map(|change| change.bpm)
+            .fold(self.initial_bpm, f64::min)
+    }
+
+    /// 谱面中出现的最高 BPM
+    pub fn max_bpm(&self) -> f64 {
+        self.changes
+            .iter()
+            .map(|change| change.bpm)
+            .fold(self.initial_bpm, f64::max)
+    }
+
+    /// 指定位置生效的 BPM（同一位置的变化视为已生效）
+    pub fn bpm_at(&self, measure: u32, fraction: f64) -> f64 {
+        let at = measure as f64 + fraction;
+        self.changes
+            .iter()
+            .take_while(|change| change.measure as f64 + change.fraction <= at)
+            .last()
+            .map_or(self.initial_bpm, |change| change.bpm)
+    }
+
+    /// 代表性 BPM：可演奏音符最多的 BPM，相同音符数时取较高的 BPM。没有可演奏音符时为 `None`
+    pub fn effective_bpm(&self, notes: &[NoteObject]) -> Option<f64> {
+        let mut counts: Vec<(f64, u32)> = Vec::new();
+        for note in notes.iter().filter(|note| note.is_playable()) {
+            let bpm = self.bpm_at(note.measure, note.fraction);
+            match counts.iter_mut().find(|(counted, _)| *counted == bpm) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((bpm, 1)),
+            }
+        }
+        counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.total_cmp(&b.0)))
+            .map(|(bpm, _)| bpm)
+    }
+
+    /// 估算前 `total_measures` 个小节的时长（毫秒）
+    ///
+    /// `measure_lengths` 为小节长度倍率（见 [`parse_measure_lengths`]），未定义的小节为 4/4 拍。
+    pub fn duration_ms(&self, total_measures: u32, measure_lengths: &HashMap<u32, f64>) -> f64 {
+        let end = beats_before(total_measures, 0.0, measure_lengths);
+        let mut position = 0.0;
+        let mut bpm = self.initial_bpm;
+        let mut duration = 0.0;
+        for change in &self.changes {
+            let at = beats_before(change.measure, change.fraction, measure_lengths).min(end);
+            duration += (at - position) * 60000.0 / bpm;
+            position = at;
+            bpm = change.bpm;
+        }
+        duration + (end - position) * 60000.0 / bpm
+    }
+}
+
+/// 从谱面开头到指定位置的拍数
+///
+/// `measure_lengths` 中未出现的小节按 4/4 拍（倍率 1.0）计算。
+pub fn beats_before(measure: u32, fraction: f64, measure_lengths: &HashMap<u32, f64>) -> f64 {
+    let length = |measure| measure_lengths.get(&measure).copied().unwrap_or(1.0) * 4.0;
+    let adjustment: f64 = measure_lengths
+        .iter()
+        .filter(|(defined, _)| **defined < measure)
+        .map(|(_, multiplier)| (multiplier - 1.0) * 4.0)
+        .sum();
+    measure as f64 * 4.0 + adjustment + fraction * length(measure)
+}
+
+/// 键位模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyMode {
+    #[serde(rename = "5K")]
+    Key5,
+    #[default]
+    #[serde(rename = "7K")]
+    Key7,
+    #[serde(rename = "9K")]
+    Key9,
+    #[serde(rename = "10K")]
+    Key10,
+    #[serde(rename = "14K")]
+    Key14,
+}
+
+impl KeyMode {
+    /// 是否使用 2P 侧轨道（DP 与 PMS）
+    pub fn is_double_play(&self) -> bool {
+        matches!(self, KeyMode::Key9 | KeyMode::Key10 | KeyMode::Key14)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyMode::Key5 => "5K",
+            KeyMode::Key7 => "7K",
+            KeyMode::Key9 => "9K",
+            KeyMode::Key10 => "10K",
+            KeyMode::Key14 => "14K",
+        }
+    }
+}
+
+/// 谱面难度分类（`#DIFFICULTY`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChartType {
+    Beginner,
+    Normal,
+    Hyper,
+    Another,
+    Insane,
+    #[default]
+    Unknown,
+}
+
+impl ChartType {
+    /// 由 `#DIFFICULTY` 的值（1-5）转换
+    pub fn from_difficulty(difficulty: Option<u8>) -> Self {
+        match difficulty {
+            Some(1) => ChartType::Beginner,
+            Some(2) => ChartType::Normal,
+            Some(3) => ChartType::Hyper,
+            Some(4) => ChartType::Another,
+            Some(5) => ChartType::Insane,
+            _ => ChartType::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChartType::Beginner => "Beginner",
+            ChartType::Normal => "Normal",
+            ChartType::Hyper => "Hyper",
+            ChartType::Another => "Another",
+            ChartType::Insane => "Insane",
+            ChartType::Unknown => "Unknown",
+        }
+    }
+}
+
+/// BGA 事件时间所用的单位：每小节 960 脉冲（每拍 240）
+const BGA_PULSES_PER_MEASURE: f64 = 960.0;
+
+/// BGA 事件
+#[derive(Debug, Clone, Serialize)]
+pub struct BgaEvent {
+    /// 位置（每拍 240 脉冲）
+    pub time: u64,
+    /// BGA 定义编号
+    pub id: u32,
+}
+
+/// 谱面的 BGA 信息
+#[derive(Debug, Clone, Default)]
+pub struct BgaInfo {
+    /// 基础 BGA 层的事件
+    pub events: Vec<BgaEvent>,
+    /// 有事件的 BGA 层数（基础层 / 图层 / miss 层）
+    pub layer_count: u32,
+    /// 是否有 BGA（存在定义且基础层有事件）
+    pub has_bga: bool,
+}
+
+/// 解析后的谱面
+#[derive(Debug, Clone, Default)]
+pub struct ParsedChart {
+    pub header: BmsHeader,
+    pub notes: Vec<NoteObject>,
+    pub bga: BgaInfo,
+    pub bpm_timeline: BpmTimeline,
+    pub stops: Vec<StopEvent>,
+    /// 滚动速度变化，BMSON 始终为空
+    pub scrolls: Vec<ScrollEvent>,
+    /// 小节长度倍率（02 通道），BMSON 始终为空
+    pub measure_lengths: HashMap<u32, f64>,
+    pub key_mode: KeyMode,
+    pub chart_type: ChartType,
+}
+
+/// 音符类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum NoteType {
+    Normal,
+    LNStart,
+    LNEnd,
+    Mine,
+    Scratch,
+    BpmChange,
+    StopEvent,
+    BgaEvent,
+}
+
+/// 谱面中的一个物件
+///
+/// `channel` 使用 BMS 的通道编号（按十六进制解读，如 `0x11` 为 1P 第 1 键），
+/// BMSON 的音符会映射到对应的 BMS 通道。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoteObject {
+    /// 所在小节
+    pub measure: u32,
+    /// 在小节内的位置（`0.0..1.0`）
+    pub fraction: f64,
+    /// 从谱面开头起的拍数（计入 02 通道的小节长度变化）
+    pub beat: f64,
+    pub channel: u8,
+    /// 物件值（BMS 中为 36 进制定义编号，BMSON 中为音源通道序号）
+    pub value: u16,
+    pub note_type: NoteType,
+}
+
+impl NoteObject {
+    /// 是否位于皿（scratch）轨道
+    pub fn is_scratch_lane(&self) -> bool {
+        matches!(self.channel >> 4, 0x1 | 0x2 | 0x5 | 0x6) && self.channel & 0x0F == 6
+    }
+
+    /// 是否为需要玩家击打的音符（长条只计起点）
+    pub fn is_playable(&self) -> bool {
+        matches!(
+            self.note_type,
+            NoteType::Normal | NoteType::Scratch | NoteType::LNStart
+        )
+    }
+}
+
+/// 音符数统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoteCounts {
+    pub note_count: u32,
+    pub ln_count: u32,
+    pub scratch_count: u32,
+}
+
+/// 统计音符列表中的音符数、长条数与皿音符数
+pub fn count_notes(notes: &[NoteObject]) -> NoteCounts {
+    let mut counts = NoteCounts::default();
+    for note in notes.iter().filter(|note| note.is_playable()) {
+        counts.note_count += 1;
+        if note.note_type == NoteType::LNStart {
+            counts.ln_count += 1;
+        }
+        if note.is_scratch_lane() {
+            counts.scratch_count += 1;
+        }
+    }
+    counts
+}
+
+/// 谱面指纹：只取轨道上的物件（音符、长条与地雷）与 BPM 时间线，按规范顺序计算 SHA256
+///
+/// 物件只保留位置、玩家侧、轨道与类型，不受头部字段顺序、空白、注释、`#WAVxx` 编号以及
+/// 长条写法（LN 通道或 `#LNOBJ`）的影响。位置按拍数精确到 10⁻⁶ 拍比较。
+pub fn chart_fingerprint(chart: &ParsedChart) -> [u8; 32] {
+    fingerprint(&chart.notes, &chart.bpm_timeline, &chart.measure_lengths)
+}
+
+/// 计算 BMS 谱面内容的指纹（见 [`chart_fingerprint`]）
+pub fn compute_chart_fingerprint(content: &[u8]) -> [u8; 32] {
+    let header = parse_bms_header(content);
+    let notes = parse_bms_notes(content);
+    let bpm_timeline = bms_bpm_timeline(&header, &notes);
+    fingerprint(&notes, &bpm_timeline, &parse_measure_lengths(content))
+}
+
+// 辅助函数：按拍数、玩家侧、轨道与类型排序后依次写入物件，BPM 变化按拍数排序
+fn fingerprint(
+    notes: &[NoteObject],
+    bpm_timeline: &BpmTimeline,
+    measure_lengths: &HashMap<u32, f64>,
+) -> [u8; 32] {
+    let micro_beats = |beat: f64| (beat * 1_000_000.0).round() as i64;
+
+    let mut bpm_changes: Vec<(i64, u64)> = bpm_timeline
+        .changes
+        .iter()
+        .map(|change| {
+            let beat = beats_before(change.measure, change.fraction, measure_lengths);
+            (micro_beats(beat), change.bpm.to_bits())
+        })
+        .collect();
+    bpm_changes.sort_unstable();
+
+    let mut objects: Vec<(i64, u8, u8, u8)> = notes
+        .iter()
+        .filter(|note| {
+            note.is_playable() || matches!(note.note_type, NoteType::LNEnd | NoteType::Mine)
+        })
+        .map(|note| {
+            (
+                micro_beats(note.beat),
+                lane_side(note.channel),
+                note.channel & 0x0F,
+                note.note_type as u8,
+            )
+        })
+        .collect();
+    objects.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    hasher.update(bpm_timeline.initial_bpm.to_bits().to_le_bytes());
+    hasher.update((bpm_changes.len() as u64).to_le_bytes());
+    for (beat, bpm) in bpm_changes {
+        hasher.update(beat.to_le_bytes());
+        hasher.update(bpm.to_le_bytes());
+    }
+    for (beat, side, lane, note_type) in objects {
+        hasher.update(beat.to_le_bytes());
+        hasher.update([side, lane, note_type]);
+    }
+    hasher.finalize().into()
+}
+
+/// 单侧各轨道的音符数（键 1-7 与皿）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaneBreakdown {
+    pub lane_1: u32,
+    pub lane_2: u32,
+    pub lane_3: u32,
+    pub lane_4: u32,
+    pub lane_5: u32,
+    pub lane_6: u32,
+    pub lane_7: u32,
+    pub scratch: u32,
+}
+
+impl LaneBreakdown {
+    /// 各轨道音符数之和
+    pub fn total(&self) -> u32 {
+        self.lane_1
+            + self.lane_2
+            + self.lane_3
+            + self.lane_4
+            + self.lane_5
+            + self.lane_6
+            + self.lane_7
+            + self.scratch
+    }
+
+    // 辅助函数：按 BMS 轨道编号（1-5、8、9 为键，6 为皿）取得计数
+    fn lane_mut(&mut self, lane: u8) -> Option<&mut u32> {
+        match lane {
+            1 => Some(&mut self.lane_1),
+            2 => Some(&mut self.lane_2),
+            3 => Some(&mut self.lane_3),
+            4 => Some(&mut self.lane_4),
+            5 => Some(&mut self.lane_5),
+            8 => Some(&mut self.lane_6),
+            9 => Some(&mut self.lane_7),
+            6 => Some(&mut self.scratch),
+            _ => None,
+        }
+    }
+}
+
+/// DP 两侧各轨道的音符数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoubleLaneBreakdown {
+    pub p1: LaneBreakdown,
+    pub p2: LaneBreakdown,
+}
+
+impl DoubleLaneBreakdown {
+    pub fn total(&self) -> u32 {
+        self.p1.total() + self.p2.total()
+    }
+}
+
+/// 统计每个轨道上需要击打的音符数（长条只计起点）
+///
+/// 7 通道（自由区）不属于任何键位，不计入。
+pub fn lane_breakdown(notes: &[NoteObject]) -> DoubleLaneBreakdown {
+    let mut breakdown = DoubleLaneBreakdown::default();
+    for note in notes.iter().filter(|note| note.is_playable()) {
+        let side = match lane_side(note.channel) {
+            1 => &mut breakdown.p1,
+            _ => &mut breakdown.p2,
+        };
+        if let Some(count) = side.lane_mut(note.channel & 0x0F) {
+            *count += 1;
+        }
+    }
+    breakdown
+}
+
+/// 和弦（同一时刻在不同轨道上的多个音符）统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChordStats {
+    /// 同一时刻的最大音符数
+    pub max_chord_size: u8,
+    /// 和弦（2 个及以上音符）的平均音符数
+    pub avg_chord_size: f32,
+    /// 属于和弦的音符总数
+    pub chord_note_count: u32,
+}
+
+/// 解析 BMS 文本并统计和弦信息
+pub fn compute_chord_stats(content: &[u8]) -> ChordStats {
+    chord_stats_from_notes(&parse_bms_notes(content))
+}
+
+/// 按时刻（小节与小节内位置）分组统计和弦信息，`notes` 需已按时间排序
+pub fn chord_stats_from_notes(notes: &[NoteObject]) -> ChordStats {
+    let mut stats = ChordStats::default();
+    let mut chord_count = 0u32;
+    let playable: Vec<&NoteObject> = notes.iter().filter(|note| note.is_playable()).collect();
+
+    for group in playable.chunk_by(|a, b| a.measure == b.measure && a.fraction == b.fraction) {
+        // 同一轨道的普通音符与长条起点只计一次
+        let mut lanes: Vec<(u8, u8)> = group
+            .iter()
+            .map(|note| (lane_side(note.channel), note.channel & 0x0F))
+            .collect();
+        lanes.sort_unstable();
+        lanes.dedup();
+
+        let size = lanes.len().min(u8::MAX as usize) as u8;
+        stats.max_chord_size = stats.max_chord_size.max(size);
+        if size >= 2 {
+            chord_count += 1;
+            stats.chord_note_count += size as u32;
+        }
+    }
+
+    if chord_count > 0 {
+        stats.avg_chord_size = stats.chord_note_count as f32 / chord_count as f32;
+    }
+    stats
+}
+
+/// BMSON 解析错误
+#[derive(Debug)]
+pub enum BmsonError {
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for BmsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BmsonError::Json(err) => write!(f, "invalid bmson: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BmsonError {}
+
+impl From<serde_json::Error> for BmsonError {
+    fn from(err: serde_json::Error) -> Self {
+        BmsonError::Json(err)
+    }
+}
+
+/// 解析 BMS 文本格式的头部定义（`#KEY VALUE` 形式的行）
+pub fn parse_bms_header(content: &[u8]) -> BmsHeader {
+    let text = String::from_utf8_lossy(content);
+    let mut header = BmsHeader::default();
+
+    for line in text.lines() {
+        let Some(rest) = line.trim().strip_prefix('#') else {
+            continue;
+        };
+        let Some((key, value)) = rest.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let value = value.trim();
+
+        match key.to_ascii_uppercase().as_str() {
+            "TITLE" => header.title = non_empty(value),
+            "ARTIST" => header.artist = non_empty(value),
+            "GENRE" => header.genre = non_empty(value),
+            "BPM" => header.bpm = value.parse().ok().filter(|bpm: &f64| *bpm > 0.0),
+            "PLAYLEVEL" => header.play_level = value.parse().ok(),
+            "DIFFICULTY" => header.difficulty = value.parse().ok(),
+            "RANK" => header.rank = value.parse().ok(),
+            "PREVIEW" => header.preview = non_empty(value),
+            "MATERIAL" => header.material = non_empty(value),
+            "OCTFP" => header.octfp = value.parse().ok(),
+            "OCTAVE" => header.octave = value.parse().ok(),
+            "CDDA" => {
+                if let Ok(track) = value.parse::<u32>() {
+                    if !header.cdda_tracks.contains(&track) {
+                        header.cdda_tracks.push(track);
+                    }
+                }
+            }
+            "WAVCMD" => header.has_wavcmd = true,
+            key if key.len() == 5 && key.starts_with("BMP") => header.bmp_count += 1,
+            key if key.len() == 5 && key.starts_with("BPM") => {
+                insert_bpm_definition(&mut header, &key[3..], value)
+            }
+            key if key.len() == 6 && key.starts_with("STOP") => {
+                if let (Some(id), Ok(length)) = (parse_base36(&key[4..]), value.parse::<f64>()) {
+                    header.stop_definitions.insert(id, length);
+                }
+            }
+            key if key.len() == 8 && key.starts_with("SCROLL") => {
+                if let (Some(id), Ok(multiplier)) = (parse_base36(&key[6..]), value.parse::<f64>())
+                {
+                    header.scroll_definitions.insert(id, multiplier);
+                }
+            }
+            key if key.len() == 7 && key.starts_with("EXBPM") => {
+                insert_bpm_definition(&mut header, &key[5..], value)
+            }
+            _ => (),
+        }
+    }
+
+    header
+}
+
+/// 解析 BMS 文本格式的数据行（`#mmmcc:data`），返回按时间排序的物件列表
+///
+/// 长条支持 LNTYPE 1（5x/6x 通道成对出现）与 `#LNOBJ` 两种写法。
+pub fn parse_bms_notes(content: &[u8]) -> Vec<NoteObject> {
+    let text = String::from_utf8_lossy(content);
+    let mut notes = Vec::new();
+    let mut lnobj = None;
+    let measure_lengths = parse_measure_lengths(content);
+
+    for line in text.lines() {
+        let Some(rest) = line.trim().strip_prefix('#') else {
+            continue;
+        };
+
+        if let Some((measure, channel, data)) = split_data_line(rest) {
+            let Some(note_type) = note_type_for_channel(channel) else {
+                continue;
+            };
+            let pairs = data.len() / 2;
+            for slot in 0..pairs {
+                let Some(pair) = data.get(slot * 2..slot * 2 + 2) else {
+                    continue;
+                };
+                // 03 通道的 BPM 变化直接以十六进制书写
+                let value = if channel == 0x03 {
+                    u16::from_str_radix(pair, 16).ok()
+                } else {
+                    parse_base36(pair)
+                };
+                let Some(value) = value.filter(|value| *value != 0) else {
+                    continue;
+                };
+                let fraction = slot as f64 / pairs as f64;
+                notes.push(NoteObject {
+                    measure,
+                    fraction,
+                    beat: beats_before(measure, fraction, &measure_lengths),
+                    channel,
+                    value,
+                    note_type,
+                });
+            }
+        } else if let Some((key, value)) = rest.split_once(char::is_whitespace) {
+            if key.eq_ignore_ascii_case("LNOBJ") {
+                lnobj = parse_base36(value.trim());
+            }
+        }
+    }
+
+    notes.sort_by(|a, b| {
+        a.measure
+            .cmp(&b.measure)
+            .then_with(|| a.fraction.total_cmp(&b.fraction))
+            .then_with(|| a.channel.cmp(&b.channel))
+    });
+    resolve_long_notes(&mut notes, lnobj);
+    notes
+}
+
+/// 解析 02 通道的小节长度倍率（如 `#00201:0.75` 表示第 2 小节为 3/4 拍）
+///
+/// 只包含出现了 02 通道的小节，其余小节视为 1.0。
+pub fn parse_measure_lengths(content: &[u8]) -> HashMap<u32, f64> {
+    let text = String::from_utf8_lossy(content);
+    text.lines()
+        .filter_map(|line| split_data_line(line.trim().strip_prefix('#')?))
+        .filter(|(_, channel, _)| *channel == 0x02)
+        .filter_map(|(measure, _, data)| {
+            let multiplier = data.trim().parse::<f64>().ok()?;
+            (multiplier > 0.0).then_some((measure, multiplier))
+        })
+        .collect()
+}
+
+/// 由 `#SCROLLxx` 定义与 SC 通道数据行提取滚动速度变化，按时间排序
+pub fn parse_scroll_events(content: &[u8], header: &BmsHeader) -> Vec<ScrollEvent> {
+    let text = String::from_utf8_lossy(content);
+    let mut events = Vec::new();
+    for line in text.lines() {
+        let Some((head, data)) = line
+            .trim()
+            .strip_prefix('#')
+            .and_then(|rest| rest.split_once(':'))
+        else {
+            continue;
+        };
+        // SC 不是十六进制通道，不能使用 split_data_line
+        if head.len() != 5 || !head.is_ascii() || !head[3..].eq_ignore_ascii_case("SC") {
+            continue;
+        }
+        let Ok(measure) = head[..3].parse::<u32>() else {
+            continue;
+        };
+        let data = data.trim();
+        let pairs = data.len() / 2;
+        for slot in 0..pairs {
+            let Some(multiplier) = data
+                .get(slot * 2..slot * 2 + 2)
+                .and_then(parse_base36)
+                .filter(|id| *id != 0)
+                .and_then(|id| header.scroll_definitions.get(&id))
+            else {
+                continue;
+            };
+            events.push(ScrollEvent {
+                measure,
+                slot: slot as u32,
+                fraction: slot as f64 / pairs as f64,
+                scroll_multiplier: *multiplier,
+            });
+        }
+    }
+    events.sort_by(|a, b| {
+        a.measure
+            .cmp(&b.measure)
+            .then_with(|| a.fraction.total_cmp(&b.fraction))
+    });
+    events
+}
+
+/// 谱面中出现过的最小与最大滚动速度（包含初始的 1.0）
+pub fn scroll_range(scrolls: &[ScrollEvent]) -> (f64, f64) {
+    scrolls.iter().fold((1.0, 1.0), |(min, max), event| {
+        (
+            f64::min(min, event.scroll_multiplier),
+            f64::max(max, event.scroll_multiplier),
+        )
+    })
+}
+
+/// BMSON 音符
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BmsonNote {
+    /// 轨道（`None` / 0 为 BGM）
+    x: Option<u32>,
+    /// 位置（脉冲）
+    y: u64,
+    /// 长度（脉冲），大于 0 时为长条
+    l: u64,
+}
+
+/// BMSON 音源通道
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BmsonSoundChannel {
+    notes: Vec<BmsonNote>,
+}
+
+/// BMSON 文件中的 `info` 对象（仅包含需要的字段）
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BmsonInfo {
+    title: String,
+    artist: String,
+    genre: String,
+    init_bpm: f64,
+    level: u64,
+    resolution: u64,
+    chart_name: String,
+    mode_hint: String,
+    preview_music: String,
+}
+
+/// BMSON 停止事件
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BmsonStopEvent {
+    y: u64,
+    /// 长度（脉冲）
+    duration: u64,
+}
+
+/// BMSON BPM 变化事件
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BmsonBpmEvent {
+    y: u64,
+    bpm: f64,
+}
+
+/// BMSON BGA 事件
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BmsonBgaEvent {
+    y: u64,
+    id: u32,
+}
+
+/// BMSON 的 `bga` 对象
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BmsonBga {
+    /// BGA 定义（只关心数量）
+    bga_header: Vec<IgnoredAny>,
+    bga_events: Vec<BmsonBgaEvent>,
+    layer_events: Vec<BmsonBgaEvent>,
+    poor_events: Vec<BmsonBgaEvent>,
+}
+
+/// BMSON 文件顶层结构（仅包含需要的字段）
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Bmson {
+    info: BmsonInfo,
+    sound_channels: Vec<BmsonSoundChannel>,
+    bpm_events: Vec<BmsonBpmEvent>,
+    stop_events: Vec<BmsonStopEvent>,
+    bga: BmsonBga,
+}
+
+/// 解析 BMSON（JSON）格式的头部信息
+pub fn parse_bmson_header(content: &[u8]) -> Result<BmsHeader, BmsonError> {
+    let bmson: Bmson = serde_json::from_slice(content)?;
+    Ok(bmson_header(&bmson))
+}
+
+/// 解析 BMSON 的 `sound_channels`，转换为与 BMS 相同的物件列表
+///
+/// 位置以 `info.resolution`（每拍脉冲数）换算为拍，并按 4/4 拍划分小节。
+pub fn parse_bmson_notes(content: &[u8]) -> Result<Vec<NoteObject>, BmsonError> {
+    let bmson: Bmson = serde_json::from_slice(content)?;
+    Ok(bmson_notes(&bmson))
+}
+
+/// 解析 BMSON 的 `bga` 对象
+pub fn parse_bmson_bga(content: &[u8]) -> Result<BgaInfo, BmsonError> {
+    let bmson: Bmson = serde_json::from_slice(content)?;
+    Ok(bmson_bga(&bmson))
+}
+
+/// 解析一个谱面文件（BMS 或 BMSON）的全部信息
+pub fn parse_chart(content: &[u8], is_bmson: bool) -> Result<ParsedChart, BmsonError> {
+    if is_bmson {
+        let bmson: Bmson = serde_json::from_slice(content)?;
+        let header = bmson_header(&bmson);
+        let notes = bmson_notes(&bmson);
+        Ok(ParsedChart {
+            key_mode: detect_key_mode(&notes, header.mode_hint.as_deref()),
+            chart_type: ChartType::from_difficulty(header.difficulty),
+            bpm_timeline: bmson_bpm_timeline(&bmson, &header),
+            stops: bmson_stops(&bmson),
+            scrolls: Vec::new(),
+            measure_lengths: HashMap::new(),
+            bga: bmson_bga(&bmson),
+            header,
+            notes,
+        })
+    } else {
+        let header = parse_bms_header(content);
+        let notes = parse_bms_notes(content);
+        Ok(ParsedChart {
+            bga: bms_bga(&header, &notes),
+            bpm_timeline: bms_bpm_timeline(&header, &notes),
+            stops: bms_stops(&header, &notes),
+            scrolls: parse_scroll_events(content, &header),
+            measure_lengths: parse_measure_lengths(content),
+            key_mode: detect_key_mode(&notes, None),
+            chart_type: ChartType::from_difficulty(header.difficulty),
+            header,
+            notes,
+        })
+    }
+}
+
+/// 由 `#BPM`、`#BPMxx` 定义与 03/08 通道物件构建 BPM 时间线
+pub fn bms_bpm_timeline(header: &BmsHeader, notes: &[NoteObject]) -> BpmTimeline {
+    let changes = notes
+        .iter()
+        .filter(|note| note.note_type == NoteType::BpmChange)
+        .filter_map(|note| {
+            let bpm = if note.channel == 0x03 {
+                note.value as f64
+            } else {
+                *header.bpm_definitions.get(&note.value)?
+            };
+            (bpm > 0.0).then_some(BpmChange {
+                measure: note.measure,
+                fraction: note.fraction,
+                bpm,
+            })
+        })
+        .collect();
+
+    BpmTimeline {
+        initial_bpm: header.bpm.unwrap_or(DEFAULT_BPM),
+        changes,
+    }
+}
+
+/// 由 `#STOPxx` 定义与 09 通道物件提取停止事件
+pub fn bms_stops(header: &BmsHeader, notes: &[NoteObject]) -> Vec<StopEvent> {
+    notes
+        .iter()
+        .filter(|note| note.note_type == NoteType::StopEvent)
+        .filter_map(|note| {
+            let length = *header.stop_definitions.get(&note.value)?;
+            // 1/192 小节 = 1/48 拍
+            (length > 0.0).then_some(StopEvent {
+                measure: note.measure,
+                fraction: note.fraction,
+                beats: length / 48.0,
+            })
+        })
+        .collect()
+}
+
+/// 由 BPM 时间线与停止事件计算前 `total_measures` 个小节的时长（毫秒）
+///
+/// 每段时长为 `小节数 × 每小节拍数 × (60000 / BPM)`，每小节拍数由 `measure_lengths` 决定，
+/// 停止期间谱面不滚动，按停止位置的 BPM 换算后计入总时长。
+pub fn compute_chart_duration_ms(
+    bpm_timeline: &BpmTimeline,
+    total_measures: u32,
+    stops: &[StopEvent],
+    measure_lengths: &HashMap<u32, f64>,
+) -> u64 {
+    let stop_ms: f64 = stops
+        .iter()
+        .filter(|stop| stop.measure < total_measures)
+        .map(|stop| stop.beats * 60000.0 / bpm_timeline.bpm_at(stop.measure, stop.fraction))
+        .sum();
+    (bpm_timeline.duration_ms(total_measures, measure_lengths) + stop_ms).round() as u64
+}
+
+/// 计算 BMS 谱面的峰值 NPS（见 [`peak_nps`]），停止事件由内容中的定义与 09 通道得到
+pub fn compute_peak_nps(content: &[u8], bpm_timeline: &BpmTimeline) -> f64 {
+    let header = parse_bms_header(content);
+    let notes = parse_bms_notes(content);
+    let stops = bms_stops(&header, &notes);
+    peak_nps(
+        &notes,
+        bpm_timeline,
+        &stops,
+        &parse_measure_lengths(content),
+    )
+}
+
+/// 峰值 NPS：任意 1 秒内需要击打的音符数（长条只计起点）的最大值
+///
+/// 音符位置按 BPM 变化与停止换算为绝对时间，停止期间不推进拍数；与停止位于同一拍的音符
+/// 在停止开始前击打。没有可演奏音符时为 `0.0`。
+pub fn peak_nps(
+    notes: &[NoteObject],
+    bpm_timeline: &BpmTimeline,
+    stops: &[StopEvent],
+    measure_lengths: &HashMap<u32, f64>,
+) -> f64 {
+    let mut beats: Vec<f64> = notes
+        .iter()
+        .filter(|note| note.is_playable())
+        .map(|note| note.beat)
+        .collect();
+    beats.sort_by(f64::total_cmp);
+
+    // (拍数, 新的 BPM 或 None 表示停止, 停止的拍数)，同一拍的 BPM 变化先于停止生效
+    let mut events: Vec<(f64, Option<f64>, f64)> = bpm_timeline
+        .changes
+        .iter()
+        .map(|change| {
+            let beat = beats_before(change.measure, change.fraction, measure_lengths);
+            (beat, Some(change.bpm), 0.0)
+        })
+        .chain(stops.iter().map(|stop| {
+            let beat = beats_before(stop.measure, stop.fraction, measure_lengths);
+            (beat, None, stop.beats)
+        }))
+        .collect();
+    events.sort_by(|a, b| {
+        a.0.total_cmp(&b.0)
+            .then_with(|| b.1.is_some().cmp(&a.1.is_some()))
+    });
+
+    let mut times_ms = Vec::with_capacity(beats.len());
+    let (mut position, mut bpm, mut elapsed_ms) = (0.0, bpm_timeline.initial_bpm, 0.0);
+    let mut events = events.into_iter().peekable();
+    for beat in beats {
+        while let Some(&(event_beat, new_bpm, stop_beats)) = events.peek() {
+            let applies = match new_bpm {
+                Some(_) => event_beat <= beat,
+                None => event_beat < beat,
+            };
+            if !applies {
+                break;
+            }
+            elapsed_ms += (event_beat - position) * 60000.0 / bpm;
+            position = event_beat;
+            match new_bpm {
+                Some(new_bpm) => bpm = new_bpm,
+                None => elapsed_ms += stop_beats * 60000.0 / bpm,
+            }
+            events.next();
+        }
+        times_ms.push(elapsed_ms + (beat - position) * 60000.0 / bpm);
+    }
+
+    // 以每个音符为窗口起点统计其后 1 秒内的音符数
+    let mut peak = 0;
+    let mut end = 0;
+    for (start, &start_ms) in times_ms.iter().enumerate() {
+        while end < times_ms.len() && times_ms[end] < start_ms + 1000.0 {
+            end += 1;
+        }
+        peak = peak.max(end - start);
+    }
+    peak as f64
+}
+
+/// 根据使用的通道判断键位模式，BMSON 优先使用 `mode_hint`
+pub fn detect_key_mode(notes: &[NoteObject], mode_hint: Option<&str>) -> KeyMode {
+    match mode_hint {
+        Some("beat-5k") => return KeyMode::Key5,
+        Some("beat-7k") => return KeyMode::Key7,
+        Some("beat-10k") => return KeyMode::Key10,
+        Some("beat-14k") => return KeyMode::Key14,
+        Some("popn-5k" | "popn-9k") => return KeyMode::Key9,
+        _ => (),
+    }
+
+    let mut uses_2p = false;
+    let mut uses_2p_scratch = false;
+    let mut uses_lane_8_9 = false;
+    let mut uses_2p_outer = false;
+    for note in notes
+        .iter()
+        .filter(|note| note.is_playable() || note.note_type == NoteType::Mine)
+    {
+        let lane = note.channel & 0x0F;
+        let is_2p = matches!(note.channel >> 4, 0x2 | 0x6 | 0xE);
+        uses_lane_8_9 |= matches!(lane, 8 | 9);
+        if is_2p {
+            uses_2p = true;
+            uses_2p_scratch |= lane == 6;
+            uses_2p_outer |= matches!(lane, 1 | 7 | 8 | 9);
+        }
+    }
+
+    // PMS：2P 侧只使用 2-5 键，且没有 2P 皿
+    if uses_2p && !uses_2p_scratch && !uses_2p_outer {
+        KeyMode::Key9
+    } else if uses_2p {
+        if uses_lane_8_9 {
+            KeyMode::Key14
+        } else {
+            KeyMode::Key10
+        }
+    } else if uses_lane_8_9 {
+        KeyMode::Key7
+    } else {
+        KeyMode::Key5
+    }
+}
+
+/// 由 BMS 的 `#BMPxx` 定义与 04/06/07 通道物件计算 BGA 信息
+pub fn bms_bga(header: &BmsHeader, notes: &[NoteObject]) -> BgaInfo {
+    let mut layers = [false; 3];
+    let mut events = Vec::new();
+    for note in notes
+        .iter()
+        .filter(|note| note.note_type == NoteType::BgaEvent)
+    {
+        match note.channel {
+            0x04 => {
+                layers[0] = true;
+                events.push(BgaEvent {
+                    time: ((note.measure as f64 + note.fraction) * BGA_PULSES_PER_MEASURE).round()
+                        as u64,
+                    id: note.value as u32,
+                });
+            }
+            0x07 => layers[1] = true,
+            0x06 => layers[2] = true,
+            _ => (),
+        }
+    }
+
+    BgaInfo {
+        has_bga: header.bmp_count > 0 && !events.is_empty(),
+        layer_count: layers.iter().filter(|used| **used).count() as u32,
+        events,
+    }
+}
+
+// 辅助函数：提取 BMSON 头部信息
+fn bmson_header(bmson: &Bmson) -> BmsHeader {
+    BmsHeader {
+        title: non_empty(&bmson.info.title),
+        artist: non_empty(&bmson.info.artist),
+        genre: non_empty(&bmson.info.genre),
+        bpm: Some(bmson.info.init_bpm).filter(|bpm| *bpm > 0.0),
+        play_level: Some(bmson.info.level.min(u8::MAX as u64) as u8),
+        bmp_count: bmson.bga.bga_header.len() as u32,
+        rank: None,
+        difficulty: bmson_difficulty(&bmson.info.chart_name),
+        bpm_definitions: HashMap::new(),
+        stop_definitions: HashMap::new(),
+        scroll_definitions: HashMap::new(),
+        mode_hint: non_empty(&bmson.info.mode_hint),
+        preview: non_empty(&bmson.info.preview_music),
+        material: None,
+        octfp: None,
+        octave: None,
+        cdda_tracks: Vec::new(),
+        has_wavcmd: false,
+    }
+}
+
+// 辅助函数：由 BMSON 的 chart_name 推断难度（1-5）
+fn bmson_difficulty(chart_name: &str) -> Option<u8> {
+    let chart_name = chart_name.to_ascii_uppercase();
+    [
+        ("BEGINNER", 1),
+        ("NORMAL", 2),
+        ("HYPER", 3),
+        ("ANOTHER", 4),
+        ("INSANE", 5),
+        ("LEGGENDARIA", 5),
+    ]
+    .iter()
+    .find(|(name, _)| chart_name.contains(name))
+    .map(|(_, difficulty)| *difficulty)
+}
+
+// 辅助函数：构建 BMSON 的 BPM 时间线
+fn bmson_bpm_timeline(bmson: &Bmson, header: &BmsHeader) -> BpmTimeline {
+    let pulses_per_measure = bmson_pulses_per_measure(bmson);
+    let mut changes: Vec<BpmChange> = bmson
+        .bpm_events
+        .iter()
+        .filter(|event| event.bpm > 0.0)
+        .map(|event| BpmChange {
+            measure: (event.y / pulses_per_measure) as u32,
+            fraction: (event.y % pulses_per_measure) as f64 / pulses_per_measure as f64,
+            bpm: event.bpm,
+        })
+        .collect();
+    changes.sort_by(|a, b| {
+        a.measure
+            .cmp(&b.measure)
+            .then_with(|| a.fraction.total_cmp(&b.fraction))
+    });
+
+    BpmTimeline {
+        initial_bpm: header.bpm.unwrap_or(DEFAULT_BPM),
+        changes,
+    }
+}
+
+// 辅助函数：提取 BMSON 的停止事件
+fn bmson_stops(bmson: &Bmson) -> Vec<StopEvent> {
+    let resolution = bmson_resolution(bmson);
+    let pulses_per_measure = bmson_pulses_per_measure(bmson);
+    bmson
+        .stop_events
+        .iter()
+        .filter(|event| event.duration > 0)
+        .map(|event| StopEvent {
+            measure: (event.y / pulses_per_measure) as u32,
+            fraction: (event.y % pulses_per_measure) as f64 / pulses_per_measure as f64,
+            beats: event.duration as f64 / resolution as f64,
+        })
+        .collect()
+}
+
+// 辅助函数：记录 `#BPMxx` / `#EXBPMxx` 定义
+fn insert_bpm_definition(header: &mut BmsHeader, id: &str, value: &str) {
+    if let (Some(id), Ok(bpm)) = (parse_base36(id), value.parse::<f64>()) {
+        header.bpm_definitions.insert(id, bpm);
+    }
+}
+
+// 辅助函数：提取 BMSON 的 BGA 信息，时间换算为每拍 240 脉冲
+fn bmson_bga(bmson: &Bmson) -> BgaInfo {
+    let resolution = bmson_resolution(bmson);
+    let bga = &bmson.bga;
+    let events: Vec<BgaEvent> = bga
+        .bga_events
+        .iter()
+        .map(|event| BgaEvent {
+            time: event.y.saturating_mul(DEFAULT_BMSON_RESOLUTION) / resolution,
+            id: event.id,
+        })
+        .collect();
+    let layer_count = [&bga.bga_events, &bga.layer_events, &bga.poor_events]
+        .iter()
+        .filter(|layer| !layer.is_empty())
+        .count() as u32;
+
+    BgaInfo {
+        has_bga: !bga.bga_header.is_empty() && !events.is_empty(),
+        layer_count,
+        events,
+    }
+}
+
+// 辅助函数：BMSON 的分辨率（每拍脉冲数）
+fn bmson_resolution(bmson: &Bmson) -> u64 {
+    match bmson.info.resolution {
+        0 => DEFAULT_BMSON_RESOLUTION,
+        resolution => resolution,
+    }
+}
+
+// 辅助函数：BMSON 每小节（4 拍）的脉冲数。分辨率来自文件，按饱和运算避免溢出
+fn bmson_pulses_per_measure(bmson: &Bmson) -> u64 {
+    bmson_resolution(bmson).saturating_mul(4)
+}
+
+// 辅助函数：将 BMSON 的 sound_channels 转换为物件列表
+fn bmson_notes(bmson: &Bmson) -> Vec<NoteObject> {
+    let resolution = bmson_resolution(bmson);
+    let pulses_per_measure = bmson_pulses_per_measure(bmson);
+    let position = |pulse: u64| {
+        (
+            (pulse / pulses_per_measure) as u32,
+            (pulse % pulses_per_measure) as f64 / pulses_per_measure as f64,
+            pulse as f64 / resolution as f64,
+        )
+    };
+
+    let mut notes = Vec::new();
+    for (index, sound_channel) in bmson.sound_channels.iter().enumerate() {
+        let value = (index + 1).min(u16::MAX as usize) as u16;
+        for note in &sound_channel.notes {
+            let Some(channel) = note.x.and_then(bmson_lane_to_channel) else {
+                continue;
+            };
+            let (measure, fraction, beat) = position(note.y);
+            if note.l == 0 {
+                let note_type = if channel & 0x0F == 6 {
+                    NoteType::Scratch
+                } else {
+                    NoteType::Normal
+                };
+                notes.push(NoteObject {
+                    measure,
+                    fraction,
+                    beat,
+                    channel,
+                    value,
+                    note_type,
+                });
+            } else {
+                // 长条映射到 5x/6x 通道
+                let channel = channel + 0x40;
+                let (end_measure, end_fraction, end_beat) = position(note.y.saturating_add(note.l));
+                notes.push(NoteObject {
+                    measure,
+                    fraction,
+                    beat,
+                    channel,
+                    value,
+                    note_type: NoteType::LNStart,
+                });
+                notes.push(NoteObject {
+                    measure: end_measure,
+                    fraction: end_fraction,
+                    beat: end_beat,
+                    channel,
+                    value,
+                    note_type: NoteType::LNEnd,
+                });
+            }
+        }
+    }
+
+    notes.sort_by(|a, b| {
+        a.measure
+            .cmp(&b.measure)
+            .then_with(|| a.fraction.total_cmp(&b.fraction))
+            .then_with(|| a.channel.cmp(&b.channel))
+    });
+    notes
+}
+
+// 辅助函数：拆分数据行 `mmmcc:data`，返回 (小节, 通道, 数据)
+fn split_data_line(line: &str) -> Option<(u32, u8, &str)> {
+    let (head, data) = line.split_once(':')?;
+    if head.len() != 5 || !head.is_ascii() {
+        return None;
+    }
+    let measure = head[..3].parse().ok()?;
+    let channel = u8::from_str_radix(&head[3..], 16).ok()?;
+    Some((measure, channel, data.trim()))
+}
+
+// 辅助函数：按通道确定物件类型，不关心的通道返回 None
+fn note_type_for_channel(channel: u8) -> Option<NoteType> {
+    let lane = channel & 0x0F;
+    match channel >> 4 {
+        0x1 | 0x2 if (1..=9).contains(&lane) => Some(if lane == 6 {
+            NoteType::Scratch
+        } else {
+            NoteType::Normal
+        }),
+        // 起止在 resolve_long_notes 中确定
+        0x5 | 0x6 if (1..=9).contains(&lane) => Some(NoteType::LNStart),
+        0xD | 0xE if (1..=9).contains(&lane) => Some(NoteType::Mine),
+        0x0 => match channel {
+            0x03 | 0x08 => Some(NoteType::BpmChange),
+            0x09 => Some(NoteType::StopEvent),
+            0x04 | 0x06 | 0x07 => Some(NoteType::BgaEvent),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// 辅助函数：确定长条的起止（notes 需已按时间排序）
+fn resolve_long_notes(notes: &mut [NoteObject], lnobj: Option<u16>) {
+    // 每个通道上一个尚未配对的长条起点 / 上一个普通音符的下标
+    let mut open_ln: [bool; 256] = [false; 256];
+    let mut last_note: [Option<usize>; 256] = [None; 256];
+
+    for index in 0..notes.len() {
+        let channel = notes[index].channel as usize;
+        match notes[index].note_type {
+            NoteType::LNStart => {
+                if open_ln[channel] {
+                    notes[index].note_type = NoteType::LNEnd;
+                }
+                open_ln[channel] = !open_ln[channel];
+            }
+            NoteType::Normal | NoteType::Scratch => {
+                if lnobj == Some(notes[index].value) {
+                    if let Some(start) = last_note[channel].take() {
+                        notes[start].note_type = NoteType::LNStart;
+                        notes[index].note_type = NoteType::LNEnd;
+                        continue;
+                    }
+                }
+                last_note[channel] = Some(index);
+            }
+            _ => (),
+        }
+    }
+}
+
+// 辅助函数：解析 36 进制的两位定义编号
+fn parse_base36(pair: &str) -> Option<u16> {
+    u16::from_str_radix(pair, 36).ok()
+}
+
+// 辅助函数：BMSON 轨道编号转换为 BMS 通道（beat 模式：1-7 键 + 8 皿，9-16 为 2P）
+fn bmson_lane_to_channel(lane: u32) -> Option<u8> {
+    const LANES: [u8; 8] = [0x11, 0x12, 0x13, 0x14, 0x15, 0x18, 0x19, 0x16];
+    match lane {
+        1..=8 => Some(LANES[lane as usize - 1]),
+        9..=16 => Some(LANES[lane as usize - 9] + 0x10),
+        _ => None,
+    }
+}
+
+/// 通道所属的玩家侧（1P 为 1，2P 为 2）
+pub fn lane_side(channel: u8) -> u8 {
+    match channel >> 4 {
+        0x2 | 0x6 | 0xE => 2,
+        _ => 1,
+    }
+}
+
+// 辅助函数：空字符串视为未定义
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1P 的 1、2 键与皿各一个音符、3 键一个长条，另有一个 BGM（x 为 0）
+    const BMSON_FIXTURE: &str = r#"{
+        "info": {
+            "title": "Fixture",
+            "artist": "Tester",
+            "init_bpm": 150,
+            "resolution": 240,
+            "chart_name": "HYPER",
+            "mode_hint": "beat-7k"
+        },
+        "sound_channels": [
+            { "notes": [ { "x": 1, "y": 0, "l": 0 }, { "x": 0, "y": 0, "l": 0 } ] },
+            { "notes": [ { "x": 2, "y": 240, "l": 0 }, { "x": 8, "y": 480, "l": 0 } ] },
+            { "notes": [ { "x": 3, "y": 960, "l": 480 } ] }
+        ]
+    }"#;
+
+    #[test]
+    fn parses_bms_header() {
+        let header = parse_bms_header(
+            b"#TITLE  Song \n#ARTIST Someone\n#GENRE \n#BPM 150\n#PLAYLEVEL 12\n\
+              #DIFFICULTY 4\n#BPM01 200\n#EXBPM02 75.5\n#STOP01 96\n",
+        );
+        assert_eq!(header.title.as_deref(), Some("Song"));
+        assert_eq!(header.artist.as_deref(), Some("Someone"));
+        assert_eq!(header.genre, None);
+        assert_eq!(header.bpm, Some(150.0));
+        assert_eq!(header.play_level, Some(12));
+        assert_eq!(header.difficulty, Some(4));
+        assert_eq!(header.bpm_definitions.get(&1), Some(&200.0));
+        assert_eq!(header.bpm_definitions.get(&2), Some(&75.5));
+        assert_eq!(header.stop_definitions.get(&1), Some(&96.0));
+    }
+
+    #[test]
+    fn parses_bms_notes_and_long_notes() {
+        let notes = parse_bms_notes(
+            b"#LNOBJ ZZ\n#00111:0100\n#00116:01\n#00151:0101\n#00212:01ZZ\n#00101:01\n",
+        );
+        let counts = count_notes(&notes);
+        assert_eq!(counts.note_count, 4);
+        assert_eq!(counts.ln_count, 2);
+        assert_eq!(counts.scratch_count, 1);
+        // BGM 通道不计入物件
+        assert!(notes.iter().all(|note| note.channel != 0x01));
+        assert_eq!(
+            notes
+                .iter()
+                .filter(|note| note.note_type == NoteType::LNEnd)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn counts_bmson_fixture_notes() {
+        let chart = parse_chart(BMSON_FIXTURE.as_bytes(), true).unwrap();
+        let counts = count_notes(&chart.notes);
+        assert_eq!(counts.note_count, 4);
+        assert_eq!(counts.ln_count, 1);
+        assert_eq!(counts.scratch_count, 1);
+        assert_eq!(chart.header.title.as_deref(), Some("Fixture"));
+        assert_eq!(chart.header.bpm, Some(150.0));
+        assert_eq!(chart.key_mode, KeyMode::Key7);
+        assert_eq!(chart.chart_type, ChartType::Hyper);
+
+        // 长条终点位于 960 + 480 脉冲，即第 1 小节的一半
+        let ln_end = chart
+            .notes
+            .iter()
+            .find(|note| note.note_type == NoteType::LNEnd)
+            .unwrap();
+        assert_eq!(
+            (ln_end.measure, ln_end.fraction, ln_end.beat),
+            (1, 0.5, 6.0)
+        );
+    }
+
+    #[test]
+    fn bmson_extreme_positions_do_not_overflow() {
+        let content = format!(
+            r#"{{
+                "info": {{ "resolution": {max} }},
+                "sound_channels": [ {{ "notes": [ {{ "x": 1, "y": {max}, "l": {max} }} ] }} ],
+                "bpm_events": [ {{ "y": {max}, "bpm": 200 }} ],
+                "bga": {{ "bga_events": [ {{ "y": {max}, "id": 1 }} ] }}
+            }}"#,
+            max = u64::MAX
+        );
+        let chart = parse_chart(content.as_bytes(), true).unwrap();
+        assert_eq!(count_notes(&chart.notes).ln_count, 1);
+        assert_eq!(chart.bpm_timeline.changes.len(), 1);
+        assert_eq!(chart.bga.events.len(), 1);
+    }
+
+    #[test]
+    fn effective_bpm_follows_most_notes() {
+        // 第 0 小节 100 BPM 下 1 个音符，第 1 小节起 200 BPM 下 8 个音符
+        let chart = parse_chart(
+            b"#BPM 100\n#BPM01 200\n#00011:01\n#00108:01\n#00111:01010101\n#00211:01010101\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(chart.bpm_timeline.min_bpm(), 100.0);
+        assert_eq!(chart.bpm_timeline.max_bpm(), 200.0);
+        let effective_bpm = chart.bpm_timeline.effective_bpm(&chart.notes).unwrap();
+        assert!((effective_bpm - 200.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn effective_bpm_prefers_higher_bpm_on_tie() {
+        let chart = parse_chart(b"#BPM 100\n#00011:01\n#00103:C8\n#00111:01\n", false).unwrap();
+        assert_eq!(chart.bpm_timeline.effective_bpm(&chart.notes), Some(200.0));
+    }
+
+    #[test]
+    fn effective_bpm_is_none_without_playable_notes() {
+        let chart = parse_chart(b"#BPM 100\n#00101:01\n", false).unwrap();
+        assert_eq!(chart.bpm_timeline.effective_bpm(&chart.notes), None);
+    }
+
+    #[test]
+    fn fingerprint_ignores_formatting() {
+        let original = compute_chart_fingerprint(
+            b"#TITLE A\n#BPM 150\n#WAV01 a.wav\n#00112:01\n#00151:01000100\n",
+        );
+        // 头部顺序、空白、注释、#WAVxx 编号不同，长条改用 #LNOBJ 写法
+        let reformatted = compute_chart_fingerprint(
+            b"*comment\n#WAV0Z a.wav\n  #BPM 150  \n#TITLE B\n#LNOBJ ZZ\n\
+              #00112:0Z\n#00111:0Z00ZZ00\n",
+        );
+        assert_eq!(original, reformatted);
+        assert_eq!(
+            original,
+            compute_chart_fingerprint(
+                b"#TITLE A\n#BPM 150\n#WAV01 a.wav\n#00112:01\n#00151:01000100\n"
+            )
+        );
+    }
+
+    #[test]
+    fn fingerprint_changes_with_notes_and_bpm() {
+        let original = compute_chart_fingerprint(b"#BPM 150\n#00112:01\n");
+        assert_ne!(
+            original,
+            compute_chart_fingerprint(b"#BPM 150\n#00112:0001\n")
+        );
+        assert_ne!(
+            original,
+            compute_chart_fingerprint(b"#BPM 150\n#00113:01\n")
+        );
+        assert_ne!(
+            original,
+            compute_chart_fingerprint(b"#BPM 160\n#00112:01\n")
+        );
+    }
+
+    #[test]
+    fn invalid_bmson_is_an_error() {
+        assert!(parse_chart(b"not json", true).is_err());
+    }
+}
@@ -1,7 +1,11 @@
 //! 本模块用于快速扫描指定目录下特定类型的音游谱面文件
 //!
+//! 常用的类型与函数可通过 [`prelude`] 一次导入；只需默认配置时可使用 [`scan_simple`]。
+//!
 //! 基本用法：
 //! ```rust
+//! use bms_scan::prelude::*;
+//!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let target_dir = std::env::args().nth(1).expect("请提供要扫描的目录路径");
@@ -11,7 +15,8 @@
 //!     println!("目录所在存储介质类型: {:?}", storage_type);
 //!
 //!     let start_time = SystemTime::now();
-//!     let handle = scan_directory_recursive(target_path, storage_type).await?;
+//!     let handle =
+//!         scan_directory_recursive(target_path, storage_type, ScanConfig::default()).await?;
 //!     println!("扫描已启动，正在实时收集结果...");
 //!
 //!     // 实时处理结果示例
@@ -25,8 +30,8 @@
 //!                     total_files += 1;
 //!                     println!(
 //!                         "[实时更新] 发现文件: {} (大小: {}字节)",
-//!                         file_info.relative_path.display(),
-//!                         file_info.content.len()
+//!                         file_info.relative_path().display(),
+//!                         file_info.content_or_empty().len()
 //!                     );
 //!                 }
 //!             }
@@ -55,44 +60,492 @@
 //! ```
 
 use std::{
+    collections::{BinaryHeap, HashSet, VecDeque},
+    fmt,
+    io::SeekFrom,
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
     },
-    time::SystemTime,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crossbeam::queue::SegQueue;
+use dashmap::DashSet;
+use futures::{future, Stream};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use sysinfo::{DiskKind, Disks};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
 use tokio::{
     fs,
+    io::{AsyncReadExt, AsyncSeekExt},
     sync::{Notify, Semaphore},
 };
+use tracing::{field, Instrument};
+
+use crate::storage::{self, StorageType};
 
 /// 需要扫描的目标文件扩展名列表
-const TARGET_EXTS: [&str; 5] = ["bms", "bme", "bml", "pms", "bmson"];
+pub const TARGET_EXTS: [&str; 5] = ["bms", "bme", "bml", "pms", "bmson"];
+
+/// 重复文件（SHA256 相同）的处理策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DedupStrategy {
+    /// 允许重复，全部导入
+    #[default]
+    AllowDuplicates,
+    /// 跳过已存在的文件
+    SkipDuplicates,
+    /// 新文件修改时间较新时替换已存在的曲目，否则跳过
+    OverwriteWithNewer,
+}
+
+/// 目录的遍历顺序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScanOrder {
+    /// 深度优先：先扫描完一个子目录树再扫描同级目录
+    DepthFirst,
+    /// 广度优先：浅层目录中的文件先出现在结果队列中
+    #[default]
+    BreadthFirst,
+}
 
-/// 存储介质类型枚举
-#[derive(Debug, Clone, Copy)]
+/// 目录的扫描优先级
 #[allow(clippy::upper_case_acronyms)]
-#[allow(dead_code)]
-pub enum StorageType {
-    SSD,
-    HDD,
-    Unknown(isize),
-    Failed,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScanPriority {
+    /// 按 [`ScanConfig::scan_order`] 的顺序扫描
+    #[default]
+    FIFO,
+    /// 修改时间较新的目录先扫描（忽略 `scan_order`），便于尽快看到新加入的谱面
+    MostRecentFirst,
+}
+
+/// 扫描模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScanMode {
+    /// 读取文件内容并计算 SHA256
+    #[default]
+    FullContent,
+    /// 只收集路径与文件大小，内容可之后通过 [`FileInfo::load_content`] 按需读取
+    MetadataOnly,
+}
+
+/// 扫描配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// 重复文件的处理策略
+    pub dedup_strategy: DedupStrategy,
+    /// SSD（以及网络存储）上的并发读取数
+    pub ssd_permits: usize,
+    /// HDD 及未知介质上的并发读取数
+    pub hdd_permits: usize,
+    /// 目录的遍历顺序
+    pub scan_order: ScanOrder,
+    /// 目录的扫描优先级
+    pub scan_priority: ScanPriority,
+    /// 是否读取文件内容
+    pub scan_mode: ScanMode,
+    /// HDD 上每次读取文件前的等待时间（毫秒），让磁盘合并连续读取、减少寻道。
+    /// 为 `None` 时 HDD 使用 2 毫秒，`Some(0)` 表示不等待
+    pub read_delay_ms: Option<u64>,
+    /// HDD 上同时进行的文件读取数
+    pub read_queue_depth: usize,
+    /// 计算哈希后重新读取文件中随机的 4 KiB 进行校验，用于发现读取时的静默损坏。
+    /// 为 `None` 时仅在 HDD 上校验
+    pub verify_reads: Option<bool>,
+    /// 校验失败时重新读取整个文件的最大次数
+    pub read_retry_count: u8,
+    /// 遍历时跳过以 `.` 开头的文件与目录
+    pub skip_hidden: bool,
+    /// 最大遍历深度（根目录下的条目深度为 1），`None` 表示不限制
+    pub max_depth: Option<usize>,
+    /// 是否跟随指向目录的符号链接
+    pub follow_symlinks: bool,
+    /// 除 [`TARGET_EXTS`] 外额外扫描的扩展名（不含 `.`，忽略大小写）
+    pub extra_extensions: Vec<String>,
+    /// 正式扫描前先遍历一遍目录并读取文件元数据，预热系统的元数据缓存，
+    /// 同时得到 [`ScanHandle::total_files`] 与 [`ScanHandle::total_bytes`]
+    pub warmup: bool,
+    /// 曲目 ID 计数器的起始值：加载配置或更新配置时，计数器至少从该值开始分配。
+    /// 导入时曲目按路径排序后再分配 ID，因此同一起始值下对同一目录的扫描得到相同的 ID
+    pub id_start: Option<u32>,
+    /// 监视目录时，收到第一个变更后继续收集其余变更的时长（毫秒）
+    pub debounce_ms: u64,
+    /// 遍历时跳过的目录，glob 语法，与相对于扫描根目录的路径（以 `/` 分隔）匹配，
+    /// 如 `**/__MACOSX/**`、`**/_backup`
+    pub exclude_patterns: Vec<String>,
+    /// 每次导入（`add_directory`、`confirm_import`、监视目录的自动导入等）完成后在后台检查
+    /// 曲库一致性，发现问题时自动重建索引
+    pub auto_consistency_check: bool,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            dedup_strategy: DedupStrategy::default(),
+            ssd_permits: 16,
+            hdd_permits: 1,
+            scan_order: ScanOrder::default(),
+            scan_priority: ScanPriority::default(),
+            scan_mode: ScanMode::default(),
+            read_delay_ms: None,
+            read_queue_depth: 1,
+            verify_reads: None,
+            read_retry_count: 2,
+            skip_hidden: false,
+            max_depth: None,
+            follow_symlinks: false,
+            extra_extensions: Vec::new(),
+            warmup: false,
+            id_start: None,
+            debounce_ms: 500,
+            exclude_patterns: Vec::new(),
+            auto_consistency_check: true,
+        }
+    }
+}
+
+impl ScanConfig {
+    /// 根据存储介质调整并发数：网络存储限制为较低的并发，避免占满带宽
+    /// HDD 上未设置读取等待时间时使用默认的 2 毫秒，未设置读取校验时开启校验
+    pub fn adjust_for_storage(&mut self, storage_type: StorageType) {
+        if storage_type == StorageType::Network {
+            self.ssd_permits = 4;
+            self.hdd_permits = 2;
+        }
+        if storage_type == StorageType::HDD {
+            self.read_delay_ms.get_or_insert(2);
+            self.verify_reads.get_or_insert(true);
+        }
+    }
+
+    /// 扩展名是否为需要扫描的类型（[`TARGET_EXTS`] 或 `extra_extensions`，忽略大小写）
+    pub fn is_target_extension(&self, ext: &str) -> bool {
+        let ext = ext.to_ascii_lowercase();
+        TARGET_EXTS.contains(&ext.as_str())
+            || self
+                .extra_extensions
+                .iter()
+                .any(|extra| extra.eq_ignore_ascii_case(&ext))
+    }
+
+    /// 文件是否为需要扫描的类型
+    pub fn is_target_file(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.is_target_extension(ext))
+    }
+
+    /// 目录是否被 `exclude_patterns` 排除，`relative` 为相对于扫描根目录的路径。
+    /// 同时以带结尾 `/` 的形式匹配，使 `**/_backup/**` 也能排除 `_backup` 目录本身
+    pub fn is_excluded_dir(&self, relative: &Path) -> bool {
+        if self.exclude_patterns.is_empty() {
+            return false;
+        }
+        let path = relative.to_string_lossy().replace('\\', "/");
+        let with_slash = format!("{}/", path);
+        self.exclude_patterns
+            .iter()
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .any(|pattern| pattern.matches(&path) || pattern.matches(&with_slash))
+    }
+
+    /// 指定存储介质上的并发读取数
+    pub fn permits_for(&self, storage_type: StorageType) -> usize {
+        match storage_type {
+            StorageType::SSD | StorageType::Network => self.ssd_permits,
+            StorageType::HDD | StorageType::Unknown(_) | StorageType::Failed => self.hdd_permits,
+        }
+        .max(1)
+    }
+}
+
+/// [`ScanConfig::ssd_permits`] 的允许范围
+pub const SSD_PERMITS_RANGE: std::ops::RangeInclusive<usize> = 1..=128;
+
+/// [`ScanConfig::read_delay_ms`] 的最大值
+pub const MAX_READ_DELAY_MS: u64 = 1000;
+
+/// 扫描配置中违反约束的一项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigError {
+    /// 字段名
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// 检查扫描配置，返回全部违反的约束（而不是只返回第一个）
+pub fn validate_scan_config(config: &ScanConfig) -> Result<(), Vec<ConfigError>> {
+    let mut errors = Vec::new();
+    let mut violation = |field: &str, message: String| {
+        errors.push(ConfigError {
+            field: field.to_string(),
+            message,
+        })
+    };
+
+    if !SSD_PERMITS_RANGE.contains(&config.ssd_permits) {
+        violation(
+            "ssd_permits",
+            format!(
+                "must be between {} and {}, got {}",
+                SSD_PERMITS_RANGE.start(),
+                SSD_PERMITS_RANGE.end(),
+                config.ssd_permits
+            ),
+        );
+    }
+    if config.max_depth == Some(0) {
+        violation("max_depth", "must be at least 1 when set".to_string());
+    }
+    if let Some(read_delay_ms) = config.read_delay_ms.filter(|&ms| ms > MAX_READ_DELAY_MS) {
+        violation(
+            "read_delay_ms",
+            format!(
+                "must be at most {}, got {}",
+                MAX_READ_DELAY_MS, read_delay_ms
+            ),
+        );
+    }
+
+    for pattern in &config.exclude_patterns {
+        if let Err(e) = glob::Pattern::new(pattern) {
+            violation(
+                "exclude_patterns",
+                format!("invalid pattern {:?}: {}", pattern, e),
+            );
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
 /// 文件信息封装结构
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct FileInfo {
-    absolute_path: PathBuf,  // 绝对路径
-    relative_path: PathBuf,  // 相对于搜索目录的路径
-    content: Arc<Box<[u8]>>, // 文件内容
-    sha256: [u8; 32],        // SHA256哈希值
+    absolute_path: PathBuf,              // 绝对路径
+    relative_path: PathBuf,              // 相对于搜索目录的路径
+    pub size: u64,                       // 文件大小（字节）
+    pub content: Option<Arc<Box<[u8]>>>, // 文件内容（未读取时为 None）
+    pub sha256: [u8; 32],                // SHA256哈希值（未读取时为全 0）
+    pub integrity_warning: bool,         // 读取校验在全部重试后仍失败
+}
+
+impl FileInfo {
+    /// 绝对路径
+    pub fn absolute_path(&self) -> &Path {
+        &self.absolute_path
+    }
+
+    /// 相对于搜索目录的路径
+    pub fn relative_path(&self) -> &Path {
+        &self.relative_path
+    }
+
+    /// 相对路径的字符串形式，路径不是合法 UTF-8 时为 `None`
+    pub fn relative_path_str(&self) -> Option<&str> {
+        self.relative_path.to_str()
+    }
+
+    /// 以 `/` 分隔的相对路径（Windows 上的 `\` 会被替换），非 UTF-8 部分按有损方式转换
+    pub fn relative_path_unix_str(&self) -> String {
+        self.relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// 读取文件内容并计算 SHA256，已读取时不做任何事
+    pub async fn load_content(&mut self, semaphore: Arc<Semaphore>) -> std::io::Result<()> {
+        if self.content.is_some() {
+            return Ok(());
+        }
+        let read_limit = ReadLimit {
+            semaphore,
+            delay: None,
+            verify: false,
+            retry_count: 0,
+            blocking_micros: Arc::new(AtomicU64::new(0)),
+            errors: Arc::new(SegQueue::new()),
+        };
+        let (content, sha256, verified) = read_and_hash(&self.absolute_path, &read_limit).await?;
+        self.size = content.len() as u64;
+        self.content = Some(content);
+        self.sha256 = sha256;
+        self.integrity_warning = !verified;
+        Ok(())
+    }
+
+    /// 文件大小（字节）：已读取内容时为内容长度，否则查询文件元数据
+    pub fn size_bytes(&self) -> u64 {
+        match &self.content {
+            Some(content) => content.len() as u64,
+            None => std::fs::metadata(&self.absolute_path)
+                .map(|m| m.len())
+                .unwrap_or(0),
+        }
+    }
+
+    /// 文件内容，未读取时为空切片
+    pub fn content_or_empty(&self) -> &[u8] {
+        match &self.content {
+            Some(content) => content,
+            None => &[],
+        }
+    }
+
+    /// SHA256 的十六进制字符串形式
+    pub fn sha256_hex(&self) -> String {
+        self.sha256
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, sha256: ",
+            self.relative_path.display(),
+            format_size(self.size_bytes())
+        )?;
+        for byte in &self.sha256[..4] {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, "...)")
+    }
+}
+
+// 辅助函数：将字节数格式化为便于阅读的形式（如 `1.5 MiB`）
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// 一次完整扫描的统计信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanSummary {
+    pub root_path: String,
+    /// 扫描完成时间（Unix 时间戳，秒）
+    pub timestamp: u64,
+    pub total_files_found: u64,
+    pub total_bytes_read: u64,
+    pub elapsed_seconds: f64,
+    /// 读取失败的文件数
+    pub errors_count: u64,
+    pub storage_type: StorageType,
+    pub worker_count: usize,
+    /// 扫描期间采样到的进程内存峰值（KiB），见 [`MEMORY_SAMPLE_INTERVAL`]
+    pub peak_memory_kb: u64,
+    /// 读取的文件内容总量（KiB）
+    pub total_disk_reads_kb: u64,
+    /// 阻塞任务（计算 SHA256）的总用时（毫秒）
+    pub cpu_time_ms: u64,
+}
+
+/// 扫描期间采样进程内存占用的间隔
+pub const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 工作线程共享的计数器
+#[derive(Debug, Default)]
+struct ScanStats {
+    files_found: AtomicU64,
+    bytes_read: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// 待扫描目录队列
+///
+/// `SegQueue` 为先进先出，无法实现深度优先与按修改时间排序，因此各种顺序都使用加锁的容器。
+#[derive(Debug)]
+enum DirQueue {
+    /// 栈（后进先出）
+    Stack(Mutex<Vec<PathBuf>>),
+    /// 队列（尾部入队，头部出队）
+    Fifo(Mutex<VecDeque<PathBuf>>),
+    /// 按修改时间排序的堆（最新的先出）
+    ByMtime(Mutex<BinaryHeap<(SystemTime, PathBuf)>>),
+}
+
+impl DirQueue {
+    fn new(scan_order: ScanOrder, scan_priority: ScanPriority) -> Self {
+        match (scan_priority, scan_order) {
+            (ScanPriority::MostRecentFirst, _) => DirQueue::ByMtime(Mutex::new(BinaryHeap::new())),
+            (ScanPriority::FIFO, ScanOrder::DepthFirst) => DirQueue::Stack(Mutex::new(Vec::new())),
+            (ScanPriority::FIFO, ScanOrder::BreadthFirst) => {
+                DirQueue::Fifo(Mutex::new(VecDeque::new()))
+            }
+        }
+    }
+
+    /// 加入一个目录的全部子目录（深度优先时保持原顺序出栈）。
+    /// 按修改时间排序时先读取各目录的修改时间（无法读取时排在最后）
+    async fn push_all(&self, dirs: Vec<PathBuf>) {
+        match self {
+            DirQueue::Stack(stack) => stack.lock().unwrap().extend(dirs.into_iter().rev()),
+            DirQueue::Fifo(queue) => queue.lock().unwrap().extend(dirs),
+            DirQueue::ByMtime(heap) => {
+                let mut entries = Vec::with_capacity(dirs.len());
+                for dir in dirs {
+                    let modified = fs::metadata(&dir)
+                        .await
+                        .and_then(|metadata| metadata.modified())
+                        .unwrap_or(UNIX_EPOCH);
+                    entries.push((modified, dir));
+                }
+                heap.lock().unwrap().extend(entries);
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<PathBuf> {
+        match self {
+            DirQueue::Stack(stack) => stack.lock().unwrap().pop(),
+            DirQueue::Fifo(queue) => queue.lock().unwrap().pop_front(),
+            DirQueue::ByMtime(heap) => heap.lock().unwrap().pop().map(|(_, dir)| dir),
+        }
+    }
+}
+
+/// 扫描中遇到的非致命错误的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ScanErrorKind {
+    /// 读取后的抽查校验失败，读到的内容可能已损坏
+    CorruptRead,
+}
+
+/// 扫描中遇到的非致命错误，文件仍会出现在结果中
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub path: PathBuf,
+    /// 出错时的读取次序（首次读取为 0）
+    pub attempt: u8,
 }
 
 /// 扫描结果句柄结构体
@@ -104,35 +557,350 @@ pub struct ScanHandle {
     pub notify: Arc<Notify>,
     /// 扫描完成标记（原子布尔值）
     pub is_completed: Arc<AtomicBool>,
+    /// 取消标记，工作线程在处理下一个目录前检查
+    cancelled: Arc<AtomicBool>,
+    /// 扫描统计（在设置 `is_completed` 之前写入，取消的扫描也会写入）
+    pub summary: Arc<OnceLock<ScanSummary>>,
+    /// 预热阶段统计的文件总数（未开启预热或预热未完成时为 0）
+    pub total_files: Arc<AtomicU64>,
+    /// 预热阶段统计的文件总大小（字节）
+    pub total_bytes: Arc<AtomicU64>,
+    /// 扫描中遇到的非致命错误（如 [`ScanErrorKind::CorruptRead`]），按发生先后排列
+    pub errors: Arc<SegQueue<ScanError>>,
+}
+
+impl fmt::Display for ScanHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.is_completed.load(Ordering::Acquire) {
+            "completed"
+        } else {
+            "scanning"
+        };
+        write!(f, "ScanHandle ({} queued, {})", self.queue.len(), status)
+    }
+}
+
+/// [`ScanHandle::timeout`] 的结果
+#[derive(Debug)]
+pub enum ScanHandleResult {
+    /// 扫描在时限内完成
+    Completed { results: Vec<FileInfo> },
+    /// 扫描超时被取消，只包含已找到的文件
+    TimedOut { partial_results: Vec<FileInfo> },
+}
+
+impl ScanHandle {
+    /// 取消扫描：工作线程处理完当前目录后停止
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// 收集扫描结果，直到扫描完成或超过 `duration`（超时后取消扫描）
+    pub async fn timeout(self, duration: Duration) -> ScanHandleResult {
+        let mut results = Vec::new();
+        let completed = tokio::time::timeout(duration, self.collect_into(&mut results))
+            .await
+            .is_ok();
+        if completed {
+            return ScanHandleResult::Completed { results };
+        }
+
+        self.cancel();
+        results.extend(self.drain_available());
+        ScanHandleResult::TimedOut {
+            partial_results: results,
+        }
+    }
+
+    /// 立即取出结果队列中当前可用的全部元素，不等待扫描完成
+    pub fn drain_available(&self) -> Vec<FileInfo> {
+        let mut results = Vec::with_capacity(self.queue.len());
+        while let Some(file_info) = self.queue.pop() {
+            results.push(file_info);
+        }
+        results
+    }
+
+    /// 等待新结果的通知，然后最多取出 `max` 个元素。
+    /// 队列中已有结果或扫描已完成时不会等待
+    pub async fn drain_next_batch(&self, max: usize) -> Vec<FileInfo> {
+        if self.queue.is_empty() && !self.is_completed.load(Ordering::Acquire) {
+            self.notify.notified().await;
+        }
+        let mut results = Vec::new();
+        while results.len() < max {
+            let Some(file_info) = self.queue.pop() else {
+                break;
+            };
+            results.push(file_info);
+        }
+        results
+    }
+
+    /// 合并两个扫描句柄：后台任务把两者的结果转入新的队列，两者都完成并取空后
+    /// 新句柄才标记完成。取消新句柄会同时取消两个源扫描；新句柄不包含扫描统计
+    pub fn merge(a: ScanHandle, b: ScanHandle) -> ScanHandle {
+        let merged = ScanHandle {
+            queue: Arc::new(SegQueue::new()),
+            notify: Arc::new(Notify::new()),
+            is_completed: Arc::new(AtomicBool::new(false)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            summary: Arc::new(OnceLock::new()),
+            total_files: Arc::new(AtomicU64::new(0)),
+            total_bytes: Arc::new(AtomicU64::new(0)),
+            errors: Arc::new(SegQueue::new()),
+        };
+
+        let queue = merged.queue.clone();
+        let notify = merged.notify.clone();
+        let is_completed = merged.is_completed.clone();
+        let cancelled = merged.cancelled.clone();
+        let total_files = merged.total_files.clone();
+        let total_bytes = merged.total_bytes.clone();
+        let errors = merged.errors.clone();
+        // 两个源扫描的预热统计之和，并转入两者的错误
+        let update_totals = move |a: &ScanHandle, b: &ScanHandle| {
+            for source in [&a.errors, &b.errors] {
+                while let Some(error) = source.pop() {
+                    errors.push(error);
+                }
+            }
+            total_files.store(
+                a.total_files.load(Ordering::Relaxed) + b.total_files.load(Ordering::Relaxed),
+                Ordering::Relaxed,
+            );
+            total_bytes.store(
+                a.total_bytes.load(Ordering::Relaxed) + b.total_bytes.load(Ordering::Relaxed),
+                Ordering::Relaxed,
+            );
+        };
+        tokio::spawn(async move {
+            loop {
+                update_totals(&a, &b);
+                if cancelled.load(Ordering::Acquire) {
+                    a.cancel();
+                    b.cancel();
+                }
+                let a_done = a.is_done_and_empty();
+                let b_done = b.is_done_and_empty();
+                if a_done && b_done {
+                    break;
+                }
+
+                let batch = tokio::select! {
+                    batch = a.drain_next_batch(usize::MAX), if !a_done => batch,
+                    batch = b.drain_next_batch(usize::MAX), if !b_done => batch,
+                };
+                if batch.is_empty() {
+                    continue;
+                }
+                for file_info in batch {
+                    queue.push(file_info);
+                }
+                notify.notify_one();
+            }
+            update_totals(&a, &b);
+            is_completed.store(true, Ordering::SeqCst);
+            notify.notify_one();
+        });
+
+        merged
+    }
+
+    /// 收集全部扫描结果，直到扫描完成
+    pub async fn collect(self) -> Vec<FileInfo> {
+        let mut results = Vec::new();
+        self.collect_into(&mut results).await;
+        results
+    }
+
+    /// 结果队列中等待取出的元素数
+    pub fn pending_count(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// 扫描仍在进行但暂时没有可取出的结果，可用于退避轮询
+    pub fn is_idle(&self) -> bool {
+        self.pending_count() == 0 && !self.is_completed.load(Ordering::Acquire)
+    }
+
+    /// 扫描已完成且结果队列已取空
+    pub fn is_done_and_empty(&self) -> bool {
+        self.is_completed.load(Ordering::Acquire) && self.queue.is_empty()
+    }
+
+    // 持续取出结果队列中的元素，直到扫描完成
+    async fn collect_into(&self, results: &mut Vec<FileInfo>) {
+        while !self.is_done_and_empty() {
+            results.extend(self.drain_next_batch(usize::MAX).await);
+        }
+    }
+}
+
+/// 异步遍历目录树，产出其中所有非目录条目
+///
+/// `config` 中的隐藏文件、深度与符号链接设置在遍历过程中应用（与
+/// [`scan_directory_recursive`] 相同）；跟随符号链接时以规范化路径记录已访问的目录，避免循环。
+pub fn walk_dir_async(root: PathBuf, config: &ScanConfig) -> impl Stream<Item = fs::DirEntry> {
+    let max_depth = config.max_depth;
+    let follow_symlinks = config.follow_symlinks;
+    let config = config.clone();
+
+    async_stream::stream! {
+        let mut visited = HashSet::new();
+        let mut pending = vec![(root.clone(), 0)];
+        while let Some((dir, depth)) = pending.pop() {
+            if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                continue;
+            }
+            if follow_symlinks {
+                let key = canonicalize_path(&dir).await;
+                if !visited.insert(key) {
+                    continue;
+                }
+            }
+            let Ok(mut entries) = fs::read_dir(&dir).await else {
+                continue;
+            };
+
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let Some(is_dir) = classify_entry(&entry, &config).await else {
+                    continue;
+                };
+
+                if is_dir {
+                    let path = entry.path();
+                    if !config.is_excluded_dir(path.strip_prefix(&root).unwrap_or(&path)) {
+                        pending.push((path, depth + 1));
+                    }
+                } else {
+                    yield entry;
+                }
+            }
+        }
+    }
+}
+
+// 辅助函数：按 `skip_hidden` 与 `follow_symlinks` 判断目录条目，需要跳过时返回 `None`，
+// 否则返回是否作为目录继续遍历（跟随符号链接时按链接目标判断）
+async fn classify_entry(entry: &fs::DirEntry, config: &ScanConfig) -> Option<bool> {
+    if config.skip_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+        return None;
+    }
+    let file_type = entry.file_type().await.ok()?;
+    if file_type.is_symlink() && config.follow_symlinks {
+        Some(
+            fs::metadata(entry.path())
+                .await
+                .is_ok_and(|metadata| metadata.is_dir()),
+        )
+    } else {
+        Some(file_type.is_dir())
+    }
+}
+
+// 辅助函数：目录相对于扫描根目录的深度（根目录为 0），与 `max_depth` 比较
+fn depth_below(root: &Path, dir: &Path) -> usize {
+    dir.strip_prefix(root)
+        .map(|relative| relative.components().count())
+        .unwrap_or(0)
+}
+
+/// 常用类型与函数的统一导出
+pub mod prelude {
+    pub use super::{
+        scan_directory_recursive, scan_simple, walk_dir_async, DedupStrategy, FileInfo, ScanConfig,
+        ScanError, ScanErrorKind, ScanHandle, ScanHandleResult, ScanMode, ScanOrder, ScanSummary,
+        TARGET_EXTS,
+    };
+    pub use crate::storage::{detect_storage_type, StorageType};
+}
+
+/// 以默认配置扫描目录，存储介质类型自动检测
+pub async fn scan_simple(root: PathBuf) -> Result<ScanHandle, std::io::Error> {
+    let storage_type = storage::detect_storage_type(&root).await;
+    scan_directory_recursive(root, storage_type, ScanConfig::default()).await
 }
 
 /// 扫描函数
 pub async fn scan_directory_recursive(
     root: PathBuf,
     storage_type: StorageType,
+    mut config: ScanConfig,
 ) -> Result<ScanHandle, std::io::Error> {
+    config.adjust_for_storage(storage_type);
+
     let queue = Arc::new(SegQueue::new());
     let notify = Arc::new(Notify::new());
     let is_completed = Arc::new(AtomicBool::new(false));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let summary = Arc::new(OnceLock::new());
 
     let root_clone = root.clone();
     let queue_clone = queue.clone();
     let notify_clone = notify.clone();
     let is_completed_clone = is_completed.clone();
+    let cancelled_clone = cancelled.clone();
+    let summary_clone = summary.clone();
+    let total_files = Arc::new(AtomicU64::new(0));
+    let total_bytes = Arc::new(AtomicU64::new(0));
+    let total_files_clone = total_files.clone();
+    let total_bytes_clone = total_bytes.clone();
+    let errors = Arc::new(SegQueue::new());
+    let errors_clone = errors.clone();
 
-    tokio::spawn(async move {
-        let dir_queue = Arc::new(SegQueue::new());
+    let span = tracing::info_span!(
+        "scan_directory_recursive",
+        root = %root.display(),
+        storage_type = ?storage_type,
+        elapsed_micros = field::Empty,
+    );
+    let start = Instant::now();
+    let scan = async move {
+        let dir_queue = Arc::new(DirQueue::new(config.scan_order, config.scan_priority));
+        let visited = Arc::new(DashSet::new());
         let root_clone_2 = root_clone.clone();
-        dir_queue.push(root_clone_2);
+        mark_visited(&visited, &root_clone_2).await;
+        dir_queue.push_all(vec![root_clone_2]).await;
+
+        let semaphore = Arc::new(Semaphore::new(config.permits_for(storage_type)));
+        // HDD 上文件读取单独限流，其余介质与目录遍历共用信号量
+        let (read_semaphore, delay) = if storage_type == StorageType::HDD {
+            (
+                Arc::new(Semaphore::new(config.read_queue_depth.max(1))),
+                config
+                    .read_delay_ms
+                    .filter(|&ms| ms > 0)
+                    .map(Duration::from_millis),
+            )
+        } else {
+            (semaphore.clone(), None)
+        };
+        let read_limit = ReadLimit {
+            semaphore: read_semaphore,
+            delay,
+            verify: config.verify_reads.unwrap_or(false),
+            retry_count: config.read_retry_count,
+            blocking_micros: Arc::new(AtomicU64::new(0)),
+            errors: errors_clone,
+        };
+        let config = Arc::new(config);
+
+        let peak_memory_kb = Arc::new(AtomicU64::new(0));
+        tokio::spawn(sample_peak_memory(
+            is_completed_clone.clone(),
+            peak_memory_kb.clone(),
+        ));
 
-        let semaphore = Arc::new(Semaphore::new(match storage_type {
-            StorageType::SSD => 16,
-            StorageType::HDD | StorageType::Unknown(_) => 1,
-            StorageType::Failed => 1,
-        }));
+        if config.warmup {
+            let (files, bytes) = warmup(&root_clone, &semaphore, &config).await;
+            total_files_clone.store(files, Ordering::Relaxed);
+            total_bytes_clone.store(bytes, Ordering::Relaxed);
+        }
 
         let mut handles = vec![];
         let worker_count = 1;
+        let stats = Arc::new(ScanStats::default());
 
         for _ in 0..worker_count {
             let dir_queue = dir_queue.clone();
@@ -140,9 +908,15 @@ pub async fn scan_directory_recursive(
             let notify = notify_clone.clone();
             let semaphore = semaphore.clone();
             let root = root_clone.clone();
+            let cancelled = cancelled_clone.clone();
+            let stats = stats.clone();
+            let visited = visited.clone();
+            let read_limit = read_limit.clone();
+            let config = config.clone();
 
             handles.push(tokio::spawn(worker_thread(
-                dir_queue, queue, notify, semaphore, root,
+                dir_queue, queue, notify, semaphore, read_limit, root, cancelled, stats, visited,
+                config,
             )));
         }
 
@@ -154,96 +928,379 @@ pub async fn scan_directory_recursive(
             }
         }
 
+        let elapsed = start.elapsed();
+        let bytes_read = stats.bytes_read.load(Ordering::Relaxed);
+        let scan_summary = ScanSummary {
+            root_path: root_clone.to_string_lossy().into_owned(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0),
+            total_files_found: stats.files_found.load(Ordering::Relaxed),
+            total_bytes_read: bytes_read,
+            elapsed_seconds: elapsed.as_secs_f64(),
+            errors_count: stats.errors.load(Ordering::Relaxed),
+            storage_type,
+            worker_count,
+            peak_memory_kb: peak_memory_kb.load(Ordering::Relaxed),
+            total_disk_reads_kb: bytes_read / 1024,
+            cpu_time_ms: read_limit.blocking_micros.load(Ordering::Relaxed) / 1000,
+        };
+        tracing::Span::current().record("elapsed_micros", elapsed.as_micros() as u64);
+        tracing::info!(
+            total_files_found = scan_summary.total_files_found,
+            total_bytes_read = scan_summary.total_bytes_read,
+            elapsed_seconds = scan_summary.elapsed_seconds,
+            errors_count = scan_summary.errors_count,
+            storage_type = ?scan_summary.storage_type,
+            worker_count = scan_summary.worker_count,
+            peak_memory_kb = scan_summary.peak_memory_kb,
+            cpu_time_ms = scan_summary.cpu_time_ms,
+            "scan completed"
+        );
+        let _ = summary_clone.set(scan_summary);
+
         // 设置完成标记
         is_completed_clone.store(true, Ordering::SeqCst);
         notify_clone.notify_one(); // 发送最终完成通知
-    });
+    };
+    tokio::spawn(scan.instrument(span));
 
     Ok(ScanHandle {
         queue,
         notify,
         is_completed,
+        cancelled,
+        summary,
+        total_files,
+        total_bytes,
+        errors,
     })
 }
 
-/// 检测指定路径所在存储介质的类型
-async fn detect_storage_type(path: &Path) -> StorageType {
-    let canonical_path = tokio::fs::canonicalize(path)
-        .await
-        .unwrap_or_else(|_| path.to_path_buf());
+// 每隔 MEMORY_SAMPLE_INTERVAL 采样一次当前进程的内存占用并记录峰值（KiB），扫描完成后停止
+async fn sample_peak_memory(is_completed: Arc<AtomicBool>, peak_memory_kb: Arc<AtomicU64>) {
+    let Ok(pid) = sysinfo::get_current_pid() else {
+        return;
+    };
+    let mut system = System::new();
+    while !is_completed.load(Ordering::Acquire) {
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[pid]),
+            true,
+            ProcessRefreshKind::nothing().with_memory(),
+        );
+        if let Some(process) = system.process(pid) {
+            peak_memory_kb.fetch_max(process.memory() / 1024, Ordering::Relaxed);
+        }
+        tokio::time::sleep(MEMORY_SAMPLE_INTERVAL).await;
+    }
+}
 
-    Disks::new_with_refreshed_list()
-        .iter()
-        .filter(|d| {
-            canonical_path.starts_with(
-                d.mount_point()
-                    .canonicalize()
-                    .unwrap_or_else(|_| path.to_path_buf()),
-            )
-        })
-        .max_by_key(|d| d.mount_point().components().count())
-        .map(|d| match d.kind() {
-            DiskKind::HDD => StorageType::HDD,
-            DiskKind::SSD => StorageType::SSD,
-            DiskKind::Unknown(t) => StorageType::Unknown(t),
-        })
-        .unwrap_or(StorageType::Failed)
+// 预热：遍历目录树并读取每个谱面文件的元数据（不读取内容），返回 (文件数, 总字节数)。
+// 与 worker_thread 相同，已访问过的文件与目录（符号链接等指向同一位置的情况）被跳过
+async fn warmup(root: &Path, semaphore: &Arc<Semaphore>, config: &ScanConfig) -> (u64, u64) {
+    let mut files_count = 0;
+    let mut bytes = 0;
+    let visited = DashSet::new();
+    mark_visited(&visited, root).await;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let (files, subdirs) = process_directory(&dir, root, semaphore.clone(), config).await;
+        for subdir in subdirs {
+            if mark_visited(&visited, &subdir).await {
+                pending.push(subdir);
+            }
+        }
+        for file_path in files {
+            if !mark_visited(&visited, &file_path).await {
+                continue;
+            }
+            let permit = semaphore.acquire().await.unwrap();
+            let metadata = fs::metadata(&file_path).await;
+            drop(permit);
+            if let Ok(metadata) = metadata {
+                files_count += 1;
+                bytes += metadata.len();
+            }
+        }
+    }
+    (files_count, bytes)
 }
 
 /// 处理单个目录的核心逻辑
 async fn process_directory(
     dir: &Path,
-    _root: &Path, // 新增根目录参数
+    root: &Path,
     semaphore: Arc<Semaphore>,
+    config: &ScanConfig,
 ) -> (Vec<PathBuf>, Vec<PathBuf>) {
-    let mut found_files = Vec::new();
-    let mut subdirs = Vec::new();
+    let span = tracing::debug_span!(
+        "process_directory",
+        dir_path = %dir.display(),
+        files_found = field::Empty,
+        elapsed_micros = field::Empty,
+    );
+    let start = Instant::now();
 
-    let entries_result = {
-        let permit = semaphore.acquire().await.unwrap();
-        let result = fs::read_dir(dir).await;
-        drop(permit);
-        result
-    };
-    let mut entries = match entries_result {
-        Ok(e) => e,
-        Err(_) => return (vec![], vec![]),
-    };
+    let (found_files, subdirs) = async {
+        let mut found_files = Vec::new();
+        let mut subdirs = Vec::new();
 
-    while let Ok(Some(entry)) = entries.next_entry().await {
-        let entry_type = entry
-            .file_type()
-            .await
-            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
-            .expect("Failed to get entry_type!");
-        let path = entry.path();
-
-        if entry_type.is_dir() {
-            subdirs.push(path);
-        } else if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-            if TARGET_EXTS.contains(&ext.to_ascii_lowercase().as_str()) {
+        let entries_result = {
+            let permit = semaphore.acquire().await.unwrap();
+            let result = fs::read_dir(dir).await;
+            drop(permit);
+            result
+        };
+        let mut entries = match entries_result {
+            Ok(e) => e,
+            Err(_) => return (vec![], vec![]),
+        };
+
+        // 子目录的深度达到 max_depth 时不再进入（与 walk_dir_async 一致）
+        let subdir_depth = depth_below(root, dir) + 1;
+        let descend = config
+            .max_depth
+            .is_none_or(|max_depth| subdir_depth < max_depth);
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Some(is_dir) = classify_entry(&entry, config).await else {
+                continue;
+            };
+            let path = entry.path();
+
+            if is_dir {
+                if descend && !config.is_excluded_dir(path.strip_prefix(root).unwrap_or(&path)) {
+                    subdirs.push(path);
+                }
+            } else if config.is_target_file(&path) {
                 found_files.push(path);
             }
         }
+
+        (found_files, subdirs)
     }
+    .instrument(span.clone())
+    .await;
 
+    span.record("files_found", found_files.len());
+    span.record("elapsed_micros", start.elapsed().as_micros() as u64);
     (found_files, subdirs)
 }
 
+// 辅助函数：以规范化路径记录访问，首次访问时返回 true
+async fn mark_visited(visited: &DashSet<PathBuf>, path: &Path) -> bool {
+    visited.insert(canonicalize_path(path).await)
+}
+
+/// 规范化路径：解析 `.`、`..` 与符号链接，并在大小写不敏感的文件系统上得到实际的大小写，
+/// 使同一文件的不同写法得到相同的路径；失败时（如文件不存在）返回原路径。
+/// Windows 上盘符统一为大写。
+pub async fn canonicalize_path(path: &Path) -> PathBuf {
+    let canonical = fs::canonicalize(path)
+        .await
+        .unwrap_or_else(|_| path.to_path_buf());
+    #[cfg(windows)]
+    let canonical = normalize_drive_letter(canonical);
+    canonical
+}
+
+// 辅助函数：将盘符前缀（`c:` 或 `\\?\c:`）转为大写
+#[cfg(windows)]
+fn normalize_drive_letter(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return path;
+    };
+    let drive = match prefix.kind() {
+        Prefix::Disk(drive) | Prefix::VerbatimDisk(drive) => drive,
+        _ => return path,
+    };
+    if drive.is_ascii_uppercase() {
+        return path;
+    }
+    let mut normalized = PathBuf::from(prefix.as_os_str().to_string_lossy().to_ascii_uppercase());
+    normalized.push(components.as_path());
+    normalized
+}
+
+// 文件读取的并发与等待限制
+#[derive(Clone)]
+struct ReadLimit {
+    semaphore: Arc<Semaphore>,
+    // 取得许可后、开始读取前的等待时间
+    delay: Option<Duration>,
+    // 是否在读取后抽查校验
+    verify: bool,
+    // 校验失败时的重试次数
+    retry_count: u8,
+    // 阻塞任务（计算 SHA256）的累计用时（微秒）
+    blocking_micros: Arc<AtomicU64>,
+    // 与 ScanHandle::errors 共享的错误队列
+    errors: Arc<SegQueue<ScanError>>,
+}
+
+// 文件的读取方式，测试中可替换为模拟实现
+trait ChartReader {
+    // 读取一次文件内容并计算SHA256
+    async fn read_once(&self, path: &Path) -> Result<(Arc<Box<[u8]>>, [u8; 32]), std::io::Error>;
+
+    // 抽查读取到的内容是否与文件一致
+    async fn spot_check(&self, path: &Path, content: &[u8]) -> Result<bool, std::io::Error>;
+}
+
+impl ChartReader for ReadLimit {
+    async fn read_once(&self, path: &Path) -> Result<(Arc<Box<[u8]>>, [u8; 32]), std::io::Error> {
+        read_and_hash_once(path, self).await
+    }
+
+    async fn spot_check(&self, path: &Path, content: &[u8]) -> Result<bool, std::io::Error> {
+        spot_check(path, content, self).await
+    }
+}
+
 /// 新增文件处理函数
 async fn process_file(
     path: &Path,
     root: &Path,
     semaphore: Arc<Semaphore>,
+    read_limit: &ReadLimit,
+    scan_mode: ScanMode,
 ) -> Result<FileInfo, std::io::Error> {
-    // 计算相对路径
-    let relative_path = path
-        .strip_prefix(root)
-        .map_err(|err| std::io::Error::new(std::io::ErrorKind::NotFound, err))?;
+    let span = tracing::trace_span!(
+        "process_file",
+        file_path = %path.display(),
+        hash_algorithm = "sha256",
+        elapsed_micros = field::Empty,
+    );
+    let start = Instant::now();
+
+    let result = async {
+        // 计算相对路径（以扫描时的写法相对于根目录）
+        let relative_path = path
+            .strip_prefix(root)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::NotFound, err))?;
+        // 索引使用规范化后的绝对路径，避免同一文件以不同写法重复出现
+        let absolute_path = canonicalize_path(path).await;
+
+        // 仅元数据模式下不读取内容
+        if scan_mode == ScanMode::MetadataOnly {
+            let size = {
+                let permit = semaphore.acquire().await.unwrap();
+                let metadata = fs::metadata(&path).await?;
+                drop(permit);
+                metadata.len()
+            };
+            return Ok(FileInfo {
+                absolute_path,
+                relative_path: relative_path.to_path_buf(),
+                size,
+                content: None,
+                sha256: [0u8; 32],
+                integrity_warning: false,
+            });
+        }
+
+        let (content, sha256, verified) = read_and_hash(path, read_limit).await?;
+        Ok::<_, std::io::Error>(FileInfo {
+            absolute_path,
+            relative_path: relative_path.to_path_buf(),
+            size: content.len() as u64,
+            content: Some(content),
+            sha256,
+            integrity_warning: !verified,
+        })
+    }
+    .instrument(span.clone())
+    .await;
+
+    span.record("elapsed_micros", start.elapsed().as_micros() as u64);
+    result
+}
+
+// 抽查校验的块大小
+const VERIFY_CHUNK_SIZE: usize = 4096;
+
+// 读取文件内容并计算SHA256，返回 (内容, 哈希, 是否通过校验)
+async fn read_and_hash(
+    path: &Path,
+    read_limit: &ReadLimit,
+) -> Result<(Arc<Box<[u8]>>, [u8; 32], bool), std::io::Error> {
+    read_and_hash_with(path, read_limit, read_limit).await
+}
+
+// 按 read_limit 的校验设置用 reader 读取文件。开启校验时抽查失败会记录一个
+// CorruptRead 错误并重新读取整个文件，重试次数用尽后返回最后一次的结果
+async fn read_and_hash_with(
+    path: &Path,
+    read_limit: &ReadLimit,
+    reader: &impl ChartReader,
+) -> Result<(Arc<Box<[u8]>>, [u8; 32], bool), std::io::Error> {
+    let mut attempt = 0;
+    loop {
+        let (content, sha256) = reader.read_once(path).await?;
+        if !read_limit.verify || reader.spot_check(path, &content).await? {
+            return Ok((content, sha256, true));
+        }
+        tracing::warn!(
+            kind = "CorruptRead",
+            file_path = %path.display(),
+            attempt,
+            "read-back verification failed"
+        );
+        read_limit.errors.push(ScanError {
+            kind: ScanErrorKind::CorruptRead,
+            path: path.to_path_buf(),
+            attempt,
+        });
+        if attempt >= read_limit.retry_count {
+            return Ok((content, sha256, false));
+        }
+        attempt += 1;
+    }
+}
+
+// 辅助函数：重新读取文件中随机位置的一块，与完整读取的内容比较 SHA256
+async fn spot_check(
+    path: &Path,
+    content: &[u8],
+    read_limit: &ReadLimit,
+) -> Result<bool, std::io::Error> {
+    let len = VERIFY_CHUNK_SIZE.min(content.len());
+    if len == 0 {
+        return Ok(true);
+    }
+    // 不需要高质量的随机数，用当前时间的纳秒部分选择位置即可
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos() as usize)
+        .unwrap_or(0);
+    let offset = nanos % (content.len() - len + 1);
 
+    let mut chunk = vec![0u8; len];
+    {
+        let permit = read_limit.semaphore.acquire().await.unwrap();
+        let mut file = fs::File::open(path).await?;
+        file.seek(SeekFrom::Start(offset as u64)).await?;
+        file.read_exact(&mut chunk).await?;
+        drop(permit);
+    }
+    Ok(Sha256::digest(&chunk) == Sha256::digest(&content[offset..offset + len]))
+}
+
+// 辅助函数：读取一次文件内容并计算SHA256
+async fn read_and_hash_once(
+    path: &Path,
+    read_limit: &ReadLimit,
+) -> Result<(Arc<Box<[u8]>>, [u8; 32]), std::io::Error> {
     // 异步读取文件内容
     let content = Arc::new({
-        let permit = semaphore.acquire().await.unwrap();
+        let permit = read_limit.semaphore.acquire().await.unwrap();
+        if let Some(delay) = read_limit.delay {
+            tokio::time::sleep(delay).await;
+        }
         let content = fs::read(&path).await?;
         drop(permit);
         content.into_boxed_slice()
@@ -251,48 +1308,848 @@ async fn process_file(
 
     // 计算SHA256
     let content_a = Arc::clone(&content);
+    let blocking_micros = read_limit.blocking_micros.clone();
     let compute_sha256 = move || {
+        let start = Instant::now();
         let mut hasher = Sha256::new();
         hasher.update(content_a.as_ref());
         let sha256 = hasher.finalize();
+        blocking_micros.fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
         <[u8; 32]>::from(sha256)
     };
     let sha256 = tokio::task::spawn_blocking(compute_sha256)
         .await
         .map_err(|err| std::io::Error::new(std::io::ErrorKind::Interrupted, err))?;
 
-    Ok(FileInfo {
-        absolute_path: path.to_path_buf(),
-        relative_path: relative_path.to_path_buf(),
-        content,
-        sha256,
-    })
+    Ok((content, sha256))
 }
 
 // 修改工作者线程逻辑
+#[allow(clippy::too_many_arguments)]
 async fn worker_thread(
-    dir_queue: Arc<SegQueue<PathBuf>>,
+    dir_queue: Arc<DirQueue>,
     queue: Arc<SegQueue<FileInfo>>,
     notify: Arc<Notify>,
     semaphore: Arc<Semaphore>,
+    read_limit: ReadLimit,
     root: PathBuf,
+    cancelled: Arc<AtomicBool>,
+    stats: Arc<ScanStats>,
+    visited: Arc<DashSet<PathBuf>>,
+    config: Arc<ScanConfig>,
 ) -> Result<(), std::io::Error> {
     while let Some(dir) = dir_queue.pop() {
-        let (files, subdirs) = process_directory(&dir, &root, semaphore.clone()).await;
+        if cancelled.load(Ordering::Acquire) {
+            break;
+        }
+
+        let (files, subdirs) = process_directory(&dir, &root, semaphore.clone(), &config).await;
 
-        // 处理文件
+        // 跳过已访问过的文件与目录（符号链接等指向同一位置的情况）
+        let mut unvisited_files = Vec::with_capacity(files.len());
         for file_path in files {
-            let Ok(file_info) = process_file(&file_path, &root, semaphore.clone()).await else {
+            if mark_visited(&visited, &file_path).await {
+                unvisited_files.push(file_path);
+            }
+        }
+        let files = unvisited_files;
+        let mut unvisited_subdirs = Vec::with_capacity(subdirs.len());
+        for subdir in subdirs {
+            if mark_visited(&visited, &subdir).await {
+                unvisited_subdirs.push(subdir);
+            }
+        }
+        let subdirs = unvisited_subdirs;
+
+        // 并发处理目录内的文件，实际并发度由信号量限制
+        let results = future::join_all(files.iter().map(|file_path| {
+            process_file(
+                file_path,
+                &root,
+                semaphore.clone(),
+                &read_limit,
+                config.scan_mode,
+            )
+        }))
+        .await;
+        for result in results {
+            let Ok(file_info) = result else {
+                stats.errors.fetch_add(1, Ordering::Relaxed);
                 continue;
             };
+            stats.files_found.fetch_add(1, Ordering::Relaxed);
+            stats
+                .bytes_read
+                .fetch_add(file_info.content_or_empty().len() as u64, Ordering::Relaxed);
             queue.push(file_info);
             notify.notify_one(); // 发送新数据通知
         }
 
         // 处理子目录
-        subdirs
-            .into_iter()
-            .for_each(|subdir| dir_queue.push(subdir));
+        dir_queue.push_all(subdirs).await;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    // 前 corrupt_reads 次抽查失败的模拟读取
+    struct FlakyReader {
+        corrupt_reads: u8,
+        reads: AtomicU64,
+    }
+
+    impl ChartReader for FlakyReader {
+        async fn read_once(
+            &self,
+            _path: &Path,
+        ) -> Result<(Arc<Box<[u8]>>, [u8; 32]), std::io::Error> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            let content: Box<[u8]> = b"#TITLE test".to_vec().into_boxed_slice();
+            let sha256 = Sha256::digest(&content).into();
+            Ok((Arc::new(content), sha256))
+        }
+
+        async fn spot_check(&self, _path: &Path, _content: &[u8]) -> Result<bool, std::io::Error> {
+            Ok(self.reads.load(Ordering::Relaxed) > self.corrupt_reads as u64)
+        }
+    }
+
+    fn read_limit(retry_count: u8) -> ReadLimit {
+        ReadLimit {
+            semaphore: Arc::new(Semaphore::new(1)),
+            delay: None,
+            verify: true,
+            retry_count,
+            blocking_micros: Arc::new(AtomicU64::new(0)),
+            errors: Arc::new(SegQueue::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn corrupt_read_is_retried_and_reported() {
+        let read_limit = read_limit(2);
+        let reader = FlakyReader {
+            corrupt_reads: 1,
+            reads: AtomicU64::new(0),
+        };
+        let (_, _, verified) = read_and_hash_with(Path::new("a.bms"), &read_limit, &reader)
+            .await
+            .unwrap();
+
+        assert!(verified);
+        assert_eq!(reader.reads.load(Ordering::Relaxed), 2);
+        let error = read_limit.errors.pop().unwrap();
+        assert_eq!(error.kind, ScanErrorKind::CorruptRead);
+        assert_eq!(error.path, Path::new("a.bms"));
+        assert_eq!(error.attempt, 0);
+        assert!(read_limit.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn corrupt_read_gives_up_after_retry_count() {
+        let read_limit = read_limit(2);
+        let reader = FlakyReader {
+            corrupt_reads: u8::MAX,
+            reads: AtomicU64::new(0),
+        };
+        let (_, _, verified) = read_and_hash_with(Path::new("a.bms"), &read_limit, &reader)
+            .await
+            .unwrap();
+
+        assert!(!verified);
+        assert_eq!(reader.reads.load(Ordering::Relaxed), 3);
+        let attempts: Vec<u8> = std::iter::from_fn(|| read_limit.errors.pop())
+            .map(|error| error.attempt)
+            .collect();
+        assert_eq!(attempts, [0, 1, 2]);
+    }
+
+    // 以指定配置扫描目录并收集结果（按出现顺序），路径为相对于扫描根目录的 `/` 分隔形式
+    async fn scan_relative_paths(root: &Path, config: ScanConfig) -> Vec<String> {
+        scan_directory_recursive(root.to_path_buf(), StorageType::SSD, config)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .iter()
+            .map(FileInfo::relative_path_unix_str)
+            .collect()
+    }
+
+    fn scan_order_fixture() -> TempDir {
+        let dir = TempDir::new();
+        dir.write("top.bms", b"#TITLE top");
+        dir.write("a/x.bms", b"#TITLE x");
+        dir.write("a/deep/z.bms", b"#TITLE z");
+        dir.write("b/y.bms", b"#TITLE y");
+        dir
+    }
+
+    #[tokio::test]
+    async fn breadth_first_scans_shallow_directories_first() {
+        let dir = scan_order_fixture();
+        let config = ScanConfig {
+            scan_order: ScanOrder::BreadthFirst,
+            ..Default::default()
+        };
+        let paths = scan_relative_paths(dir.path(), config).await;
+        assert_eq!(paths.len(), 4);
+        let depths: Vec<usize> = paths.iter().map(|path| path.matches('/').count()).collect();
+        assert!(depths.is_sorted(), "{:?}", paths);
+    }
+
+    #[tokio::test]
+    async fn depth_first_finishes_a_subtree_first() {
+        let dir = scan_order_fixture();
+        let config = ScanConfig {
+            scan_order: ScanOrder::DepthFirst,
+            ..Default::default()
+        };
+        let paths = scan_relative_paths(dir.path(), config).await;
+        assert_eq!(paths[0], "top.bms");
+        let x = paths.iter().position(|path| path == "a/x.bms").unwrap();
+        assert_eq!(paths[x + 1], "a/deep/z.bms", "{:?}", paths);
+    }
+
+    #[tokio::test]
+    async fn exclude_patterns_prune_matching_directories() {
+        let dir = scan_order_fixture();
+        dir.write("_backup/old.bms", b"#TITLE old");
+        dir.write("a/_backup/older.bms", b"#TITLE older");
+        dir.write("a/_backup/nested/oldest.bms", b"#TITLE oldest");
+        dir.write("a/_backup_notes.bms", b"#TITLE notes");
+        let config = ScanConfig {
+            exclude_patterns: vec!["**/_backup/**".to_string()],
+            ..Default::default()
+        };
+        let mut paths = scan_relative_paths(dir.path(), config).await;
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "a/_backup_notes.bms",
+                "a/deep/z.bms",
+                "a/x.bms",
+                "b/y.bms",
+                "top.bms"
+            ]
+        );
+
+        let config = ScanConfig {
+            exclude_patterns: vec!["a".to_string()],
+            ..Default::default()
+        };
+        let mut paths = scan_relative_paths(dir.path(), config).await;
+        paths.sort();
+        assert_eq!(paths, ["_backup/old.bms", "b/y.bms", "top.bms"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinked_directories_and_files_are_scanned_once() {
+        let dir = TempDir::new();
+        let chart = dir.write("pack/a.bms", b"#TITLE a");
+        std::os::unix::fs::symlink(dir.path().join("pack"), dir.path().join("pack_link")).unwrap();
+        std::os::unix::fs::symlink(&chart, dir.path().join("b.bms")).unwrap();
+        // 指回根目录的链接不会造成循环
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("pack/loop")).unwrap();
+
+        let config = ScanConfig {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let results = scan_directory_recursive(dir.path().to_path_buf(), StorageType::SSD, config)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].absolute_path(),
+            std::fs::canonicalize(&chart).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_collects_both_scans() {
+        let first = TempDir::new();
+        first.write("a.bms", b"#TITLE a");
+        first.write("sub/b.bms", b"#TITLE b");
+        let second = TempDir::new();
+        second.write("c.bms", b"#TITLE c");
+
+        let scan = |dir: &TempDir| {
+            scan_directory_recursive(
+                dir.path().to_path_buf(),
+                StorageType::SSD,
+                ScanConfig::default(),
+            )
+        };
+        let merged = ScanHandle::merge(scan(&first).await.unwrap(), scan(&second).await.unwrap());
+        let mut names: Vec<String> = merged
+            .collect()
+            .await
+            .iter()
+            .map(FileInfo::relative_path_unix_str)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.bms", "c.bms", "sub/b.bms"]);
+    }
+
+    #[tokio::test]
+    async fn merge_of_empty_scans_completes() {
+        let first = TempDir::new();
+        let second = TempDir::new();
+        let scan = |dir: &TempDir| {
+            scan_directory_recursive(
+                dir.path().to_path_buf(),
+                StorageType::SSD,
+                ScanConfig::default(),
+            )
+        };
+        let merged = ScanHandle::merge(scan(&first).await.unwrap(), scan(&second).await.unwrap());
+        let result = merged.timeout(Duration::from_secs(10)).await;
+        assert!(matches!(result, ScanHandleResult::Completed { results } if results.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn canonicalize_path_resolves_dot_segments() {
+        let dir = TempDir::new();
+        let chart = dir.write("pack/a.bms", b"#TITLE a");
+        let canonical = std::fs::canonicalize(&chart).unwrap();
+        assert_eq!(canonicalize_path(&chart).await, canonical);
+        assert_eq!(
+            canonicalize_path(&dir.path().join("pack/./../pack/a.bms")).await,
+            canonical
+        );
+        // 相对路径解析为绝对路径
+        let relative = Path::new("./src/../src/lib.rs");
+        let resolved = canonicalize_path(relative).await;
+        assert!(resolved.is_absolute());
+        assert_eq!(
+            resolved,
+            std::env::current_dir().unwrap().join("src/lib.rs")
+        );
+        // 不存在的路径原样返回
+        let missing = dir.path().join("missing/../b.bms");
+        assert_eq!(canonicalize_path(&missing).await, missing);
+    }
+
+    #[tokio::test]
+    async fn scan_results_use_canonical_paths() {
+        let dir = TempDir::new();
+        let chart = dir.write("pack/a.bms", b"#TITLE a");
+        let root = dir.path().join("pack/..");
+        let results = scan_directory_recursive(root, StorageType::SSD, ScanConfig::default())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].absolute_path(),
+            std::fs::canonicalize(&chart).unwrap()
+        );
+        // 相对路径仍按扫描根目录的写法计算
+        assert_eq!(results[0].relative_path_unix_str(), "pack/a.bms");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn drive_letter_is_uppercased() {
+        assert_eq!(
+            normalize_drive_letter(PathBuf::from(r"c:\Music\a.bms")),
+            PathBuf::from(r"C:\Music\a.bms")
+        );
+        assert_eq!(
+            normalize_drive_letter(PathBuf::from(r"\\?\c:\Music")),
+            PathBuf::from(r"\\?\C:\Music")
+        );
+    }
+
+    #[test]
+    fn default_scan_config_is_valid() {
+        assert_eq!(validate_scan_config(&ScanConfig::default()), Ok(()));
+    }
+
+    #[test]
+    fn every_scan_config_violation_is_reported() {
+        let config = ScanConfig {
+            ssd_permits: 0,
+            max_depth: Some(0),
+            read_delay_ms: Some(MAX_READ_DELAY_MS + 1),
+            exclude_patterns: vec!["**/ok".to_string(), "[".to_string(), "a[".to_string()],
+            ..Default::default()
+        };
+        let fields: Vec<String> = validate_scan_config(&config)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "ssd_permits",
+                "max_depth",
+                "read_delay_ms",
+                "exclude_patterns",
+                "exclude_patterns"
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_config_bounds_are_inclusive() {
+        let config = ScanConfig {
+            ssd_permits: *SSD_PERMITS_RANGE.end(),
+            max_depth: Some(1),
+            read_delay_ms: Some(MAX_READ_DELAY_MS),
+            ..Default::default()
+        };
+        assert_eq!(validate_scan_config(&config), Ok(()));
+
+        let config = ScanConfig {
+            ssd_permits: *SSD_PERMITS_RANGE.end() + 1,
+            ..Default::default()
+        };
+        let errors = validate_scan_config(&config).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "ssd_permits: must be between 1 and 128, got 129"
+        );
+    }
+
+    // 以指定配置用 walk_dir_async 遍历目录，返回其中谱面文件排序后的相对路径
+    async fn walk_relative_paths(root: &Path, config: &ScanConfig) -> Vec<String> {
+        use futures::StreamExt;
+
+        let mut paths: Vec<String> = walk_dir_async(root.to_path_buf(), config)
+            .filter(|entry| future::ready(config.is_target_file(&entry.path())))
+            .map(|entry| {
+                let path = entry.path();
+                let relative = path.strip_prefix(root).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+            .await;
+        paths.sort();
+        paths
+    }
+
+    // 扫描与 walk_dir_async 应用相同的遍历设置，返回扫描结果的相对路径（已排序）
+    async fn assert_scan_matches_walk(root: &Path, config: ScanConfig) -> Vec<String> {
+        let walked = walk_relative_paths(root, &config).await;
+        let mut scanned = scan_relative_paths(root, config).await;
+        scanned.sort();
+        assert_eq!(scanned, walked);
+        scanned
+    }
+
+    #[tokio::test]
+    async fn scan_skips_hidden_entries() {
+        let dir = TempDir::new();
+        dir.write("a.bms", b"#TITLE a");
+        dir.write(".b.bms", b"#TITLE b");
+        dir.write(".hidden/c.bms", b"#TITLE c");
+
+        let config = ScanConfig {
+            skip_hidden: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            assert_scan_matches_walk(dir.path(), config).await,
+            ["a.bms"]
+        );
+        assert_eq!(
+            scan_relative_paths(dir.path(), ScanConfig::default())
+                .await
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_respects_max_depth() {
+        let dir = scan_order_fixture();
+        let depth = |max_depth| ScanConfig {
+            max_depth: Some(max_depth),
+            ..Default::default()
+        };
+        assert_eq!(
+            assert_scan_matches_walk(dir.path(), depth(1)).await,
+            ["top.bms"]
+        );
+        assert_eq!(
+            assert_scan_matches_walk(dir.path(), depth(2)).await,
+            ["a/x.bms", "b/y.bms", "top.bms"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn scan_follows_symlinks_only_when_enabled() {
+        let dir = TempDir::new();
+        let target = TempDir::new();
+        target.write("linked.bms", b"#TITLE linked");
+        dir.write("a.bms", b"#TITLE a");
+        std::os::unix::fs::symlink(target.path(), dir.path().join("link")).unwrap();
+
+        assert_eq!(
+            assert_scan_matches_walk(dir.path(), ScanConfig::default()).await,
+            ["a.bms"]
+        );
+        let config = ScanConfig {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            assert_scan_matches_walk(dir.path(), config).await,
+            ["a.bms", "link/linked.bms"]
+        );
+    }
+
+    // 基准测试：walk_dir_async 与 walkdir 遍历 100,000 个条目的用时。
+    // 运行方式：cargo test --release walk_dir_async_benchmark -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn walk_dir_async_benchmark() {
+        const DIRS: usize = 1_000;
+        const FILES_PER_DIR: usize = 99;
+
+        let dir = TempDir::new();
+        for d in 0..DIRS {
+            for f in 0..FILES_PER_DIR {
+                dir.write(&format!("{:04}/{:02}.bms", d, f), b"");
+            }
+        }
+        let config = ScanConfig::default();
+
+        let start = Instant::now();
+        let walked = walk_relative_paths(dir.path(), &config).await.len();
+        let async_elapsed = start.elapsed();
+
+        let start = Instant::now();
+        let root = dir.path().to_path_buf();
+        let walkdir_count = tokio::task::spawn_blocking(move || {
+            walkdir::WalkDir::new(root)
+                .min_depth(1)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| !entry.file_type().is_dir())
+                .count()
+        })
+        .await
+        .unwrap();
+        let walkdir_elapsed = start.elapsed();
+
+        assert_eq!(walked, DIRS * FILES_PER_DIR);
+        assert_eq!(walkdir_count, walked);
+        println!(
+            "{} entries: walk_dir_async {:?}, walkdir {:?}",
+            DIRS * (FILES_PER_DIR + 1),
+            async_elapsed,
+            walkdir_elapsed
+        );
+    }
+
+    #[tokio::test]
+    async fn every_file_in_a_directory_is_scanned_at_any_concurrency() {
+        let dir = TempDir::new();
+        for i in 0..100 {
+            dir.write(
+                &format!("pack/{:03}.bms", i),
+                format!("#TITLE {}", i).as_bytes(),
+            );
+        }
+        let expected: Vec<String> = (0..100).map(|i| format!("pack/{:03}.bms", i)).collect();
+
+        for ssd_permits in [1, 16] {
+            let config = ScanConfig {
+                ssd_permits,
+                ..Default::default()
+            };
+            let mut paths = scan_relative_paths(dir.path(), config).await;
+            paths.sort();
+            assert_eq!(paths, expected, "ssd_permits = {}", ssd_permits);
+        }
+    }
+
+    // 基准测试：每个目录 100 个文件时，目录内顺序处理（ssd_permits = 1）与并发处理的用时。
+    // 运行方式：cargo test --release process_directory_concurrency_benchmark -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn process_directory_concurrency_benchmark() {
+        const DIRS: usize = 20;
+        const FILES_PER_DIR: usize = 100;
+
+        let dir = TempDir::new();
+        let content = vec![b'#'; 64 * 1024];
+        for d in 0..DIRS {
+            for f in 0..FILES_PER_DIR {
+                dir.write(&format!("{:02}/{:03}.bms", d, f), &content);
+            }
+        }
+
+        for ssd_permits in [1, 16] {
+            let config = ScanConfig {
+                ssd_permits,
+                ..Default::default()
+            };
+            let start = Instant::now();
+            let scanned = scan_relative_paths(dir.path(), config).await.len();
+            assert_eq!(scanned, DIRS * FILES_PER_DIR);
+            println!("ssd_permits = {}: {:?}", ssd_permits, start.elapsed());
+        }
+    }
+
+    #[tokio::test]
+    async fn metadata_only_scan_loads_content_on_demand() {
+        let dir = TempDir::new();
+        dir.write("a.bms", b"#TITLE a");
+        dir.write("pack/b.bme", b"#TITLE bb");
+
+        let scan = |scan_mode| {
+            let root = dir.path().to_path_buf();
+            async move {
+                let config = ScanConfig {
+                    scan_mode,
+                    ..Default::default()
+                };
+                let mut files = scan_directory_recursive(root, StorageType::SSD, config)
+                    .await
+                    .unwrap()
+                    .collect()
+                    .await;
+                files.sort_by(|a, b| a.relative_path().cmp(b.relative_path()));
+                files
+            }
+        };
+        let full = scan(ScanMode::FullContent).await;
+        let mut metadata_only = scan(ScanMode::MetadataOnly).await;
+
+        for (file, full) in metadata_only.iter_mut().zip(&full) {
+            assert!(file.content.is_none());
+            assert_eq!(file.sha256, [0; 32]);
+            assert_eq!(file.size, full.size);
+
+            file.load_content(Arc::new(Semaphore::new(1)))
+                .await
+                .unwrap();
+            assert_eq!(file.content_or_empty(), full.content_or_empty());
+            assert_eq!(file.sha256, full.sha256);
+        }
+        assert_eq!(metadata_only.len(), 2);
+    }
+
+    // 基准测试：10,000 个文件时只读取元数据与读取全部内容的用时。
+    // 运行方式：cargo test --release scan_mode_benchmark -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn scan_mode_benchmark() {
+        const DIRS: usize = 100;
+        const FILES_PER_DIR: usize = 100;
+
+        let dir = TempDir::new();
+        let content = vec![b'#'; 64 * 1024];
+        for d in 0..DIRS {
+            for f in 0..FILES_PER_DIR {
+                dir.write(&format!("{:02}/{:03}.bms", d, f), &content);
+            }
+        }
+
+        for scan_mode in [ScanMode::FullContent, ScanMode::MetadataOnly] {
+            let config = ScanConfig {
+                scan_mode,
+                ..Default::default()
+            };
+            let start = Instant::now();
+            let scanned = scan_relative_paths(dir.path(), config).await.len();
+            assert_eq!(scanned, DIRS * FILES_PER_DIR);
+            println!("{:?}: {:?}", scan_mode, start.elapsed());
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_simple_scans_with_the_prelude() {
+        use crate::bms_scan::prelude::*;
+
+        let dir = TempDir::new();
+        dir.write("a.bms", b"#TITLE a");
+        dir.write("pack/b.bme", b"#TITLE b");
+        let handle: ScanHandle = scan_simple(dir.path().to_path_buf()).await.unwrap();
+        let mut files: Vec<FileInfo> = handle.collect().await;
+        files.sort_by(|a, b| a.relative_path().cmp(b.relative_path()));
+        let paths: Vec<_> = files.iter().map(FileInfo::relative_path_unix_str).collect();
+        assert_eq!(paths, ["a.bms", "pack/b.bme"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn warmup_stops_at_symlink_loops() {
+        let dir = TempDir::new();
+        dir.write("pack/a.bms", b"#TITLE a");
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("pack/loop")).unwrap();
+
+        let config = ScanConfig {
+            follow_symlinks: true,
+            warmup: true,
+            ..Default::default()
+        };
+        let scan = async {
+            let handle =
+                scan_directory_recursive(dir.path().to_path_buf(), StorageType::SSD, config)
+                    .await
+                    .unwrap();
+            let total_files = handle.total_files.clone();
+            let results = handle.collect().await;
+            (total_files.load(Ordering::Relaxed), results.len())
+        };
+        let (total_files, scanned) = tokio::time::timeout(Duration::from_secs(10), scan)
+            .await
+            .expect("warmup looped on the symlink");
+        assert_eq!((total_files, scanned), (1, 1));
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn completed_scan_emits_info_event() {
+        let dir = TempDir::new();
+        dir.write("a.bms", b"#TITLE a");
+        dir.write("sub/b.bms", b"#TITLE b");
+
+        scan_directory_recursive(
+            dir.path().to_path_buf(),
+            StorageType::SSD,
+            ScanConfig::default(),
+        )
+        .await
+        .unwrap()
+        .collect()
+        .await;
+        assert!(logs_contain("INFO"));
+        assert!(logs_contain("scan completed"));
+        assert!(logs_contain("total_files_found=2"));
+    }
+
+    // 手动推入结果的扫描句柄，用于模拟进行中的扫描
+    fn manual_handle() -> ScanHandle {
+        ScanHandle {
+            queue: Arc::new(SegQueue::new()),
+            notify: Arc::new(Notify::new()),
+            is_completed: Arc::new(AtomicBool::new(false)),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            summary: Arc::new(OnceLock::new()),
+            total_files: Arc::new(AtomicU64::new(0)),
+            total_bytes: Arc::new(AtomicU64::new(0)),
+            errors: Arc::new(SegQueue::new()),
+        }
+    }
+
+    fn fake_file_info(name: &str) -> FileInfo {
+        FileInfo {
+            absolute_path: PathBuf::from("/bms").join(name),
+            relative_path: PathBuf::from(name),
+            size: 0,
+            content: None,
+            sha256: [0; 32],
+            integrity_warning: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn drain_available_returns_partial_results() {
+        let handle = manual_handle();
+        assert!(handle.drain_available().is_empty());
+        for i in 0..3 {
+            handle.queue.push(fake_file_info(&format!("{}.bms", i)));
+        }
+
+        assert!(!handle.is_done_and_empty());
+        let drained = handle.drain_available();
+        assert_eq!(drained.len(), 3);
+        assert_eq!(drained[0].relative_path_str(), Some("0.bms"));
+        // 队列已取空但扫描仍在进行
+        assert!(!handle.is_done_and_empty());
+
+        handle.is_completed.store(true, Ordering::SeqCst);
+        assert!(handle.is_done_and_empty());
+    }
+
+    #[tokio::test]
+    async fn drain_next_batch_takes_at_most_max() {
+        let handle = manual_handle();
+        for i in 0..3 {
+            handle.queue.push(fake_file_info(&format!("{}.bms", i)));
+        }
+        // 队列中已有结果时不等待通知
+        assert_eq!(handle.drain_next_batch(2).await.len(), 2);
+        assert_eq!(handle.drain_next_batch(2).await.len(), 1);
+
+        let waiting = tokio::spawn({
+            let queue = handle.queue.clone();
+            let notify = handle.notify.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                queue.push(fake_file_info("late.bms"));
+                notify.notify_one();
+            }
+        });
+        let batch = handle.drain_next_batch(2).await;
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].relative_path_str(), Some("late.bms"));
+        waiting.await.unwrap();
+
+        // 扫描完成后不再等待
+        handle.is_completed.store(true, Ordering::SeqCst);
+        assert!(handle.drain_next_batch(2).await.is_empty());
+    }
+
+    #[test]
+    fn pending_count_tracks_queue_length() {
+        let handle = manual_handle();
+        assert!(handle.is_idle());
+        for i in 0..10 {
+            handle.queue.push(fake_file_info(&format!("{}.bms", i)));
+        }
+        assert_eq!(handle.pending_count(), 10);
+        assert!(!handle.is_idle());
+
+        for _ in 0..5 {
+            handle.queue.pop().unwrap();
+        }
+        assert_eq!(handle.pending_count(), 5);
+
+        handle.drain_available();
+        assert!(handle.is_idle());
+        handle.is_completed.store(true, Ordering::SeqCst);
+        assert!(!handle.is_idle());
+    }
+
+    #[tokio::test]
+    async fn most_recent_first_scans_the_newest_directory_first() {
+        let dir = TempDir::new();
+        dir.write("a_old/1.bms", b"#TITLE 1");
+        dir.write("a_old/2.bms", b"#TITLE 2");
+        dir.write("z_new/3.bms", b"#TITLE 3");
+        let now = SystemTime::now();
+        for (name, age) in [("a_old", 3600), ("z_new", 0)] {
+            std::fs::File::open(dir.path().join(name))
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        let config = ScanConfig {
+            scan_priority: ScanPriority::MostRecentFirst,
+            ..Default::default()
+        };
+        let paths = scan_relative_paths(dir.path(), config).await;
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0], "z_new/3.bms", "{:?}", paths);
+    }
+}
@@ -0,0 +1,208 @@
+//! 本模块将谱面的物件布局绘制为 SVG 1.1 图像
+//!
+//! 每个轨道为一列，时间自下而上推进（纵坐标按拍数乘以 `pixels_per_beat`）。普通音符为
+//! 矩形，长条为从起点延伸到终点的矩形，地雷为 X 形标记；BPM 变化与停止绘制为贯穿全部轨道的
+//! 横线，右侧标注数值。轨道顺序为 1P 皿、1P 键 1-7、2P 键 1-7、2P 皿。
+
+use std::{collections::HashMap, fmt::Write};
+
+use serde::Deserialize;
+
+use crate::bms_parse::{self, NoteType, ParsedChart};
+
+/// 每个轨道的宽度（像素）
+const LANE_WIDTH: f64 = 24.0;
+/// 音符矩形的高度（像素）
+const NOTE_HEIGHT: f64 = 6.0;
+/// 右侧标注区的宽度（像素）
+const LABEL_WIDTH: f64 = 80.0;
+/// 图像上下的留白（像素）
+const MARGIN: f64 = 16.0;
+/// 支持的最大轨道数（DP）
+pub const MAX_LANES: u8 = 16;
+
+/// SVG 导出选项
+#[derive(Debug, Clone, Deserialize)]
+pub struct SvgOptions {
+    /// 绘制的轨道数（`1..=16`），超出范围的轨道上的物件不绘制；SP 通常为 8
+    pub lanes: u8,
+    /// 每拍的高度（像素）
+    pub pixels_per_beat: f64,
+    pub show_bpm_changes: bool,
+    pub show_stops: bool,
+}
+
+/// 生成谱面的 SVG 文档
+pub fn render_chart_svg(chart: &ParsedChart, options: &SvgOptions) -> Result<String, String> {
+    if !(1..=MAX_LANES).contains(&options.lanes) {
+        return Err(format!("lanes must be between 1 and {}", MAX_LANES));
+    }
+    if !(options.pixels_per_beat.is_finite() && options.pixels_per_beat > 0.0) {
+        return Err("pixels_per_beat must be positive".into());
+    }
+
+    let last_beat = chart.notes.iter().map(|note| note.beat).fold(0.0, f64::max);
+    let lanes_width = options.lanes as f64 * LANE_WIDTH;
+    let width = lanes_width + LABEL_WIDTH;
+    let height = (last_beat + 4.0) * options.pixels_per_beat + 2.0 * MARGIN;
+    // 拍数对应的纵坐标（第 0 拍位于底部）
+    let y_of = |beat: f64| height - MARGIN - beat * options.pixels_per_beat;
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{:.1}" height="{:.1}" viewBox="0 0 {:.1} {:.1}">"#,
+        width, height, width, height
+    );
+    let _ = writeln!(
+        svg,
+        r##"<rect x="0" y="0" width="{:.1}" height="{:.1}" fill="#101010"/>"##,
+        width, height
+    );
+    for lane in 1..options.lanes {
+        let x = lane as f64 * LANE_WIDTH;
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{:.1}" y1="0" x2="{:.1}" y2="{:.1}" stroke="#404040" stroke-width="1"/>"##,
+            x, x, height
+        );
+    }
+
+    // 长条起点按通道记录，遇到终点时绘制
+    let mut open_long_notes: HashMap<u8, f64> = HashMap::new();
+    let mut notes: Vec<_> = chart.notes.iter().collect();
+    notes.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+    for note in notes {
+        let Some(lane) = lane_index(note.channel).filter(|&lane| lane < options.lanes) else {
+            continue;
+        };
+        let x = lane as f64 * LANE_WIDTH;
+        match note.note_type {
+            NoteType::Normal | NoteType::Scratch => {
+                write_note(&mut svg, "note", x, y_of(note.beat), NOTE_HEIGHT, lane);
+            }
+            NoteType::LNStart => {
+                open_long_notes.insert(note.channel, note.beat);
+            }
+            NoteType::LNEnd => {
+                let start = open_long_notes.remove(&note.channel).unwrap_or(note.beat);
+                let top = y_of(note.beat);
+                let length = y_of(start) - top + NOTE_HEIGHT;
+                write_note(&mut svg, "long-note", x, top, length, lane);
+            }
+            NoteType::Mine => {
+                let (left, right) = (x + 4.0, x + LANE_WIDTH - 4.0);
+                let y = y_of(note.beat);
+                let _ = writeln!(
+                    svg,
+                    r##"<path class="mine" d="M{:.1},{:.1} L{:.1},{:.1} M{:.1},{:.1} L{:.1},{:.1}" stroke="#ff4040" stroke-width="2" fill="none"/>"##,
+                    left,
+                    y - 4.0,
+                    right,
+                    y + 4.0,
+                    left,
+                    y + 4.0,
+                    right,
+                    y - 4.0
+                );
+            }
+            _ => {}
+        }
+    }
+    // 没有终点的长条按普通音符绘制
+    let mut unterminated: Vec<(u8, f64)> = open_long_notes.into_iter().collect();
+    unterminated.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    for (channel, beat) in unterminated {
+        if let Some(lane) = lane_index(channel) {
+            let x = lane as f64 * LANE_WIDTH;
+            write_note(&mut svg, "note", x, y_of(beat), NOTE_HEIGHT, lane);
+        }
+    }
+
+    if options.show_bpm_changes {
+        for change in &chart.bpm_timeline.changes {
+            let beat =
+                bms_parse::beats_before(change.measure, change.fraction, &chart.measure_lengths);
+            write_marker(
+                &mut svg,
+                "bpm",
+                "#40c0ff",
+                lanes_width,
+                y_of(beat),
+                &format!("BPM {}", change.bpm),
+            );
+        }
+    }
+    if options.show_stops {
+        for stop in &chart.stops {
+            let beat = bms_parse::beats_before(stop.measure, stop.fraction, &chart.measure_lengths);
+            write_marker(
+                &mut svg,
+                "stop",
+                "#ffc040",
+                lanes_width,
+                y_of(beat),
+                &format!("STOP {:.2}", stop.beats),
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+// 辅助函数：通道对应的轨道序号（1P 皿为 0，1P 键 1-7 为 1-7，2P 键 1-7 为 8-14，2P 皿为 15）
+fn lane_index(channel: u8) -> Option<u8> {
+    let position = match channel & 0x0F {
+        6 => 0,
+        key @ 1..=5 => key,
+        8 => 6,
+        9 => 7,
+        _ => return None,
+    };
+    Some(match (bms_parse::lane_side(channel), position) {
+        (1, position) => position,
+        (_, 0) => 15,
+        (_, position) => 7 + position,
+    })
+}
+
+// 辅助函数：绘制一个音符或长条矩形（皿为红色，奇数键为白色，偶数键为蓝色）
+fn write_note(svg: &mut String, class: &str, x: f64, top: f64, length: f64, lane: u8) {
+    let color = match lane {
+        0 | 15 => "#e04040",
+        lane if (lane - 1) % 7 % 2 == 0 => "#f0f0f0",
+        _ => "#4080ff",
+    };
+    let opacity = if class == "long-note" { 0.6 } else { 1.0 };
+    let _ = writeln!(
+        svg,
+        r#"<rect class="{}" x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}" fill-opacity="{}"/>"#,
+        class,
+        x + 2.0,
+        top - NOTE_HEIGHT / 2.0,
+        LANE_WIDTH - 4.0,
+        length,
+        color,
+        opacity
+    );
+}
+
+// 辅助函数：绘制贯穿全部轨道的横线及其标注
+fn write_marker(svg: &mut String, class: &str, color: &str, lanes_width: f64, y: f64, label: &str) {
+    let _ = writeln!(
+        svg,
+        r#"<line class="{}" x1="0" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="1"/>"#,
+        class, y, lanes_width, y, color
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text class="{}" x="{:.1}" y="{:.1}" fill="{}" font-family="sans-serif" font-size="10">{}</text>"#,
+        class,
+        lanes_width + 4.0,
+        y + 3.0,
+        color,
+        label
+    );
+}
@@ -0,0 +1,142 @@
+//! 本模块用于检测并转换谱面文件的文本编码
+//!
+//! BMS 谱面多为 Shift_JIS，也有 UTF-8 与 EUC-JP。存在 BOM 时以 BOM 为准；否则分别以
+//! 各候选编码解码文件开头的 [`SAMPLE_SIZE`] 字节，按解码错误数与半角片假名比例打分，
+//! 取得分最高者（同分时依次优先 UTF-8、Shift_JIS、EUC-JP）。
+
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_8};
+use serde::Serialize;
+
+/// 用于检测编码的文件开头字节数
+pub const SAMPLE_SIZE: usize = 4 * 1024;
+
+/// 不存在 BOM 时的候选编码（按同分时的优先顺序排列）
+const CANDIDATES: [&Encoding; 3] = [UTF_8, SHIFT_JIS, EUC_JP];
+
+/// 编码检测结果
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodingInfo {
+    /// IANA 编码名，如 `Shift_JIS`、`UTF-8`、`EUC-JP`
+    pub encoding: String,
+    /// 可信度（0.0 ~ 1.0），由 BOM 确定时为 1.0
+    pub confidence: f32,
+    pub bom_detected: bool,
+}
+
+/// 检测内容的编码（只使用开头的 [`SAMPLE_SIZE`] 字节）
+pub fn detect_encoding(content: &[u8]) -> EncodingInfo {
+    let (encoding, confidence, bom_detected) = detect(content);
+    EncodingInfo {
+        encoding: encoding.name().to_string(),
+        confidence,
+        bom_detected,
+    }
+}
+
+/// 按检测到的编码将内容解码为 UTF-8 字符串（去除 BOM）
+pub fn decode_to_utf8(content: &[u8]) -> String {
+    let (encoding, _, bom_detected) = detect(content);
+    if bom_detected {
+        encoding.decode_with_bom_removal(content).0.into_owned()
+    } else {
+        encoding.decode_without_bom_handling(content).0.into_owned()
+    }
+}
+
+// 辅助函数：返回 (编码, 可信度, 是否存在 BOM)
+fn detect(content: &[u8]) -> (&'static Encoding, f32, bool) {
+    if let Some((encoding, _)) = Encoding::for_bom(content) {
+        return (encoding, 1.0, true);
+    }
+
+    let sample = &content[..content.len().min(SAMPLE_SIZE)];
+    let mut best = (UTF_8, f32::MIN);
+    for encoding in CANDIDATES {
+        let score = decode_score(encoding, sample);
+        if score > best.1 {
+            best = (encoding, score);
+        }
+    }
+    (best.0, best.1.clamp(0.0, 1.0), false)
+}
+
+// 辅助函数：以指定编码解码样本的得分。每个解码错误扣 1 分、每个半角片假名扣 0.5 分
+// （EUC-JP 文本被当作 Shift_JIS 解码时通常没有错误，但会产生大量半角片假名），
+// 再除以字符数。样本末尾被截断的多字节字符不计入错误
+fn decode_score(encoding: &'static Encoding, sample: &[u8]) -> f32 {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::with_capacity(
+        decoder
+            .max_utf8_buffer_length(sample.len())
+            .unwrap_or(sample.len() * 3),
+    );
+    // 样本被截断时不是输入的结尾，末尾不完整的字符留在解码器中而不会解码为 U+FFFD
+    let last = sample.len() < SAMPLE_SIZE;
+    let _ = decoder.decode_to_string(sample, &mut text, last);
+
+    let mut chars = 0;
+    let mut penalty = 0.0;
+    for c in text.chars() {
+        chars += 1;
+        match c {
+            '\u{FFFD}' => penalty += 1.0,
+            '\u{FF61}'..='\u{FF9F}' => penalty += 0.5,
+            _ => {}
+        }
+    }
+    if chars == 0 {
+        return 1.0;
+    }
+    1.0 - penalty / chars as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "#TITLE 東方アレンジ「幻想郷」\n#ARTIST ゆうき／譜面：たなか\n";
+
+    fn encode(encoding: &'static Encoding) -> Vec<u8> {
+        encoding.encode(TEXT).0.into_owned()
+    }
+
+    #[test]
+    fn detects_japanese_encodings() {
+        for encoding in CANDIDATES {
+            let (detected, confidence, bom_detected) = detect(&encode(encoding));
+            assert_eq!(detected, encoding);
+            assert_eq!(confidence, 1.0);
+            assert!(!bom_detected);
+        }
+    }
+
+    #[test]
+    fn bom_takes_precedence() {
+        let content = [&[0xEF, 0xBB, 0xBF][..], &encode(UTF_8)].concat();
+        let info = detect_encoding(&content);
+        assert_eq!(info.encoding, "UTF-8");
+        assert!(info.bom_detected);
+        assert_eq!(decode_to_utf8(&content), TEXT);
+    }
+
+    #[test]
+    fn ascii_and_empty_content_prefer_utf8() {
+        assert_eq!(detect(b"#BPM 150\n").0, UTF_8);
+        assert_eq!(detect(b"").0, UTF_8);
+    }
+
+    #[test]
+    fn decodes_shift_jis_to_utf8() {
+        assert_eq!(decode_to_utf8(&encode(SHIFT_JIS)), TEXT);
+    }
+
+    #[test]
+    fn truncated_character_at_sample_end_is_ignored() {
+        // 双字节字符从奇数位置开始，样本的最后一个字节为首字节
+        let mut content = vec![b' '];
+        while content.len() <= SAMPLE_SIZE {
+            content.extend(SHIFT_JIS.encode("東方").0.iter());
+        }
+        assert_eq!(detect(&content), (SHIFT_JIS, 1.0, false));
+    }
+}
@@ -0,0 +1,164 @@
+//! 本模块定义命令错误
+//!
+//! 命令统一以 `String` 作为错误类型：先构造 [`CabinetError`]，通过 [`CabinetError::context`]
+//! 附加发生错误时正在进行的操作，再转换为字符串，使消息同时包含错误种类与相关的目录名或
+//! 曲目 ID，例如 `while getting tracks of "foo": Directory "foo" not found`。
+
+use std::fmt;
+
+/// 命令错误
+#[derive(Debug, Clone)]
+pub enum CabinetError {
+    /// 曲目正在被批量重新解析，暂时不能修改
+    TrackLocked { id: u32 },
+    /// 曲目不存在
+    TrackNotFound { id: u32 },
+    /// 曲目 ID 已被曲库中的其他曲目使用
+    TrackAlreadyExists { id: u32 },
+    /// 目录不存在
+    DirectoryNotFound { name: String },
+    /// 目录没有记录导入时的根路径
+    DirectoryRootNotFound { name: String },
+    /// 路径没有可用作目录名的文件名
+    InvalidDirectoryName { path: String },
+    /// 回收站中没有该曲目
+    NotInTrash { id: u32 },
+    /// 导入冲突不存在（可能已被处理）
+    ConflictNotFound { sha256: String },
+    /// 播放列表不存在
+    PlaylistNotFound { id: u32 },
+    /// 游玩会话不是当前进行中的会话
+    SessionNotActive { id: u32 },
+    /// 暂存的导入不存在或已过期
+    PendingImportNotFound { id: String },
+    /// 已有进行中的游玩会话
+    SessionAlreadyActive { id: u32 },
+    /// 尚没有完成过扫描
+    NoCompletedScan,
+    /// 扫描配置中没有该扩展名
+    ExtensionNotFound { extension: String },
+    /// 扫描配置中没有该排除规则
+    ExcludePatternNotFound { pattern: String },
+    /// 不能识别的名称或版本（如事件类型、字段名）
+    UnknownValue { kind: String, value: String },
+    /// 参数无效
+    InvalidArgument { name: String, reason: String },
+    /// 曲库版本不支持降级
+    DowngradeNotSupported { from: u32, to: u32 },
+    /// 命令仅在调试构建中可用
+    DebugOnly { command: String },
+    /// 当前平台不支持该功能
+    UnsupportedPlatform { feature: String },
+    /// 文件读写失败
+    Io { message: String },
+    /// 其他错误
+    Other { message: String },
+}
+
+impl fmt::Display for CabinetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CabinetError::TrackLocked { id } => write!(f, "Track {} is locked", id),
+            CabinetError::TrackNotFound { id } => write!(f, "Track {} not found", id),
+            CabinetError::TrackAlreadyExists { id } => write!(f, "Track {} already exists", id),
+            CabinetError::DirectoryNotFound { name } => {
+                write!(f, "Directory {:?} not found", name)
+            }
+            CabinetError::DirectoryRootNotFound { name } => {
+                write!(f, "Root of directory {:?} not found", name)
+            }
+            CabinetError::InvalidDirectoryName { path } => {
+                write!(f, "Invalid directory name: {}", path)
+            }
+            CabinetError::NotInTrash { id } => write!(f, "Track {} not in trash", id),
+            CabinetError::ConflictNotFound { sha256 } => {
+                write!(f, "Conflict for {} not found", sha256)
+            }
+            CabinetError::PlaylistNotFound { id } => write!(f, "Playlist {} not found", id),
+            CabinetError::SessionNotActive { id } => write!(f, "Session {} not active", id),
+            CabinetError::PendingImportNotFound { id } => {
+                write!(f, "Pending import {:?} not found", id)
+            }
+            CabinetError::SessionAlreadyActive { id } => {
+                write!(f, "Session {} is already active", id)
+            }
+            CabinetError::NoCompletedScan => f.write_str("No completed scan"),
+            CabinetError::ExtensionNotFound { extension } => {
+                write!(f, "Extension {:?} not found", extension)
+            }
+            CabinetError::ExcludePatternNotFound { pattern } => {
+                write!(f, "Exclude pattern {:?} not found", pattern)
+            }
+            CabinetError::UnknownValue { kind, value } => write!(f, "Unknown {}: {}", kind, value),
+            CabinetError::InvalidArgument { name, reason } => {
+                write!(f, "Invalid {}: {}", name, reason)
+            }
+            CabinetError::DowngradeNotSupported { from, to } => write!(
+                f,
+                "Downgrading the library from version {} to {} is not supported",
+                from, to
+            ),
+            CabinetError::DebugOnly { command } => {
+                write!(f, "{} is only available in debug builds", command)
+            }
+            CabinetError::UnsupportedPlatform { feature } => {
+                write!(f, "{} is not supported on this platform", feature)
+            }
+            CabinetError::Io { message } => write!(f, "I/O error: {}", message),
+            CabinetError::Other { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CabinetError {}
+
+impl CabinetError {
+    /// 附加发生错误时正在进行的操作（如 `while adding directory "foo"`）
+    pub fn context(self, context: impl Into<String>) -> CabinetErrorContext {
+        CabinetErrorContext {
+            error: self,
+            context: context.into(),
+        }
+    }
+}
+
+/// 带操作上下文的命令错误，显示为 `<上下文>: <错误>`
+#[derive(Debug, Clone)]
+pub struct CabinetErrorContext {
+    pub error: CabinetError,
+    pub context: String,
+}
+
+impl fmt::Display for CabinetErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.error)
+    }
+}
+
+impl std::error::Error for CabinetErrorContext {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<std::io::Error> for CabinetError {
+    fn from(error: std::io::Error) -> Self {
+        CabinetError::Io {
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<String> for CabinetError {
+    fn from(message: String) -> Self {
+        CabinetError::Other { message }
+    }
+}
+
+impl From<&str> for CabinetError {
+    fn from(message: &str) -> Self {
+        CabinetError::Other {
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! 本模块定义曲库变更事件
+//!
+//! 全部变更以 [`LIBRARY_EVENT`] 事件发送，负载序列化为 `{ "type": ..., "payload": ... }`；
+//! 窗口可通过 `subscribe_to_events` 只接收部分类型的事件。
+//!
+//! 进度事件不属于曲库变更，以独立的事件名直接发送给全部窗口，不受订阅过滤：
+//! `import_progress`（导入进度百分比）与 `recompute_progress`（批量重新解析进度百分比）。
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::{bms_scan::ScanSummary, BulkTagApplied, ReindexReport, Track};
+
+/// 曲库变更事件的事件名
+pub const LIBRARY_EVENT: &str = "library_event";
+
+/// 全部事件类型名（即 `type` 字段可能的取值）
+pub const EVENT_TYPES: &[&str] = &[
+    "TrackAdded",
+    "TrackRemoved",
+    "TrackUpdated",
+    "DirectoryAdded",
+    "DirectoryRemoved",
+    "ScanCompleted",
+    "IntegrityWarning",
+    "ReindexCompleted",
+    "ConsistencyWarning",
+    "BulkTagApplied",
+    "DedupSkipped",
+];
+
+/// 曲库变更事件
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum LibraryEvent {
+    TrackAdded(Track),
+    /// 被移除曲目的 ID
+    TrackRemoved(u32),
+    TrackUpdated(Track),
+    /// 目录名
+    DirectoryAdded(String),
+    DirectoryRemoved(String),
+    ScanCompleted(ScanSummary),
+    /// 读取校验失败的曲目 ID
+    IntegrityWarning(u32),
+    /// `reindex_library` 完成
+    ReindexCompleted(ReindexReport),
+    /// 扫描后的一致性检查发现的问题（以 `; ` 分隔），之后会自动重建索引
+    ConsistencyWarning(String),
+    /// 批量打标签完成
+    BulkTagApplied(BulkTagApplied),
+    /// 导入时因去重被跳过的谱面路径
+    DedupSkipped(PathBuf),
+}
+
+impl LibraryEvent {
+    /// 事件类型名，与序列化后的 `type` 字段一致
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            LibraryEvent::TrackAdded(_) => "TrackAdded",
+            LibraryEvent::TrackRemoved(_) => "TrackRemoved",
+            LibraryEvent::TrackUpdated(_) => "TrackUpdated",
+            LibraryEvent::DirectoryAdded(_) => "DirectoryAdded",
+            LibraryEvent::DirectoryRemoved(_) => "DirectoryRemoved",
+            LibraryEvent::ScanCompleted(_) => "ScanCompleted",
+            LibraryEvent::IntegrityWarning(_) => "IntegrityWarning",
+            LibraryEvent::ReindexCompleted(_) => "ReindexCompleted",
+            LibraryEvent::ConsistencyWarning(_) => "ConsistencyWarning",
+            LibraryEvent::BulkTagApplied(_) => "BulkTagApplied",
+            LibraryEvent::DedupSkipped(_) => "DedupSkipped",
+        }
+    }
+}
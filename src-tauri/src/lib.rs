@@ -1,178 +1,7334 @@
 // src-tauri/src/main.rs
-mod bms_scan;
+mod audio;
+mod benchmark;
+pub mod bms_parse;
+pub mod bms_scan;
+mod chart_svg;
+mod encoding;
+mod error;
+mod events;
+mod lr2;
+mod m3u;
+mod metadata;
+mod migrations;
+mod rating;
+mod session;
+mod sidecar;
+mod similarity;
+mod snapshot;
+pub mod storage;
+#[cfg(test)]
+mod testing;
+mod track_cache;
+mod trash;
+mod watcher;
 
+use bms_parse::{
+    BgaEvent, ChartType, ChordStats, DoubleLaneBreakdown, KeyMode, LaneBreakdown, NoteObject,
+    ParsedChart, ScrollEvent,
+};
+use bms_scan::{DedupStrategy, FileInfo, ScanConfig, ScanHandleResult, ScanSummary};
+use error::CabinetError;
+use events::LibraryEvent;
+use futures::{future, stream, StreamExt};
+use metadata::MetadataSource;
+use rating::GaugeType;
 use sha2::{Digest, Sha256};
 #[allow(unused_imports)]
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
+    ops::Range,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 #[allow(unused_imports)]
 use tauri::{
     async_runtime::Mutex,
+    ipc::Channel,
     plugin::{Builder, TauriPlugin},
-    Emitter, Manager, Runtime, State, Window,
+    AppHandle, Emitter, Manager, Runtime, State, Window,
+};
+use tokio::{
+    fs,
+    io::AsyncReadExt,
+    sync::{mpsc, MutexGuard},
 };
-use tokio::fs;
-use walkdir::WalkDir;
+use unicode_normalization::UnicodeNormalization;
 
 // 应用状态结构体
 #[derive(Default)]
 struct AppState {
-    // 存储目录与曲目的映射关系
-    directories: Mutex<HashMap<String, Vec<Track>>>,
+    // 扁平曲目表：同一目录的曲目连续存放，目录内按 id 升序
+    // 加锁顺序：tracks -> directory_index -> id_index -> sha256_index -> artist_index
+    // -> genre_index -> stats_cache，需要多个锁时使用 lock_library
+    tracks: Mutex<Vec<Track>>,
+    // 目录名与其曲目在 tracks 中所占区间的映射关系
+    directory_index: Mutex<HashMap<String, Range<usize>>>,
+    // 曲目 ID 与其在 tracks 中下标的映射关系
+    id_index: Mutex<HashMap<u32, usize>>,
+    // SHA256 与曲目 ID 列表的映射关系
+    sha256_index: Mutex<HashMap<String, Vec<u32>>>,
+    // 规范化艺术家名与曲目 ID 列表的映射关系
+    artist_index: Mutex<HashMap<String, Vec<u32>>>,
+    // 规范化流派名与曲目 ID 列表的映射关系
+    genre_index: Mutex<HashMap<String, Vec<u32>>>,
+    // 曲库统计缓存，曲目增删或修改时清空
+    stats_cache: Mutex<LibraryStatsCache>,
+    // 下一个待分配的曲目 ID（单调递增）
+    next_id: AtomicU32,
+    // 曲库已执行到的迁移版本（见 migrations 模块）
+    schema_version: AtomicU32,
     // 当前导入进度
     current_progress: Mutex<f64>,
+    // 最近一次扫描的完成标记，保留到下一次扫描开始
+    current_scan: Mutex<Option<Arc<AtomicBool>>>,
+    // 扫描配置
+    config: Mutex<ScanConfig>,
+    // 最近一次导入中因去重被跳过的文件（需在曲目表相关锁之后获取）
+    import_conflicts: Mutex<Vec<ImportConflict>>,
+    // 最近的完整扫描统计（最多保留 SCAN_HISTORY_LIMIT 条）
+    scan_history: Mutex<Vec<ScanSummary>>,
+    // 目录名与导入时根路径的映射关系
+    directory_roots: Mutex<HashMap<String, PathBuf>>,
+    // prepare_import 扫描后等待确认的导入（键为预览 ID）
+    pending_imports: Mutex<HashMap<String, PendingImport>>,
+    // 由 watch_directory 监视的目录（键为目录路径），移除即停止监视
+    watched_directories: Mutex<HashMap<PathBuf, watcher::DirectoryWatcher>>,
+    // 正在被批量重新解析、暂不允许编辑的曲目（需在曲目表相关锁之后获取）
+    locked_track_ids: Mutex<HashSet<u32>>,
+    // 窗口标签与其订阅的曲库事件类型，不在其中的窗口接收全部事件
+    event_subscriptions: Mutex<HashMap<String, HashSet<String>>>,
+    // 按 ID 查询曲目的缓存，写入与失效都需在持有曲目表锁时进行
+    track_cache: track_cache::TrackCache,
+    // 当前进行中的游玩会话（需在曲目表相关锁之后、session_history 之前获取）
+    active_session: Mutex<Option<session::PlaySession>>,
+    // 已结束的游玩会话（按结束先后排序）
+    session_history: Mutex<Vec<session::PlaySession>>,
+    // 下一个待分配的会话 ID
+    next_session_id: AtomicU32,
+    // 播放列表（键为播放列表 ID，需在曲目表相关锁之后获取）
+    playlists: Mutex<HashMap<u32, Playlist>>,
+    // 下一个待分配的播放列表 ID
+    next_playlist_id: AtomicU32,
+    // 标签 ID 与打上该标签的曲目 ID（需在曲目表相关锁之后获取）
+    track_tags: Mutex<HashMap<u32, HashSet<u32>>>,
+    // 等待在后台预读取物件的曲目（不与曲目表相关锁同时持有）
+    prefetch: Mutex<PrefetchQueue>,
+    // 回收站（按删除先后排序，需在曲目表相关锁之后获取）
+    trash: Mutex<Vec<trash::TrashedTrack>>,
+}
+
+// 曲库统计缓存
+#[derive(Default)]
+struct LibraryStatsCache {
+    // 难度（play_level）的 (平均值, 标准差)
+    difficulty: Option<(f64, f64)>,
+}
+
+// 等待确认的导入。只保留路径与哈希（不含文件内容），确认时重新读取
+struct PendingImport {
+    path: PathBuf,
+    files: Vec<FileInfo>,
+    created_at: Instant,
+}
+
+// 未确认也未取消的导入的保留时间，超过后在下一次 prepare_import 时丢弃
+const PENDING_IMPORT_TTL: Duration = Duration::from_secs(30 * 60);
+
+// 扫描历史的最大条数
+const SCAN_HISTORY_LIMIT: usize = 100;
+
+impl AppState {
+    // 按固定顺序获取曲目表及全部索引的锁
+    async fn lock_library(&self) -> LibraryGuard<'_> {
+        LibraryGuard {
+            tracks: self.tracks.lock().await,
+            directory_index: self.directory_index.lock().await,
+            id_index: self.id_index.lock().await,
+            sha256_index: self.sha256_index.lock().await,
+            artist_index: self.artist_index.lock().await,
+            genre_index: self.genre_index.lock().await,
+            stats_cache: self.stats_cache.lock().await,
+            track_cache: &self.track_cache,
+        }
+    }
+
+    // 以新的曲目列表替换（或新增）一个目录：
+    // 先移除旧区间，按去重策略过滤后分配递增 ID 并追加到扁平表末尾，返回产生的导入冲突
+    async fn replace_directory(
+        &self,
+        name: String,
+        mut new_tracks: Vec<Track>,
+        dedup_strategy: DedupStrategy,
+    ) -> Vec<ImportConflict> {
+        let mut library = self.lock_library().await;
+
+        if let Some(old) = library.directory_index.remove(&name) {
+            library.remove_range(old);
+        }
+
+        // 按路径排序后再去重与分配 ID，使结果不依赖扫描完成的先后顺序
+        new_tracks.sort_by(|a, b| a.absolute_path.cmp(&b.absolute_path));
+        let (mut new_tracks, skipped) = library.dedup_tracks(new_tracks, dedup_strategy);
+        for track in &mut new_tracks {
+            track.id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        }
+        let range = library.push_tracks(new_tracks);
+        library.directory_index.insert(name.clone(), range);
+
+        // 被跳过的曲目与保留下来的同哈希曲目构成冲突
+        skipped
+            .into_iter()
+            .filter_map(|track| {
+                let existing_track_id = *library.sha256_index.get(&track.sha256)?.first()?;
+                let existing_directory = library.directory_of(existing_track_id)?;
+                Some(ImportConflict {
+                    skipped_path: track.absolute_path.clone(),
+                    existing_track_id,
+                    existing_directory,
+                    sha256: track.sha256.clone(),
+                    skipped_track: track,
+                    target_directory: name.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+// 同时持有曲目表与全部索引的锁，所有修改操作都经由它进行以保持索引一致
+struct LibraryGuard<'a> {
+    tracks: MutexGuard<'a, Vec<Track>>,
+    directory_index: MutexGuard<'a, HashMap<String, Range<usize>>>,
+    id_index: MutexGuard<'a, HashMap<u32, usize>>,
+    sha256_index: MutexGuard<'a, HashMap<String, Vec<u32>>>,
+    artist_index: MutexGuard<'a, HashMap<String, Vec<u32>>>,
+    genre_index: MutexGuard<'a, HashMap<String, Vec<u32>>>,
+    stats_cache: MutexGuard<'a, LibraryStatsCache>,
+    // 曲目被修改或移除时使其缓存失效
+    track_cache: &'a track_cache::TrackCache,
+}
+
+impl LibraryGuard<'_> {
+    // 将曲目追加到扁平表末尾并登记全部索引，返回其所占区间
+    fn push_tracks(&mut self, new_tracks: Vec<Track>) -> Range<usize> {
+        let start = self.tracks.len();
+        for track in &new_tracks {
+            self.index_secondary(track);
+        }
+        self.tracks.extend(new_tracks);
+        self.reindex_from(start);
+        self.invalidate_stats();
+        start..self.tracks.len()
+    }
+
+    // 将曲目插入到已存在目录的区间内（目录内按 id 有序），并修正目录区间与 id 索引
+    fn insert_into_directory(&mut self, directory: &str, track: Track) -> Result<(), String> {
+        let target = self
+            .directory_index
+            .get(directory)
+            .cloned()
+            .ok_or_else(|| {
+                CabinetError::DirectoryNotFound {
+                    name: directory.to_string(),
+                }
+                .context("while inserting track")
+                .to_string()
+            })?;
+        let pos = target.start + self.tracks[target.clone()].partition_point(|t| t.id < track.id);
+
+        self.index_secondary(&track);
+        self.tracks.insert(pos, track);
+        for (name, range) in self.directory_index.iter_mut() {
+            if name == directory {
+                range.end += 1;
+            } else if range.start >= pos {
+                *range = range.start + 1..range.end + 1;
+            }
+        }
+
+        self.reindex_from(pos);
+        self.invalidate_stats();
+        Ok(())
+    }
+
+    // 将曲目插入目录，目录不存在时在曲目表末尾新建
+    fn insert_track(&mut self, directory: &str, track: Track) -> Result<(), String> {
+        if !self.directory_index.contains_key(directory) {
+            let start = self.tracks.len();
+            self.directory_index
+                .insert(directory.to_string(), start..start);
+        }
+        self.insert_into_directory(directory, track)
+    }
+
+    // 从扁平表中移除一段区间，并修正目录区间与全部索引，返回被移除的曲目
+    fn remove_range(&mut self, removed: Range<usize>) -> Vec<Track> {
+        let removed_tracks: Vec<Track> = self.tracks.drain(removed.clone()).collect();
+        for track in &removed_tracks {
+            self.id_index.remove(&track.id);
+            self.unindex_secondary(track);
+            self.track_cache.invalidate(track.id);
+        }
+
+        // 区间端点：位于被移除区间之后的前移，落在其中的收缩到起点
+        let shift = |pos: usize| {
+            if pos >= removed.end {
+                pos - removed.len()
+            } else {
+                pos.min(removed.start)
+            }
+        };
+        for range in self.directory_index.values_mut() {
+            *range = shift(range.start)..shift(range.end);
+        }
+
+        self.reindex_from(removed.start);
+        self.invalidate_stats();
+        removed_tracks
+    }
+
+    // 移除单个曲目
+    fn remove_track(&mut self, id: u32) -> Option<Track> {
+        let index = *self.id_index.get(&id)?;
+        self.remove_range(index..index + 1).pop()
+    }
+
+    // 将曲目移动到另一个已存在的目录
+    fn move_track(&mut self, id: u32, target_directory: &str) -> Result<(), String> {
+        if !self.directory_index.contains_key(target_directory) {
+            return Err(CabinetError::DirectoryNotFound {
+                name: target_directory.to_string(),
+            }
+            .context("while moving track")
+            .to_string());
+        }
+        let track = self.remove_track(id).ok_or_else(|| {
+            CabinetError::TrackNotFound { id }
+                .context("while moving track")
+                .to_string()
+        })?;
+        self.insert_into_directory(target_directory, track)
+    }
+
+    // 按去重策略过滤待导入的曲目（同时处理批次内部的重复），返回 (保留的, 跳过的)。
+    // OverwriteWithNewer 策略下被替换的已存在曲目会直接从曲目表中移除
+    fn dedup_tracks(
+        &mut self,
+        new_tracks: Vec<Track>,
+        strategy: DedupStrategy,
+    ) -> (Vec<Track>, Vec<Track>) {
+        let decision = decide_dedup(new_tracks, strategy, |sha256| {
+            self.newest_existing_mtime(sha256, &(0..0))
+        });
+        for sha256 in &decision.replaced {
+            for id in self.sha256_index.get(sha256).cloned().unwrap_or_default() {
+                self.remove_track(id);
+            }
+        }
+        (decision.kept, decision.skipped)
+    }
+
+    // 不修改曲目表地预演 replace_directory 中的去重：目标目录中原有的曲目视为已移除，
+    // OverwriteWithNewer 策略下会被替换的曲目也不会真正移除，返回 (会保留的, 会跳过的)
+    fn preview_replace_directory(
+        &self,
+        directory: &str,
+        mut new_tracks: Vec<Track>,
+        strategy: DedupStrategy,
+    ) -> (Vec<Track>, Vec<Track>) {
+        new_tracks.sort_by(|a, b| a.absolute_path.cmp(&b.absolute_path));
+        let replaced = self.directory_index.get(directory).cloned().unwrap_or(0..0);
+        let decision = decide_dedup(new_tracks, strategy, |sha256| {
+            self.newest_existing_mtime(sha256, &replaced)
+        });
+        (decision.kept, decision.skipped)
+    }
+
+    // 与 sha256 相同的已存在曲目中最新的修改时间，忽略下标在 excluded 中的曲目；没有时为 None
+    fn newest_existing_mtime(&self, sha256: &str, excluded: &Range<usize>) -> Option<u64> {
+        self.sha256_index
+            .get(sha256)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.id_index.get(id))
+            .filter(|&&index| !excluded.contains(&index))
+            .map(|&index| self.tracks[index].mtime)
+            .max()
+    }
+
+    // 重新登记下标 start 及之后曲目的 id 索引
+    fn reindex_from(&mut self, start: usize) {
+        for (index, track) in self.tracks.iter().enumerate().skip(start) {
+            self.id_index.insert(track.id, index);
+        }
+    }
+
+    // 登记以曲目 ID 为值的二级索引（sha256 / 艺术家 / 流派）
+    fn index_secondary(&mut self, track: &Track) {
+        self.sha256_index
+            .entry(track.sha256.clone())
+            .or_default()
+            .push(track.id);
+        self.artist_index
+            .entry(normalize_name(&track.artist))
+            .or_default()
+            .push(track.id);
+        self.genre_index
+            .entry(normalize_name(&track.genre))
+            .or_default()
+            .push(track.id);
+    }
+
+    // 注销以曲目 ID 为值的二级索引
+    fn unindex_secondary(&mut self, track: &Track) {
+        remove_from_index(&mut self.sha256_index, &track.sha256, track.id);
+        remove_from_index(
+            &mut self.artist_index,
+            &normalize_name(&track.artist),
+            track.id,
+        );
+        remove_from_index(
+            &mut self.genre_index,
+            &normalize_name(&track.genre),
+            track.id,
+        );
+    }
+
+    // 以同 ID 的新曲目替换原曲目（位置不变）并更新二级索引，返回原曲目
+    fn update_track(&mut self, track: Track) -> Option<Track> {
+        let index = *self.id_index.get(&track.id)?;
+        let original = std::mem::replace(&mut self.tracks[index], track.clone());
+        self.unindex_secondary(&original);
+        self.index_secondary(&track);
+        self.track_cache.invalidate(track.id);
+        self.invalidate_stats();
+        Some(original)
+    }
+
+    // 查找曲目所在的目录
+    fn directory_of(&self, id: u32) -> Option<String> {
+        let index = *self.id_index.get(&id)?;
+        self.directory_index
+            .iter()
+            .find(|(_, range)| range.contains(&index))
+            .map(|(name, _)| name.clone())
+    }
+
+    // 按 ID 列表取出曲目（忽略不存在的 ID）
+    fn tracks_by_ids(&self, ids: &[u32]) -> Vec<Track> {
+        ids.iter()
+            .filter_map(|id| self.id_index.get(id))
+            .map(|&index| self.tracks[index].clone())
+            .collect()
+    }
+
+    // 清空曲目表与全部索引
+    fn clear(&mut self) {
+        self.tracks.clear();
+        self.directory_index.clear();
+        self.id_index.clear();
+        self.sha256_index.clear();
+        self.artist_index.clear();
+        self.genre_index.clear();
+        self.track_cache.invalidate_all();
+        self.invalidate_stats();
+    }
+
+    // 清空曲库统计缓存
+    fn invalidate_stats(&mut self) {
+        *self.stats_cache = LibraryStatsCache::default();
+    }
+
+    // 全部曲目难度（play_level）的平均值与总体标准差，未缓存时计算并缓存；曲库为空时均为 0
+    fn difficulty_mean_std_dev(&mut self) -> (f64, f64) {
+        if let Some(cached) = self.stats_cache.difficulty {
+            return cached;
+        }
+        let count = self.tracks.len() as f64;
+        let result = if self.tracks.is_empty() {
+            (0.0, 0.0)
+        } else {
+            let mean = self
+                .tracks
+                .iter()
+                .map(|track| track.play_level as f64)
+                .sum::<f64>()
+                / count;
+            let variance = self
+                .tracks
+                .iter()
+                .map(|track| (track.play_level as f64 - mean).powi(2))
+                .sum::<f64>()
+                / count;
+            (mean, variance.sqrt())
+        };
+        self.stats_cache.difficulty = Some(result);
+        result
+    }
+
+    // 清空 id 索引与二级索引，按扁平表重新登记（目录区间不变）
+    fn rebuild_indexes(&mut self) {
+        self.id_index.clear();
+        self.sha256_index.clear();
+        self.artist_index.clear();
+        self.genre_index.clear();
+        self.reindex_from(0);
+        let tracks = std::mem::take(&mut *self.tracks);
+        for track in &tracks {
+            self.index_secondary(track);
+        }
+        *self.tracks = tracks;
+        self.track_cache.invalidate_all();
+    }
+
+    // 扫描后的快速一致性检查：sha256 索引登记的曲目数与曲目表一致、曲目 ID 不重复，
+    // 返回发现的问题
+    fn quick_consistency_issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let indexed: usize = self.sha256_index.values().map(Vec::len).sum();
+        if indexed != self.tracks.len() {
+            issues.push(format!(
+                "sha256_index has {} entries, track table has {} tracks",
+                indexed,
+                self.tracks.len()
+            ));
+        }
+        let mut ids = HashSet::with_capacity(self.tracks.len());
+        for track in self.tracks.iter() {
+            if !ids.insert(track.id) {
+                issues.push(format!("track id {} is used more than once", track.id));
+            }
+        }
+        issues
+    }
+
+    // 校验 id 索引与扁平表是否一致
+    fn is_id_index_consistent(&self) -> bool {
+        self.id_index.len() == self.tracks.len()
+            && self
+                .tracks
+                .iter()
+                .enumerate()
+                .all(|(index, track)| self.id_index.get(&track.id) == Some(&index))
+    }
+
+    // 校验全部索引与曲目表的一致性
+    fn health_report(&self) -> HealthReport {
+        let mut report = HealthReport::default();
+
+        let mut id_index_ok = true;
+        for (&id, &index) in self.id_index.iter() {
+            if self.tracks.get(index).map(|track| track.id) != Some(id) {
+                id_index_ok = false;
+                report.orphan_count += 1;
+                report.details.push(format!(
+                    "id_index: id {} points to invalid index {}",
+                    id, index
+                ));
+            }
+        }
+        for (index, track) in self.tracks.iter().enumerate() {
+            if self.id_index.get(&track.id) != Some(&index) {
+                id_index_ok = false;
+                report.inconsistency_count += 1;
+                report.details.push(format!(
+                    "id_index: track {} at index {} is not indexed",
+                    track.id, index
+                ));
+            }
+        }
+        report.id_index_ok = id_index_ok;
+
+        report.sha256_index_ok = self.check_name_index(
+            "sha256_index",
+            &self.sha256_index,
+            |track| track.sha256.clone(),
+            &mut report,
+        );
+        report.artist_index_ok = self.check_name_index(
+            "artist_index",
+            &self.artist_index,
+            |track| normalize_name(&track.artist),
+            &mut report,
+        );
+        report.genre_index_ok = self.check_name_index(
+            "genre_index",
+            &self.genre_index,
+            |track| normalize_name(&track.genre),
+            &mut report,
+        );
+
+        let directory_count: usize = self.directory_index.values().map(|range| range.len()).sum();
+        if directory_count != self.tracks.len()
+            || self
+                .directory_index
+                .values()
+                .any(|range| range.end > self.tracks.len())
+        {
+            report.inconsistency_count += 1;
+            report.details.push(format!(
+                "directory_index: ranges cover {} tracks, track table has {}",
+                directory_count,
+                self.tracks.len()
+            ));
+        }
+        report
+    }
+
+    // 辅助函数：校验以曲目 ID 为值的二级索引，将问题记入 report，返回索引是否一致
+    fn check_name_index(
+        &self,
+        name: &str,
+        index: &HashMap<String, Vec<u32>>,
+        key_of: impl Fn(&Track) -> String,
+        report: &mut HealthReport,
+    ) -> bool {
+        let mut ok = true;
+        for (key, ids) in index {
+            for id in ids {
+                match self.id_index.get(id).and_then(|&i| self.tracks.get(i)) {
+                    None => {
+                        ok = false;
+                        report.orphan_count += 1;
+                        report.details.push(format!(
+                            "{}: key {:?} refers to missing track {}",
+                            name, key, id
+                        ));
+                    }
+                    Some(track) if key_of(track) != *key => {
+                        ok = false;
+                        report.inconsistency_count += 1;
+                        report.details.push(format!(
+                            "{}: track {} is indexed under wrong key {:?}",
+                            name, id, key
+                        ));
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+        for track in self.tracks.iter() {
+            let indexed = index
+                .get(&key_of(track))
+                .is_some_and(|ids| ids.contains(&track.id));
+            if !indexed {
+                ok = false;
+                report.inconsistency_count += 1;
+                report
+                    .details
+                    .push(format!("{}: track {} is not indexed", name, track.id));
+            }
+        }
+        ok
+    }
+}
+
+// 状态一致性检查结果
+#[derive(Clone, Default, serde::Serialize)]
+struct HealthReport {
+    id_index_ok: bool,
+    sha256_index_ok: bool,
+    artist_index_ok: bool,
+    genre_index_ok: bool,
+    // 指向不存在曲目的索引条目数
+    orphan_count: u32,
+    // 缺失或错误的索引条目数（包括目录区间与曲目表不符）
+    inconsistency_count: u32,
+    details: Vec<String>,
+}
+
+// 去重决策结果
+struct DedupDecision {
+    kept: Vec<Track>,
+    skipped: Vec<Track>,
+    // 需要被新曲目替换（从曲目表中移除）的已存在曲目的哈希
+    replaced: Vec<String>,
+}
+
+// 辅助函数：按去重策略决定待导入曲目的保留、跳过与替换（同时处理批次内部的重复），
+// 不修改曲目表。newest_existing 返回与哈希相同的已存在曲目中最新的修改时间，没有时为 None
+fn decide_dedup(
+    new_tracks: Vec<Track>,
+    strategy: DedupStrategy,
+    newest_existing: impl Fn(&str) -> Option<u64>,
+) -> DedupDecision {
+    let mut decision = DedupDecision {
+        kept: Vec::new(),
+        skipped: Vec::new(),
+        replaced: Vec::new(),
+    };
+    if strategy == DedupStrategy::AllowDuplicates {
+        decision.kept = new_tracks;
+        return decision;
+    }
+
+    let overwrite = strategy == DedupStrategy::OverwriteWithNewer;
+    let mut kept_by_hash: HashMap<String, usize> = HashMap::new();
+    for track in new_tracks {
+        // 批次内部的重复
+        if let Some(&index) = kept_by_hash.get(&track.sha256) {
+            if overwrite && track.mtime > decision.kept[index].mtime {
+                decision
+                    .skipped
+                    .push(std::mem::replace(&mut decision.kept[index], track));
+            } else {
+                decision.skipped.push(track);
+            }
+            continue;
+        }
+
+        // 与已存在曲目的重复
+        if let Some(newest) = newest_existing(&track.sha256) {
+            if !(overwrite && track.mtime > newest) {
+                decision.skipped.push(track);
+                continue;
+            }
+            decision.replaced.push(track.sha256.clone());
+        }
+
+        kept_by_hash.insert(track.sha256.clone(), decision.kept.len());
+        decision.kept.push(track);
+    }
+    decision
+}
+
+// 辅助函数：从名称索引中移除一个曲目 ID，列表为空时移除整个键
+fn remove_from_index(index: &mut HashMap<String, Vec<u32>>, key: &str, id: u32) {
+    if let Some(ids) = index.get_mut(key) {
+        ids.retain(|&existing| existing != id);
+        if ids.is_empty() {
+            index.remove(key);
+        }
+    }
+}
+
+// 辅助函数：名称规范化（NFC 规范化后转小写），用作索引键
+fn normalize_name(name: &str) -> String {
+    name.trim().nfc().collect::<String>().to_lowercase()
+}
+
+// 辅助函数：字节的十六进制字符串形式
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// 辅助函数：由规范化后的艺术家与谱面所在文件夹生成稳定的专辑 ID
+fn album_id_for(artist: &str, path: &Path) -> u32 {
+    let parent = path.parent().unwrap_or(path);
+    stable_id(&[
+        normalize_name(artist).as_bytes(),
+        parent.to_string_lossy().as_bytes(),
+    ])
+}
+
+// 辅助函数：由各部分内容的 SHA256 前 4 字节生成稳定的 ID
+fn stable_id(parts: &[&[u8]]) -> u32 {
+    let mut hasher = Sha256::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            hasher.update([0]);
+        }
+        hasher.update(part);
+    }
+    let digest = hasher.finalize();
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+// 辅助函数：谱面所属的包文件夹（导入根目录下的第一级子文件夹，直接位于根目录时为根目录本身）
+fn pack_folder(root: &Path, path: &Path) -> PathBuf {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut components = relative.components();
+    match (components.next(), components.next()) {
+        (Some(first), Some(_)) => root.join(first),
+        _ => root.to_path_buf(),
+    }
+}
+
+// 辅助函数：为导入的曲目设置包 ID
+fn assign_pack_ids(tracks: &mut [Track], root: &Path) {
+    for track in tracks {
+        let folder = pack_folder(root, &track.absolute_path);
+        track.pack_id = Some(stable_id(&[folder.to_string_lossy().as_bytes()]));
+    }
 }
 
 // 曲目数据结构
-#[derive(Clone, serde::Serialize)]
+#[derive(Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
 struct Track {
     id: u32,
     title: String,
     artist: String,
     genre: String,
+    bpm: f64,
+    // 谱面中出现的最低 / 最高 BPM
+    bpm_min: f64,
+    bpm_max: f64,
+    // 代表性 BPM（音符最多的 BPM，见 effective_bpm），用于排序
+    effective_bpm: f64,
+    play_level: u8,
+    key_mode: KeyMode,
+    chart_type: ChartType,
+    note_count: u32,
+    ln_count: u32,
+    scratch_count: u32,
+    bga_layer_count: u32,
+    has_bga: bool,
+    // 预览音频的时长（毫秒）
+    preview_duration_ms: Option<u32>,
+    // 由 BPM 与小节数估算的谱面时长（毫秒）
+    estimated_duration_ms: Option<u32>,
+    // 计入停止事件后的谱面时长（毫秒）
+    chart_duration_ms: Option<u64>,
+    // 平均每秒音符数（由 chart_duration_ms 计算）
+    note_density: f64,
+    // 任意 1 秒内的最大音符数
+    peak_nps: f64,
+    // 是否存在非 4/4 拍的小节（02 通道）
+    has_variable_time_signature: bool,
+    // 是否存在非 1.0 倍的滚动速度（SC 通道）
+    has_scroll_changes: bool,
+    // 最小 / 最大滚动速度倍率（包含初始的 1.0）
+    min_scroll: f64,
+    max_scroll: f64,
+    // 是否存在负的滚动速度（音符反向移动）
+    has_negative_scroll: bool,
+    // 力度指标（见 rating::compute_power_factor）
+    power_factor: f64,
+    // 谱面文件大小（字节）
+    file_size: u64,
+    // 和弦统计
+    chord_stats: ChordStats,
+    // 各轨道音符数（SP 谱面）
+    lane_breakdown: Option<LaneBreakdown>,
+    // 两侧各轨道音符数（DP / PMS 谱面）
+    double_lane_breakdown: Option<DoubleLaneBreakdown>,
+    // 推荐的血条类型
+    gauge_type: GaugeType,
+    // 音源素材的来源（#MATERIAL）
+    material: Option<String>,
+    // #OCTFP 与音高的八度偏移（#OCTAVE）
+    octfp: Option<u8>,
+    octave: Option<i8>,
+    // #CDDA 指定播放的 CD 音轨编号
+    cdda_tracks: Vec<u32>,
+    // 是否使用 CD 音轨（cdda_tracks 非空）
+    is_cdda_chart: bool,
+    // 是否存在 #WAVCMD 音源处理命令
+    has_wavcmd: bool,
     sha256: String,
+    // 只由物件与 BPM 时间线计算的谱面指纹（十六进制），格式不同而内容相同的谱面一致
+    chart_fingerprint: Option<String>,
+    absolute_path: PathBuf,
+    // 标题 / 艺术家的来源
+    metadata_source: MetadataSource,
+    // 由用户修改过的元数据字段（title / artist / genre），随 sidecar 保存
+    user_edited_fields: Vec<String>,
+    // 所属专辑（同一文件夹下同一艺术家的谱面）
+    album_id: Option<u32>,
+    // 所属包（导入根目录下的第一级子文件夹）
+    pack_id: Option<u32>,
+    // 文件修改时间（Unix 时间戳，秒）
+    mtime: u64,
+    // 扫描时的读取校验在全部重试后仍失败，内容可能已损坏
+    integrity_warning: bool,
+    // 找不到音源文件的 #WAVxx 定义（如 WAV01），仅由磁盘上的谱面导入时检查
+    missing_keysounds: Vec<String>,
+    // 全部物件，首次调用 get_notes 时解析并缓存，不参与序列化
+    #[serde(skip)]
+    notes: Option<Vec<NoteObject>>,
 }
 
 #[tauri::command]
 async fn get_directories(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let dirs = state.directories.lock().await;
-    Ok(dirs.keys().cloned().collect())
+    let directory_index = state.directory_index.lock().await;
+    Ok(directory_index.keys().cloned().collect())
 }
 
 #[tauri::command]
 async fn get_tracks(directory: String, state: State<'_, AppState>) -> Result<Vec<Track>, String> {
-    let dirs = state.directories.lock().await;
-    dirs.get(&directory)
-        .cloned()
-        .ok_or_else(|| "Directory not found".into())
+    directory_tracks(&state, &directory).await
+}
+
+// 辅助函数：复制目录在扁平曲目表中所占的区间
+async fn directory_tracks(state: &AppState, directory: &str) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    let directory_index = state.directory_index.lock().await;
+    directory_index
+        .get(directory)
+        .map(|range| tracks[range.clone()].to_vec())
+        .ok_or_else(|| {
+            CabinetError::DirectoryNotFound {
+                name: directory.to_string(),
+            }
+            .context("while getting tracks")
+            .to_string()
+        })
+}
+
+// 曲目排序字段
+#[derive(Clone, Copy, serde::Deserialize)]
+enum SortField {
+    Title,
+    Artist,
+    // 按代表性 BPM 排序
+    #[serde(rename = "BPM")]
+    Bpm,
+    PlayLevel,
+    NoteCount,
 }
 
+// 获取目录中按指定字段升序排序的曲目
 #[tauri::command]
-async fn handle_dropped_files(
-    window: Window,
-    files: Vec<PathBuf>,
+async fn get_tracks_sorted(
+    directory: String,
+    field: SortField,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    let mut tracks = get_tracks(directory, state).await?;
+    match field {
+        SortField::Title => tracks.sort_by(|a, b| a.title.cmp(&b.title)),
+        SortField::Artist => tracks.sort_by(|a, b| a.artist.cmp(&b.artist)),
+        SortField::Bpm => tracks.sort_by(|a, b| a.effective_bpm.total_cmp(&b.effective_bpm)),
+        SortField::PlayLevel => tracks.sort_by_key(|track| track.play_level),
+        SortField::NoteCount => tracks.sort_by_key(|track| track.note_count),
+    }
+    Ok(tracks)
+}
+
+// 列表展示用的曲目摘要，避免序列化完整的 Track
+#[derive(Clone, serde::Serialize)]
+struct TrackSummary {
+    id: u32,
+    title: String,
+    artist: String,
+    genre: String,
+    bpm_min: f64,
+    bpm_max: f64,
+    play_level: u8,
+    note_count: u32,
+    sha256: String,
+}
+
+impl From<&Track> for TrackSummary {
+    fn from(track: &Track) -> Self {
+        Self {
+            id: track.id,
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            genre: track.genre.clone(),
+            bpm_min: track.bpm_min,
+            bpm_max: track.bpm_max,
+            play_level: track.play_level,
+            note_count: track.note_count,
+            sha256: track.sha256.clone(),
+        }
+    }
+}
+
+// 分页获取目录中的曲目摘要，offset 超出范围时返回空列表
+#[tauri::command]
+async fn get_tracks_page(
+    directory: String,
+    offset: usize,
+    limit: usize,
+    state: State<'_, AppState>,
+) -> Result<Vec<TrackSummary>, String> {
+    let tracks = state.tracks.lock().await;
+    let directory_index = state.directory_index.lock().await;
+    let range = directory_index.get(&directory).ok_or_else(|| {
+        CabinetError::DirectoryNotFound {
+            name: directory.clone(),
+        }
+        .context("while paging tracks")
+        .to_string()
+    })?;
+    Ok(tracks[range.clone()]
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(TrackSummary::from)
+        .collect())
+}
+
+// 每批通过 IPC 通道发送的曲目摘要数
+const STREAM_BATCH_SIZE: usize = 100;
+
+// 通过 IPC 通道分批发送目录中的曲目摘要，避免大目录一次性序列化阻塞界面
+#[tauri::command]
+async fn stream_tracks(
+    directory: String,
+    channel: Channel<Vec<TrackSummary>>,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    for path in files {
-        if path.is_dir() {
-            process_directory(&window, &state, path).await?;
-        } else if let Some(ext) = path.extension() {
-            if ext == "zip" {
-                // TODO:
-                // process_archive(&window, &state, path).await?;
+    let summaries: Vec<TrackSummary> = {
+        let tracks = state.tracks.lock().await;
+        let directory_index = state.directory_index.lock().await;
+        let range = directory_index.get(&directory).ok_or_else(|| {
+            CabinetError::DirectoryNotFound {
+                name: directory.clone(),
             }
-        }
+            .context("while streaming tracks")
+            .to_string()
+        })?;
+        tracks[range.clone()]
+            .iter()
+            .map(TrackSummary::from)
+            .collect()
+    };
+
+    for batch in summaries.chunks(STREAM_BATCH_SIZE) {
+        channel.send(batch.to_vec()).map_err(|e| e.to_string())?;
     }
     Ok(())
 }
 
-// 添加目录主逻辑
+// 专辑：同一文件夹下同一艺术家的谱面
+#[derive(Clone, serde::Serialize)]
+struct AlbumGroup {
+    album_id: u32,
+    folder_name: String,
+    artist: String,
+    tracks: Vec<u32>,
+}
+
+// 获取目录下的专辑列表（按首次出现的顺序）
 #[tauri::command]
-async fn add_directory(
-    window: Window,
-    path: PathBuf,
+async fn get_albums(
+    directory: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
-    process_directory(&window, &state, path).await
+) -> Result<Vec<AlbumGroup>, String> {
+    directory_albums(&state, &directory).await
 }
 
-// 处理目录的异步函数
-async fn process_directory(
-    window: &Window,
-    state: &State<'_, AppState>,
-    path: PathBuf,
-) -> Result<(), String> {
-    let mut tracks = Vec::new();
-    let total_files = count_audio_files(&path).await?;
-    let mut processed = 0;
+// 辅助函数：按 album_id 对目录下的曲目分组（按首次出现的顺序）
+async fn directory_albums(state: &AppState, directory: &str) -> Result<Vec<AlbumGroup>, String> {
+    let tracks = state.tracks.lock().await;
+    let directory_index = state.directory_index.lock().await;
+    let range = directory_index.get(directory).ok_or_else(|| {
+        CabinetError::DirectoryNotFound {
+            name: directory.to_string(),
+        }
+        .context("while listing albums")
+        .to_string()
+    })?;
 
-    for entry in WalkDir::new(&path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(is_audio_file)
-    {
-        let track = process_single_file(entry.path()).await?;
-        tracks.push(track);
+    let mut albums: Vec<AlbumGroup> = Vec::new();
+    let mut positions: HashMap<u32, usize> = HashMap::new();
+    for track in &tracks[range.clone()] {
+        let Some(album_id) = track.album_id else {
+            continue;
+        };
+        let position = *positions.entry(album_id).or_insert_with(|| {
+            albums.push(AlbumGroup {
+                album_id,
+                folder_name: track
+                    .absolute_path
+                    .parent()
+                    .and_then(|parent| parent.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                artist: track.artist.clone(),
+                tracks: Vec::new(),
+            });
+            albums.len() - 1
+        });
+        albums[position].tracks.push(track.id);
+    }
+    Ok(albums)
+}
 
-        // 更新进度
-        processed += 1;
-        let progress = (processed as f64 / total_files as f64) * 100.0;
-        *state.current_progress.lock().await = progress;
-        window
-            .emit("import_progress", progress)
-            .map_err(|e| e.to_string())?;
+// BMS 包：导入根目录下同一第一级子文件夹中的谱面
+#[derive(Clone, serde::Serialize)]
+struct Pack {
+    id: u32,
+    name: String,
+    directory_path: String,
+    track_ids: Vec<u32>,
+    track_count: u32,
+}
+
+// 按包文件夹对目录下的曲目分组（按首次出现的顺序）
+#[tauri::command]
+async fn detect_packs(directory: String, state: State<'_, AppState>) -> Result<Vec<Pack>, String> {
+    directory_packs(&state, &directory).await
+}
+
+// 辅助函数：按 pack_id 对目录下的曲目分组（按首次出现的顺序）
+async fn directory_packs(state: &AppState, directory: &str) -> Result<Vec<Pack>, String> {
+    let tracks = state.tracks.lock().await;
+    let directory_index = state.directory_index.lock().await;
+    let range = directory_index.get(directory).ok_or_else(|| {
+        CabinetError::DirectoryNotFound {
+            name: directory.to_string(),
+        }
+        .context("while detecting packs")
+        .to_string()
+    })?;
+    let root = state
+        .directory_roots
+        .lock()
+        .await
+        .get(directory)
+        .cloned()
+        .ok_or_else(|| {
+            CabinetError::DirectoryRootNotFound {
+                name: directory.to_string(),
+            }
+            .context("while detecting packs")
+            .to_string()
+        })?;
+
+    let mut packs: Vec<Pack> = Vec::new();
+    let mut positions: HashMap<u32, usize> = HashMap::new();
+    for track in &tracks[range.clone()] {
+        let Some(pack_id) = track.pack_id else {
+            continue;
+        };
+        let position = *positions.entry(pack_id).or_insert_with(|| {
+            let folder = pack_folder(&root, &track.absolute_path);
+            packs.push(Pack {
+                id: pack_id,
+                name: folder
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                directory_path: folder.to_string_lossy().into_owned(),
+                track_ids: Vec::new(),
+                track_count: 0,
+            });
+            packs.len() - 1
+        });
+        packs[position].track_ids.push(track.id);
+        packs[position].track_count += 1;
     }
+    Ok(packs)
+}
 
-    // 更新状态
-    let dir_name = path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .ok_or("Invalid directory name")?
-        .to_string();
+// 获取包中的全部曲目
+#[tauri::command]
+async fn get_pack_tracks(pack_id: u32, state: State<'_, AppState>) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| track.pack_id == Some(pack_id))
+        .cloned()
+        .collect())
+}
 
-    let mut dirs = state.directories.lock().await;
-    dirs.insert(dir_name, tracks);
+// 获取专辑中的全部曲目
+#[tauri::command]
+async fn get_album_tracks(album_id: u32, state: State<'_, AppState>) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| track.album_id == Some(album_id))
+        .cloned()
+        .collect())
+}
 
-    Ok(())
+// 按 ID 获取曲目，优先使用缓存
+#[tauri::command]
+async fn get_track_by_id(id: u32, state: State<'_, AppState>) -> Result<Option<Track>, String> {
+    Ok(track_by_id(&state, id).await)
 }
 
-// 处理单个音频文件
-async fn process_single_file(path: &Path) -> Result<Track, String> {
-    // 读取文件内容计算SHA256
-    let content = fs::read(path).await.map_err(|e| e.to_string())?;
-    let mut hasher = Sha256::new();
-    hasher.update(&content);
-    let hash = format!("{:x}", hasher.finalize());
+// 辅助函数：经由缓存或 id_index 查找曲目，查到后放入缓存
+async fn track_by_id(state: &AppState, id: u32) -> Option<Track> {
+    if let Some(track) = state.track_cache.get(id) {
+        return Some(track);
+    }
+    let library = state.lock_library().await;
+    let track = library
+        .id_index
+        .get(&id)
+        .map(|&index| library.tracks[index].clone());
+    if let Some(track) = &track {
+        library.track_cache.insert(track.clone());
+    }
+    track
+}
 
-    // 解析元数据（示例使用占位值，实际应使用音频文件元数据解析库）
-    Ok(Track {
-        id: rand::random(),
-        title: path
-            .file_stem()
-            .and_then(|n| n.to_str())
-            .unwrap_or("Unknown")
-            .to_string(),
-        artist: "Unknown Artist".into(),
-        genre: "Unknown Genre".into(),
-        sha256: hash,
+// 按 ID 获取完整的曲目信息（配合 get_tracks_page 的摘要使用），不存在时返回错误
+#[tauri::command]
+async fn get_track_detail(id: u32, state: State<'_, AppState>) -> Result<Track, String> {
+    get_track_by_id(id, state).await?.ok_or_else(|| {
+        CabinetError::TrackNotFound { id }
+            .context("while getting track detail")
+            .to_string()
     })
 }
 
-// 辅助函数：统计音频文件数量
-async fn count_audio_files(path: &Path) -> Result<usize, String> {
-    let count = WalkDir::new(path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(is_audio_file)
-        .count();
-    Ok(count)
+// 曲目 ID 是否存在
+#[tauri::command]
+async fn track_exists(id: u32, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.id_index.lock().await.contains_key(&id))
 }
 
-// 辅助函数：判断是否是音频文件
-fn is_audio_file(entry: &walkdir::DirEntry) -> bool {
-    entry
-        .path()
-        .extension()
-        .map(|ext| {
-            let ext = ext.to_str().unwrap_or("").to_lowercase();
-            matches!(ext.as_str(), "mp3" | "wav" | "flac" | "ogg")
-        })
-        .unwrap_or(false)
+// 删除曲目
+#[tauri::command]
+async fn remove_track(window: Window, id: u32, state: State<'_, AppState>) -> Result<(), String> {
+    let mut library = state.lock_library().await;
+    library.remove_track(id).ok_or_else(|| {
+        CabinetError::TrackNotFound { id }
+            .context("while removing track")
+            .to_string()
+    })?;
+    drop(library);
+    emit_library_event(&window, LibraryEvent::TrackRemoved(id)).await
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .manage(AppState::default())
-        .invoke_handler(tauri::generate_handler![
-            get_directories,
-            get_tracks,
-            handle_dropped_files,
-            add_directory
+// LR2 曲库导入结果
+#[derive(Clone, serde::Serialize)]
+struct Lr2ImportReport {
+    imported: u32,
+    // SHA256 已在曲库中（或在同一数据库中重复出现）的行
+    skipped_duplicate: u32,
+    // 缺少有效 SHA256 的行
+    failed: u32,
+}
+
+// 从 LR2 曲库数据库（LR2files.db）导入曲目元数据到 target_directory（目录不存在时新建），
+// 不读取谱面文件；之后可通过 migrate_library 由谱面补全其余字段
+#[tauri::command]
+async fn import_from_lr2_db(
+    window: Window,
+    path: PathBuf,
+    target_directory: String,
+    state: State<'_, AppState>,
+) -> Result<Lr2ImportReport, String> {
+    let report = import_lr2_songs(&state, &path, &target_directory).await?;
+    emit_library_event(&window, LibraryEvent::DirectoryAdded(target_directory)).await?;
+    schedule_consistency_check(&window, &state).await;
+    Ok(report)
+}
+
+// 辅助函数：读取 LR2 数据库并将其中的曲目插入 target_directory，返回导入结果
+async fn import_lr2_songs(
+    state: &AppState,
+    path: &Path,
+    target_directory: &str,
+) -> Result<Lr2ImportReport, String> {
+    let songs = lr2::read_lr2_songs(path).await?;
+
+    let mut report = Lr2ImportReport {
+        imported: 0,
+        skipped_duplicate: 0,
+        failed: 0,
+    };
+    let mut library = state.lock_library().await;
+    for song in songs {
+        let Some(track) = track_from_lr2_song(song) else {
+            report.failed += 1;
+            continue;
+        };
+        if library.sha256_index.contains_key(&track.sha256) {
+            report.skipped_duplicate += 1;
+            continue;
+        }
+        let track = Track {
+            id: state.next_id.fetch_add(1, Ordering::Relaxed),
+            ..track
+        };
+        library.insert_track(target_directory, track)?;
+        report.imported += 1;
+    }
+    Ok(report)
+}
+
+// 辅助函数：由 LR2 数据库中的一行构建曲目（尚未分配 ID），缺少有效 SHA256 时返回 None
+fn track_from_lr2_song(song: lr2::Lr2Song) -> Option<Track> {
+    let sha256 = song
+        .sha256
+        .filter(|sha256| sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit()))?;
+    let bpm = song
+        .bpm
+        .filter(|bpm| *bpm > 0.0)
+        .unwrap_or(bms_parse::DEFAULT_BPM);
+    let artist = song.artist.unwrap_or_else(|| "Unknown Artist".into());
+    let absolute_path = song.path.unwrap_or_default();
+    Some(Track {
+        title: song.title.unwrap_or_else(|| "Unknown".into()),
+        album_id: Some(album_id_for(&artist, &absolute_path)),
+        artist,
+        genre: "Unknown Genre".into(),
+        bpm,
+        bpm_min: bpm,
+        bpm_max: bpm,
+        effective_bpm: bpm,
+        note_count: song.notes_count.unwrap_or(0),
+        sha256,
+        absolute_path,
+        metadata_source: MetadataSource::Lr2Database,
+        ..Default::default()
+    })
+}
+
+// 将曲目移入回收站：从曲库与全部索引中移除，可由 restore_from_trash 放回原目录
+#[tauri::command]
+async fn soft_delete_track(
+    window: Window,
+    app: AppHandle,
+    id: u32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    move_to_trash(&state, id).await?;
+    let trash = state.trash.lock().await;
+    trash::save_trash(&trash_file_path(&app)?, &trash).await?;
+    drop(trash);
+    emit_library_event(&window, LibraryEvent::TrackRemoved(id)).await
+}
+
+// 辅助函数：将曲目从曲库与全部索引中移除并放入回收站
+async fn move_to_trash(state: &AppState, id: u32) -> Result<(), String> {
+    let mut library = state.lock_library().await;
+    let directory = library.directory_of(id).ok_or_else(|| {
+        CabinetError::TrackNotFound { id }
+            .context("while moving track to trash")
+            .to_string()
+    })?;
+    let track = library.remove_track(id).ok_or_else(|| {
+        CabinetError::TrackNotFound { id }
+            .context("while moving track to trash")
+            .to_string()
+    })?;
+    state
+        .trash
+        .lock()
+        .await
+        .push(trash::TrashedTrack::new(track, directory));
+    Ok(())
+}
+
+// 将回收站中的曲目放回删除前所在的目录（沿用原 ID）
+#[tauri::command]
+async fn restore_from_trash(
+    window: Window,
+    app: AppHandle,
+    id: u32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let track = restore_trashed(&state, id).await?;
+    let trash = state.trash.lock().await;
+    trash::save_trash(&trash_file_path(&app)?, &trash).await?;
+    drop(trash);
+    emit_library_event(&window, LibraryEvent::TrackAdded(track)).await
+}
+
+// 辅助函数：将回收站中的曲目放回删除前所在的目录（目录已不存在时重新创建），返回恢复的曲目
+async fn restore_trashed(state: &AppState, id: u32) -> Result<Track, String> {
+    let mut library = state.lock_library().await;
+    let mut trash = state.trash.lock().await;
+    let position = trash
+        .iter()
+        .position(|trashed| trashed.track.id == id)
+        .ok_or_else(|| {
+            CabinetError::NotInTrash { id }
+                .context("while restoring from trash")
+                .to_string()
+        })?;
+    if library.id_index.contains_key(&id) {
+        return Err(CabinetError::TrackAlreadyExists { id }
+            .context("while restoring from trash")
+            .to_string());
+    }
+    let trashed = trash.remove(position);
+    let track = trashed.track.clone();
+    library.insert_track(&trashed.directory, trashed.track)?;
+    Ok(track)
+}
+
+// 永久删除回收站中的全部曲目，返回删除的数量
+#[tauri::command]
+async fn empty_trash(app: AppHandle, state: State<'_, AppState>) -> Result<u32, String> {
+    let mut trash = state.trash.lock().await;
+    let count = trash.len() as u32;
+    trash.clear();
+    trash::save_trash(&trash_file_path(&app)?, &trash).await?;
+    Ok(count)
+}
+
+// 获取回收站中的全部曲目（按删除先后排序）
+#[tauri::command]
+async fn get_trash(state: State<'_, AppState>) -> Result<Vec<trash::TrashedTrack>, String> {
+    Ok(state.trash.lock().await.clone())
+}
+
+// 辅助函数：回收站文件的路径
+fn trash_file_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(trash::TRASH_FILE_NAME))
+        .map_err(|e| e.to_string())
+}
+
+// 移除目录及其全部曲目
+#[tauri::command]
+async fn remove_directory(
+    window: Window,
+    directory: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let mut library = state.lock_library().await;
+    let range = library.directory_index.remove(&directory).ok_or_else(|| {
+        CabinetError::DirectoryNotFound {
+            name: directory.clone(),
+        }
+        .context("while removing directory")
+        .to_string()
+    })?;
+    library.remove_range(range);
+    drop(library);
+
+    state.directory_roots.lock().await.remove(&directory);
+    emit_library_event(&window, LibraryEvent::DirectoryRemoved(directory)).await
+}
+
+// 设置当前窗口接收的曲库事件类型（见 events::EVENT_TYPES），为空时接收全部事件
+#[tauri::command]
+async fn subscribe_to_events(
+    window: Window,
+    filter: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if let Some(unknown) = filter
+        .iter()
+        .find(|event_type| !events::EVENT_TYPES.contains(&event_type.as_str()))
+    {
+        return Err(CabinetError::UnknownValue {
+            kind: "event type".into(),
+            value: unknown.clone(),
+        }
+        .context("while subscribing to events")
+        .to_string());
+    }
+
+    let mut subscriptions = state.event_subscriptions.lock().await;
+    if filter.is_empty() {
+        subscriptions.remove(window.label());
+    } else {
+        subscriptions.insert(window.label().to_string(), filter.into_iter().collect());
+    }
+    Ok(())
+}
+
+// 辅助函数：向订阅了该事件类型的全部窗口发送曲库事件
+async fn emit_library_event(window: &Window, event: LibraryEvent) -> Result<(), String> {
+    let state = window.state::<AppState>();
+    let subscriptions = state.event_subscriptions.lock().await;
+    for label in window.webview_windows().into_keys() {
+        let interested = subscriptions
+            .get(&label)
+            .is_none_or(|event_types| event_types.contains(event.event_type()));
+        if interested {
+            window
+                .emit_to(label.as_str(), events::LIBRARY_EVENT, &event)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+// 开发用：校验 id 索引与曲目表是否一致
+#[tauri::command]
+async fn validate_index_consistency(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.lock_library().await.is_id_index_consistent())
+}
+
+// 开发用：检查全部索引与曲目表的一致性（仅调试构建可用）
+#[tauri::command]
+async fn check_state_health(state: State<'_, AppState>) -> Result<HealthReport, String> {
+    if !cfg!(debug_assertions) {
+        return Err(CabinetError::DebugOnly {
+            command: "check_state_health".into(),
+        }
+        .context("while checking state health")
+        .to_string());
+    }
+    Ok(state.lock_library().await.health_report())
+}
+
+// 重建索引的结果（各索引重建后的条目数）
+#[derive(Clone, serde::Serialize)]
+struct ReindexReport {
+    id_index_rebuilt: u32,
+    sha256_index_rebuilt: u32,
+    artist_index_rebuilt: u32,
+    genre_index_rebuilt: u32,
+    elapsed_ms: u64,
+}
+
+// 按曲目表从头重建 id 索引与全部二级索引，用于修复 check_state_health 发现的不一致；
+// 重建期间持有曲目表相关锁，其他修改操作等待其完成
+#[tauri::command]
+async fn reindex_library(
+    window: Window,
+    state: State<'_, AppState>,
+) -> Result<ReindexReport, String> {
+    reindex(&window, &state).await
+}
+
+// 辅助函数：重建索引并发送 ReindexCompleted 事件
+async fn reindex(window: &Window, state: &AppState) -> Result<ReindexReport, String> {
+    let report = rebuild_indexes(state).await;
+    emit_library_event(window, LibraryEvent::ReindexCompleted(report.clone())).await?;
+    Ok(report)
+}
+
+// 辅助函数：持有曲目表相关锁重建全部索引，返回重建结果
+async fn rebuild_indexes(state: &AppState) -> ReindexReport {
+    let started = Instant::now();
+    let mut library = state.lock_library().await;
+    library.rebuild_indexes();
+    ReindexReport {
+        id_index_rebuilt: library.id_index.len() as u32,
+        sha256_index_rebuilt: library.sha256_index.len() as u32,
+        artist_index_rebuilt: library.artist_index.len() as u32,
+        genre_index_rebuilt: library.genre_index.len() as u32,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+// 辅助函数：导入完成后按 auto_consistency_check 配置在后台检查曲库一致性，全部导入路径共用
+async fn schedule_consistency_check(window: &Window, state: &AppState) {
+    if state.config.lock().await.auto_consistency_check {
+        tauri::async_runtime::spawn(check_consistency_after_scan(window.clone()));
+    }
+}
+
+// 辅助函数：扫描后在后台检查曲库一致性，发现问题时发送 ConsistencyWarning 曲库事件（负载为
+// 以 `; ` 分隔的问题描述）并重建索引
+async fn check_consistency_after_scan(window: Window) {
+    let state = window.state::<AppState>();
+    for event in repair_inconsistencies(&state).await {
+        if let Err(e) = emit_library_event(&window, event).await {
+            tracing::warn!(error = %e, "failed to emit consistency check event");
+        }
+    }
+}
+
+// 辅助函数：执行快速一致性检查，发现问题时重建索引，返回需要发送的 ConsistencyWarning 与
+// ReindexCompleted 事件；没有问题时返回空列表
+async fn repair_inconsistencies(state: &AppState) -> Vec<LibraryEvent> {
+    let issues = state.lock_library().await.quick_consistency_issues();
+    if issues.is_empty() {
+        return Vec::new();
+    }
+    let description = issues.join("; ");
+    tracing::warn!(issues = %description, "library inconsistency detected after scan");
+    let report = rebuild_indexes(state).await;
+    vec![
+        LibraryEvent::ConsistencyWarning(description),
+        LibraryEvent::ReindexCompleted(report),
+    ]
+}
+
+// 开发用：停止全部目录监视，清空内存中的全部曲目、索引、导入记录、扫描历史、回收站、播放列表、
+// 标签、游玩会话与事件订阅，并重置 ID 计数器与曲库版本（仅调试构建可用）。
+// 扫描配置与已写入磁盘的文件保持不变
+#[tauri::command]
+async fn reset_state(state: State<'_, AppState>) -> Result<(), String> {
+    if !cfg!(debug_assertions) {
+        return Err(CabinetError::DebugOnly {
+            command: "reset_state".into(),
+        }
+        .context("while resetting state")
+        .to_string());
+    }
+    reset_app_state(&state).await;
+    Ok(())
+}
+
+// 辅助函数：执行 reset_state 的清空与重置
+async fn reset_app_state(state: &AppState) {
+    // 先停止监视，避免监视任务向清空后的曲库继续导入
+    let watched: Vec<PathBuf> = state
+        .watched_directories
+        .lock()
+        .await
+        .keys()
+        .cloned()
+        .collect();
+    for path in watched {
+        stop_watching(state, &path).await;
+    }
+
+    let mut library = state.lock_library().await;
+    library.clear();
+    let id_start = state.config.lock().await.id_start.unwrap_or(0);
+    state.next_id.store(id_start, Ordering::Relaxed);
+    state.schema_version.store(0, Ordering::Release);
+    state.import_conflicts.lock().await.clear();
+    state.locked_track_ids.lock().await.clear();
+    *state.active_session.lock().await = None;
+    state.session_history.lock().await.clear();
+    state.next_session_id.store(0, Ordering::Relaxed);
+    state.playlists.lock().await.clear();
+    state.next_playlist_id.store(0, Ordering::Relaxed);
+    state.track_tags.lock().await.clear();
+    state.trash.lock().await.clear();
+    drop(library);
+
+    state.scan_history.lock().await.clear();
+    state.directory_roots.lock().await.clear();
+    state.pending_imports.lock().await.clear();
+    state.event_subscriptions.lock().await.clear();
+    // 正在运行的预读取任务发现队列为空后自行结束
+    state.prefetch.lock().await.queue.clear();
+    *state.current_progress.lock().await = 0.0;
+    *state.current_scan.lock().await = None;
+}
+
+// 将全部目录与曲目导出为快照文件（.msgpack / .mpk 为 MessagePack，否则为 JSON）
+#[tauri::command]
+async fn export_state_snapshot(path: PathBuf, state: State<'_, AppState>) -> Result<(), String> {
+    let snapshot = build_state_snapshot(&state).await;
+    snapshot::write_snapshot(&path, &snapshot).await
+}
+
+// 辅助函数：按目录名排序收集全部目录与曲目
+async fn build_state_snapshot(state: &AppState) -> snapshot::StateSnapshot {
+    let library = state.lock_library().await;
+    let directory_roots = state.directory_roots.lock().await;
+    let mut directories: Vec<snapshot::DirectorySnapshot> = library
+        .directory_index
+        .iter()
+        .map(|(name, range)| snapshot::DirectorySnapshot {
+            name: name.clone(),
+            root: directory_roots.get(name).cloned(),
+            tracks: library.tracks[range.clone()].to_vec(),
+        })
+        .collect();
+    drop(directory_roots);
+    drop(library);
+
+    directories.sort_by(|a, b| a.name.cmp(&b.name));
+    snapshot::StateSnapshot {
+        snapshot_version: snapshot::SNAPSHOT_VERSION,
+        directories,
+    }
+}
+
+// 将全部目录与曲目写入二进制索引（bincode），返回文件大小（字节）
+#[tauri::command]
+async fn serialize_library_to_binary(
+    path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let snapshot = build_state_snapshot(&state).await;
+    snapshot::write_binary_index(&path, &snapshot).await
+}
+
+// 以二进制索引替换当前曲库，曲目沿用索引中的 ID，返回载入的曲目数
+#[tauri::command]
+async fn load_library_from_binary(
+    path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    load_binary_index(&state, &path).await
+}
+
+// 辅助函数：读取二进制索引并替换曲库与目录根路径
+async fn load_binary_index(state: &AppState, path: &Path) -> Result<u32, String> {
+    let snapshot = snapshot::read_binary_index(path).await?;
+
+    let mut loaded = 0;
+    let mut library = state.lock_library().await;
+    let mut directory_roots = state.directory_roots.lock().await;
+    library.clear();
+    directory_roots.clear();
+    for directory in snapshot.directories {
+        for track in directory.tracks {
+            // 之后新导入的曲目不能与索引中的 ID 重复
+            state.next_id.fetch_max(track.id + 1, Ordering::Relaxed);
+            library.insert_track(&directory.name, track)?;
+            loaded += 1;
+        }
+        if let Some(root) = directory.root {
+            directory_roots.insert(directory.name, root);
+        }
+    }
+    Ok(loaded)
+}
+
+// 辅助函数：二进制索引的默认路径
+fn binary_index_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(snapshot::BINARY_INDEX_FILE_NAME))
+        .map_err(|e| e.to_string())
+}
+
+// 从快照文件恢复，与当前曲库合并：SHA256 相同的曲目被快照中的版本替换（沿用原 ID），
+// 其余曲目分配新 ID 加入对应目录，返回恢复的曲目数
+#[tauri::command]
+async fn restore_state_snapshot(path: PathBuf, state: State<'_, AppState>) -> Result<u32, String> {
+    restore_snapshot(&state, &path).await
+}
+
+// 辅助函数：读取快照并按 SHA-256 合并到当前曲库
+async fn restore_snapshot(state: &AppState, path: &Path) -> Result<u32, String> {
+    let snapshot = snapshot::read_snapshot(path).await?;
+
+    let mut restored = 0;
+    let mut library = state.lock_library().await;
+    for directory in &snapshot.directories {
+        for track in &directory.tracks {
+            let existing_ids = library
+                .sha256_index
+                .get(&track.sha256)
+                .cloned()
+                .unwrap_or_default();
+            let id = existing_ids
+                .first()
+                .copied()
+                .unwrap_or_else(|| state.next_id.fetch_add(1, Ordering::Relaxed));
+            for existing_id in existing_ids {
+                library.remove_track(existing_id);
+            }
+            library.insert_track(
+                &directory.name,
+                Track {
+                    id,
+                    ..track.clone()
+                },
+            )?;
+            restored += 1;
+        }
+    }
+    drop(library);
+
+    let mut directory_roots = state.directory_roots.lock().await;
+    for directory in snapshot.directories {
+        if let Some(root) = directory.root {
+            directory_roots.entry(directory.name).or_insert(root);
+        }
+    }
+    Ok(restored)
+}
+
+// 曲库迁移结果
+#[derive(Clone, serde::Serialize)]
+struct MigrationReport {
+    migrated_tracks: u32,
+    schema_version_before: u32,
+    schema_version_after: u32,
+}
+
+// 依次执行从当前版本到 target_version 的迁移步骤，重新读取谱面补全曲目中缺失的字段；
+// 读取或解析失败的曲目保持不变。不支持降级
+#[tauri::command]
+async fn migrate_library(
+    target_version: u32,
+    state: State<'_, AppState>,
+) -> Result<MigrationReport, String> {
+    migrate_tracks(&state, target_version).await
+}
+
+// 辅助函数：migrate_library 的实现
+async fn migrate_tracks(state: &AppState, target_version: u32) -> Result<MigrationReport, String> {
+    if target_version > migrations::LATEST_SCHEMA_VERSION {
+        return Err(CabinetError::UnknownValue {
+            kind: "schema version".into(),
+            value: target_version.to_string(),
+        }
+        .context("while migrating library")
+        .to_string());
+    }
+    let schema_version_before = state.schema_version.load(Ordering::Acquire);
+    if target_version < schema_version_before {
+        return Err(CabinetError::DowngradeNotSupported {
+            from: schema_version_before,
+            to: target_version,
+        }
+        .context("while migrating library")
+        .to_string());
+    }
+    let steps = &migrations::MIGRATIONS[schema_version_before as usize..target_version as usize];
+
+    let tracks: Vec<Track> = if steps.is_empty() {
+        Vec::new()
+    } else {
+        state.lock_library().await.tracks.to_vec()
+    };
+    // 只保留需要修改的曲目的解析结果，写回时对最新的曲目重新执行迁移，避免覆盖期间的编辑
+    let mut charts = Vec::new();
+    for mut track in tracks {
+        let Ok(content) = fs::read(&track.absolute_path).await else {
+            continue;
+        };
+        let Ok(chart) = parse_chart_file(&track.absolute_path, &content) else {
+            continue;
+        };
+        if apply_migrations(steps, &mut track, &chart) {
+            charts.push((track.id, chart));
+        }
+    }
+
+    let mut library = state.lock_library().await;
+    let mut migrated_tracks = 0;
+    for (id, chart) in charts {
+        let Some(&index) = library.id_index.get(&id) else {
+            continue;
+        };
+        let mut track = library.tracks[index].clone();
+        if apply_migrations(steps, &mut track, &chart) {
+            library.update_track(track);
+            migrated_tracks += 1;
+        }
+    }
+    state
+        .schema_version
+        .store(target_version, Ordering::Release);
+    drop(library);
+
+    Ok(MigrationReport {
+        migrated_tracks,
+        schema_version_before,
+        schema_version_after: target_version,
+    })
+}
+
+// 辅助函数：依次对曲目执行迁移步骤，返回曲目是否被修改
+fn apply_migrations(
+    steps: &[migrations::Migration],
+    track: &mut Track,
+    chart: &ParsedChart,
+) -> bool {
+    // 不能使用 any：每个步骤都要执行
+    let mut changed = false;
+    for step in steps {
+        changed |= step(track, chart);
+    }
+    changed
+}
+
+// 按艺术家获取曲目，case_sensitive 为 false 时忽略大小写
+#[tauri::command]
+async fn get_tracks_by_artist(
+    artist: String,
+    case_sensitive: bool,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    Ok(tracks_by_artist(&state, &artist, case_sensitive).await)
+}
+
+// 辅助函数：经由 artist_index 查找艺术家的曲目
+async fn tracks_by_artist(state: &AppState, artist: &str, case_sensitive: bool) -> Vec<Track> {
+    let library = state.lock_library().await;
+    let ids = library
+        .artist_index
+        .get(&normalize_name(artist))
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut tracks = library.tracks_by_ids(ids);
+    if case_sensitive {
+        tracks.retain(|track| track.artist == artist);
+    }
+    tracks
+}
+
+// 按流派获取曲目（忽略大小写）
+#[tauri::command]
+async fn get_tracks_by_genre(
+    genre: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    Ok(tracks_by_genre(&state, &genre).await)
+}
+
+// 辅助函数：经由 genre_index 查找流派的曲目
+async fn tracks_by_genre(state: &AppState, genre: &str) -> Vec<Track> {
+    let library = state.lock_library().await;
+    let ids = library
+        .genre_index
+        .get(&normalize_name(genre))
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    library.tracks_by_ids(ids)
+}
+
+// 获取元数据由文件夹名推断得到的曲目
+#[tauri::command]
+async fn get_tracks_with_inferred_metadata(
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| track.metadata_source == MetadataSource::InferredFromPath)
+        .cloned()
+        .collect())
+}
+
+// 可整理的元数据字段
+const UNCATEGORIZED_FIELDS: [&str; 3] = ["genre", "artist", "title"];
+
+// 获取指定字段（genre / artist / title）仍为占位值或为空的曲目，用于整理元数据；
+// 用户修改过（记录在 user_edited_fields 中，可经由 sidecar 恢复）的字段不会出现在结果中
+#[tauri::command]
+async fn get_uncategorized_tracks(
+    field: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    if !UNCATEGORIZED_FIELDS.contains(&field.as_str()) {
+        return Err(CabinetError::UnknownValue {
+            kind: "field".into(),
+            value: field,
+        }
+        .context("while getting uncategorized tracks")
+        .to_string());
+    }
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| is_uncategorized(track, &field))
+        .cloned()
+        .collect())
+}
+
+// 统计每个字段仍为占位值或为空的曲目数（判断方式同 get_uncategorized_tracks）
+#[tauri::command]
+async fn get_uncategorized_count(
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, u32>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(UNCATEGORIZED_FIELDS
+        .iter()
+        .map(|&field| {
+            let count = tracks
+                .iter()
+                .filter(|track| is_uncategorized(track, field))
+                .count() as u32;
+            (field.to_string(), count)
+        })
+        .collect())
+}
+
+// 辅助函数：曲目的指定字段是否为导入时的占位值（"Unknown" / "Unknown Artist" /
+// "Unknown Genre"）或为空，用户修改过的字段即使与占位值相同也视为已整理
+fn is_uncategorized(track: &Track, field: &str) -> bool {
+    let (value, placeholder) = match field {
+        "genre" => (&track.genre, "Unknown Genre"),
+        "artist" => (&track.artist, "Unknown Artist"),
+        "title" => (&track.title, "Unknown"),
+        _ => return false,
+    };
+    if track
+        .user_edited_fields
+        .iter()
+        .any(|edited| edited == field)
+    {
+        return false;
+    }
+    let value = value.trim();
+    value.is_empty() || value == placeholder
+}
+
+// 获取力度指标在 [min_pf, max_pf] 范围内的曲目
+#[tauri::command]
+async fn get_tracks_by_power_factor_range(
+    min_pf: f64,
+    max_pf: f64,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| (min_pf..=max_pf).contains(&track.power_factor))
+        .cloned()
+        .collect())
+}
+
+// 获取使用 CD 音轨（#CDDA）的曲目
+#[tauri::command]
+async fn get_tracks_with_cdda(state: State<'_, AppState>) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| track.is_cdda_chart)
+        .cloned()
+        .collect())
+}
+
+// 获取峰值 NPS 在 [min_nps, max_nps] 范围内的曲目（平均 NPS 见 note_density）
+#[tauri::command]
+async fn get_tracks_by_nps_range(
+    min_nps: f64,
+    max_nps: f64,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| (min_nps..=max_nps).contains(&track.peak_nps))
+        .cloned()
+        .collect())
+}
+
+// 按推荐血条类型获取曲目（normal / hard / exhard / hazard）
+#[tauri::command]
+async fn get_tracks_by_gauge_type(
+    gauge: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    let gauge = GaugeType::from_name(&gauge).ok_or_else(|| {
+        CabinetError::UnknownValue {
+            kind: "gauge type".into(),
+            value: gauge.clone(),
+        }
+        .context("while getting tracks by gauge type")
+        .to_string()
+    })?;
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .filter(|track| track.gauge_type == gauge)
+        .cloned()
+        .collect())
+}
+
+// 由得分率（百分比）计算评级
+#[tauri::command]
+async fn get_score_grade(score: f64) -> Result<rating::ScoreGrade, String> {
+    Ok(rating::score_grade(score))
+}
+
+// 流派统计信息
+#[derive(Clone, serde::Serialize)]
+struct GenreStat {
+    genre: String,
+    track_count: u32,
+    avg_play_level: f64,
+}
+
+// 获取各流派的曲目数与平均难度，按曲目数降序排序
+#[tauri::command]
+async fn get_genre_stats(state: State<'_, AppState>) -> Result<Vec<GenreStat>, String> {
+    Ok(genre_stats(&state).await)
+}
+
+// 辅助函数：按 genre_index 统计各流派的曲目数与平均难度，按曲目数降序排列
+async fn genre_stats(state: &AppState) -> Vec<GenreStat> {
+    let library = state.lock_library().await;
+    let mut stats: Vec<GenreStat> = library
+        .genre_index
+        .values()
+        .filter_map(|ids| {
+            let tracks = library.tracks_by_ids(ids);
+            let first = tracks.first()?;
+            let total_level: f64 = tracks.iter().map(|track| track.play_level as f64).sum();
+            Some(GenreStat {
+                genre: first.genre.clone(),
+                track_count: tracks.len() as u32,
+                avg_play_level: total_level / tracks.len() as f64,
+            })
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        b.track_count
+            .cmp(&a.track_count)
+            .then_with(|| a.genre.cmp(&b.genre))
+    });
+    stats
+}
+
+// 曲库统计报告
+#[derive(serde::Serialize)]
+struct LibraryReport {
+    total_tracks: u32,
+    unique_artists: u32,
+    unique_genres: u32,
+    // p5、p25、p50、p75、p95
+    bpm_percentiles: [f64; 5],
+    note_count_percentiles: [f64; 5],
+    key_mode_distribution: HashMap<String, u32>,
+    chart_type_distribution: HashMap<String, u32>,
+    // 曲目数最多的 10 位艺术家
+    top_artists: Vec<(String, u32)>,
+    // 扫描历史中全部扫描的用时之和
+    total_scan_time_secs: f64,
+    total_library_size_bytes: u64,
+}
+
+// 生成曲库统计报告
+#[tauri::command]
+async fn generate_library_report(state: State<'_, AppState>) -> Result<LibraryReport, String> {
+    Ok(library_report(&state).await)
+}
+
+// 辅助函数：统计曲目表与扫描历史生成报告
+async fn library_report(state: &AppState) -> LibraryReport {
+    let library = state.lock_library().await;
+
+    let mut key_mode_distribution = HashMap::new();
+    let mut chart_type_distribution = HashMap::new();
+    for track in library.tracks.iter() {
+        *key_mode_distribution
+            .entry(track.key_mode.as_str().to_string())
+            .or_insert(0) += 1;
+        *chart_type_distribution
+            .entry(track.chart_type.as_str().to_string())
+            .or_insert(0) += 1;
+    }
+
+    let mut top_artists: Vec<(String, u32)> = library
+        .artist_index
+        .values()
+        .filter_map(|ids| {
+            let &index = library.id_index.get(ids.first()?)?;
+            Some((library.tracks[index].artist.clone(), ids.len() as u32))
+        })
+        .collect();
+    top_artists.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_artists.truncate(10);
+
+    let report = LibraryReport {
+        total_tracks: library.tracks.len() as u32,
+        unique_artists: library.artist_index.len() as u32,
+        unique_genres: library.genre_index.len() as u32,
+        bpm_percentiles: nearest_rank_percentiles(
+            library.tracks.iter().map(|track| track.bpm).collect(),
+            REPORT_PERCENTILES,
+        ),
+        note_count_percentiles: nearest_rank_percentiles(
+            library
+                .tracks
+                .iter()
+                .map(|track| track.note_count as f64)
+                .collect(),
+            REPORT_PERCENTILES,
+        ),
+        key_mode_distribution,
+        chart_type_distribution,
+        top_artists,
+        total_scan_time_secs: 0.0,
+        total_library_size_bytes: library.tracks.iter().map(|track| track.file_size).sum(),
+    };
+    drop(library);
+
+    LibraryReport {
+        total_scan_time_secs: state
+            .scan_history
+            .lock()
+            .await
+            .iter()
+            .map(|summary| summary.elapsed_seconds)
+            .sum(),
+        ..report
+    }
+}
+
+// 曲库统计报告中的百分位
+const REPORT_PERCENTILES: [f64; 5] = [5.0, 25.0, 50.0, 75.0, 95.0];
+
+// 辅助函数：以最近秩法计算各百分位的值，空列表返回全 0
+fn nearest_rank_percentiles<const N: usize>(
+    mut values: Vec<f64>,
+    percentiles: [f64; N],
+) -> [f64; N] {
+    if values.is_empty() {
+        return [0.0; N];
+    }
+    values.sort_by(f64::total_cmp);
+    percentiles.map(|p| {
+        let rank = (p / 100.0 * values.len() as f64).ceil() as usize;
+        values[rank.clamp(1, values.len()) - 1]
+    })
+}
+
+// 曲库难度分布
+#[derive(Clone, serde::Serialize)]
+struct DifficultyStats {
+    mean: f64,
+    // 总体标准差
+    std_dev: f64,
+    // p10、p20、…、p90
+    percentiles: [f64; 9],
+}
+
+// 获取全部曲目难度（play_level）的平均值、标准差与百分位
+#[tauri::command]
+async fn get_difficulty_stats(state: State<'_, AppState>) -> Result<DifficultyStats, String> {
+    let mut library = state.lock_library().await;
+    let (mean, std_dev) = library.difficulty_mean_std_dev();
+    let percentiles = nearest_rank_percentiles(
+        library
+            .tracks
+            .iter()
+            .map(|track| track.play_level as f64)
+            .collect(),
+        [10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0],
+    );
+    Ok(DifficultyStats {
+        mean,
+        std_dev,
+        percentiles,
+    })
+}
+
+// 获取曲目难度相对于曲库的 z 分数 (play_level - mean) / std_dev；
+// 全部曲目难度相同（标准差为 0）时返回 0
+#[tauri::command]
+async fn relative_difficulty(track_id: u32, state: State<'_, AppState>) -> Result<f64, String> {
+    track_relative_difficulty(&state, track_id).await
+}
+
+// 辅助函数：计算曲目难度相对曲库平均值的 z 分数
+async fn track_relative_difficulty(state: &AppState, track_id: u32) -> Result<f64, String> {
+    let mut library = state.lock_library().await;
+    let &index = library.id_index.get(&track_id).ok_or_else(|| {
+        CabinetError::TrackNotFound { id: track_id }
+            .context("while computing relative difficulty")
+            .to_string()
+    })?;
+    let play_level = library.tracks[index].play_level as f64;
+    let (mean, std_dev) = library.difficulty_mean_std_dev();
+    Ok(z_score(play_level, mean, std_dev))
+}
+
+// 辅助函数：z 分数 (value - mean) / std_dev，标准差为 0 时为 0
+fn z_score(value: f64, mean: f64, std_dev: f64) -> f64 {
+    if std_dev == 0.0 {
+        return 0.0;
+    }
+    (value - mean) / std_dev
+}
+
+// 将曲目移动到另一个目录
+#[tauri::command]
+async fn move_track_to_directory(
+    track_id: u32,
+    target_directory: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .lock_library()
+        .await
+        .move_track(track_id, &target_directory)
+}
+
+// 将一个目录下的全部曲目移动到另一个目录，返回移动的曲目数
+#[tauri::command]
+async fn move_all_tracks(
+    source_directory: String,
+    target_directory: String,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    move_directory_tracks(&state, &source_directory, &target_directory).await
+}
+
+// 辅助函数：在一次加锁内逐个移动目录下的曲目
+async fn move_directory_tracks(
+    state: &AppState,
+    source_directory: &str,
+    target_directory: &str,
+) -> Result<u32, String> {
+    let mut library = state.lock_library().await;
+    let source = library
+        .directory_index
+        .get(source_directory)
+        .cloned()
+        .ok_or_else(|| {
+            CabinetError::DirectoryNotFound {
+                name: source_directory.to_string(),
+            }
+            .context("while moving tracks")
+            .to_string()
+        })?;
+    if !library.directory_index.contains_key(target_directory) {
+        return Err(CabinetError::DirectoryNotFound {
+            name: target_directory.to_string(),
+        }
+        .context("while moving tracks")
+        .to_string());
+    }
+    if source_directory == target_directory {
+        return Ok(0);
+    }
+
+    let ids: Vec<u32> = library.tracks[source]
+        .iter()
+        .map(|track| track.id)
+        .collect();
+    for &id in &ids {
+        library.move_track(id, target_directory)?;
+    }
+    Ok(ids.len() as u32)
+}
+
+// 获取曲目的 BGA 事件（重新读取谱面文件解析）
+#[tauri::command]
+async fn get_bga_events(
+    track_id: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<BgaEvent>, String> {
+    let (path, content) = read_track_file(&state, track_id)
+        .await
+        .map_err(|e| e.context("while getting BGA events").to_string())?;
+    Ok(parse_chart_file(&path, &content)?.bga.events)
+}
+
+// 获取曲目的滚动速度变化（重新读取谱面文件解析）
+#[tauri::command]
+async fn get_scroll_events(
+    track_id: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<ScrollEvent>, String> {
+    let (path, content) = read_track_file(&state, track_id)
+        .await
+        .map_err(|e| e.context("while getting scroll events").to_string())?;
+    Ok(parse_chart_file(&path, &content)?.scrolls)
+}
+
+// 相似曲目推荐结果
+#[derive(serde::Serialize)]
+struct SimilarTrack {
+    track: Track,
+    score: f64,
+}
+
+// 推荐与指定曲目最相似的 top_n 首曲目（按相似度降序）
+#[tauri::command]
+async fn recommend_similar_tracks(
+    track_id: u32,
+    top_n: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<SimilarTrack>, String> {
+    let library = state.lock_library().await;
+    let target = library
+        .id_index
+        .get(&track_id)
+        .map(|&index| &library.tracks[index])
+        .ok_or_else(|| {
+            CabinetError::TrackNotFound { id: track_id }
+                .context("while recommending similar tracks")
+                .to_string()
+        })?;
+
+    let mut similar: Vec<SimilarTrack> = library
+        .tracks
+        .iter()
+        .filter(|track| track.id != track_id)
+        .map(|track| SimilarTrack {
+            score: similarity::compute_similarity(target, track),
+            track: track.clone(),
+        })
+        .collect();
+    similar.sort_by(|a, b| b.score.total_cmp(&a.score));
+    similar.truncate(top_n as usize);
+    Ok(similar)
+}
+
+// 基于游玩历史推荐的曲目
+#[derive(Clone, serde::Serialize)]
+struct RecommendedTrack {
+    track: Track,
+    // 与常玩曲目共有的用户标签 ID
+    shared_tags: Vec<u32>,
+    // 推荐理由（与常玩曲目共有的标签）
+    reason: String,
+}
+
+// 根据会话中的游玩记录推荐未玩或较少游玩的曲目（按推荐分数降序，最多 count 个）
+#[tauri::command]
+async fn recommend_from_history(
+    count: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<RecommendedTrack>, String> {
+    let library = state.lock_library().await;
+    let play_counts = play_counts(&state).await;
+    let track_tags = state.track_tags.lock().await;
+
+    Ok(similarity::recommend_from_history(
+        &library.tracks,
+        &play_counts,
+        &track_tags,
+        count as usize,
+    )
+    .into_iter()
+    .map(|recommendation| RecommendedTrack {
+        track: recommendation.track.clone(),
+        shared_tags: recommendation.shared_tags,
+        reason: recommendation.reason,
+    })
+    .collect())
+}
+
+// 辅助函数：统计每首曲目在会话历史与进行中的会话里的游玩次数
+async fn play_counts(state: &AppState) -> HashMap<u32, u32> {
+    let active_session = state.active_session.lock().await;
+    let session_history = state.session_history.lock().await;
+    let mut play_counts: HashMap<u32, u32> = HashMap::new();
+    for session in session_history.iter().chain(active_session.as_ref()) {
+        for &track_id in &session.tracks_played {
+            *play_counts.entry(track_id).or_default() += 1;
+        }
+    }
+    play_counts
+}
+
+// 获取曲目各轨道的音符数，SP 谱面返回 LaneBreakdown，DP 谱面返回 DoubleLaneBreakdown
+#[tauri::command]
+async fn get_lane_breakdown(
+    track_id: u32,
+    state: State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let library = state.lock_library().await;
+    let track = library
+        .id_index
+        .get(&track_id)
+        .map(|&index| &library.tracks[index])
+        .ok_or_else(|| {
+            CabinetError::TrackNotFound { id: track_id }
+                .context("while getting lane breakdown")
+                .to_string()
+        })?;
+
+    let value = if track.key_mode.is_double_play() {
+        serde_json::to_value(track.double_lane_breakdown)
+    } else {
+        serde_json::to_value(track.lane_breakdown)
+    };
+    value.map_err(|e| e.to_string())
+}
+
+// 获取曲目的全部物件（按时间排序），首次调用时读取谱面文件解析并缓存
+#[tauri::command]
+async fn get_notes(track_id: u32, state: State<'_, AppState>) -> Result<Vec<NoteObject>, String> {
+    load_notes(&state, track_id).await
+}
+
+// 辅助函数：取出曲目缓存的物件，尚未缓存时读取谱面文件解析并缓存
+async fn load_notes(state: &AppState, track_id: u32) -> Result<Vec<NoteObject>, String> {
+    {
+        let library = state.lock_library().await;
+        let index = *library.id_index.get(&track_id).ok_or_else(|| {
+            CabinetError::TrackNotFound { id: track_id }
+                .context("while loading notes")
+                .to_string()
+        })?;
+        if let Some(notes) = &library.tracks[index].notes {
+            return Ok(notes.clone());
+        }
+    }
+
+    let (path, content) = read_track_file(state, track_id)
+        .await
+        .map_err(|e| e.context("while loading notes").to_string())?;
+    let notes = parse_chart_file(&path, &content)?.notes;
+
+    let mut library = state.lock_library().await;
+    if let Some(&index) = library.id_index.get(&track_id) {
+        library.tracks[index].notes = Some(notes.clone());
+    }
+    Ok(notes)
+}
+
+// 每次 prefetch_tracks 最多加入队列的曲目数
+const PREFETCH_BATCH_SIZE: usize = 20;
+
+// 预读取时同时读取的曲目数
+const PREFETCH_CONCURRENCY: usize = 4;
+
+// 等待预读取的曲目
+struct PrefetchQueue {
+    queue: VecDeque<u32>,
+    // 后台任务每批同时读取的曲目数
+    max_concurrent: usize,
+    // 是否已有后台任务在处理队列
+    running: bool,
+}
+
+impl Default for PrefetchQueue {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            max_concurrent: PREFETCH_CONCURRENCY,
+            running: false,
+        }
+    }
+}
+
+// 在后台预先读取并解析即将用到的曲目的物件（最多 PREFETCH_BATCH_SIZE 个），
+// 之后的 get_notes 无需再读取磁盘。已缓存或已在队列中的曲目会被跳过，读取失败时忽略
+#[tauri::command]
+async fn prefetch_tracks(
+    ids: Vec<u32>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if enqueue_prefetch(&state, ids).await {
+        tauri::async_runtime::spawn(async move {
+            run_prefetch(&app.state::<AppState>()).await;
+        });
+    }
+    Ok(())
+}
+
+// 辅助函数：将曲目加入预读取队列（至多 PREFETCH_BATCH_SIZE 个），
+// 需要启动后台任务时将其标记为运行中并返回 true
+async fn enqueue_prefetch(state: &AppState, ids: Vec<u32>) -> bool {
+    let mut prefetch = state.prefetch.lock().await;
+    for id in ids.into_iter().take(PREFETCH_BATCH_SIZE) {
+        if !prefetch.queue.contains(&id) {
+            prefetch.queue.push_back(id);
+        }
+    }
+    if prefetch.running || prefetch.queue.is_empty() {
+        return false;
+    }
+    prefetch.running = true;
+    true
+}
+
+// 辅助函数：预读取后台任务，每批读取 max_concurrent 个曲目，队列为空时结束
+async fn run_prefetch(state: &AppState) {
+    loop {
+        let batch: Vec<u32> = {
+            let mut prefetch = state.prefetch.lock().await;
+            if prefetch.queue.is_empty() {
+                prefetch.running = false;
+                break;
+            }
+            let count = prefetch.max_concurrent.max(1).min(prefetch.queue.len());
+            prefetch.queue.drain(..count).collect()
+        };
+        future::join_all(batch.into_iter().map(|id| load_notes(state, id))).await;
+    }
+}
+
+// 取消尚未开始的预读取（正在读取的曲目仍会完成）
+#[tauri::command]
+async fn cancel_prefetch(state: State<'_, AppState>) -> Result<(), String> {
+    state.prefetch.lock().await.queue.clear();
+    Ok(())
+}
+
+// 获取曲目的和弦统计（使用导入时记录的结果）
+#[tauri::command]
+async fn get_chord_stats(track_id: u32, state: State<'_, AppState>) -> Result<ChordStats, String> {
+    let library = state.lock_library().await;
+    library
+        .id_index
+        .get(&track_id)
+        .map(|&index| library.tracks[index].chord_stats)
+        .ok_or_else(|| {
+            CabinetError::TrackNotFound { id: track_id }
+                .context("while getting chord stats")
+                .to_string()
+        })
+}
+
+// 按谱面指纹分组，返回包含两个及以上曲目的组（组内按 ID 排序）。指纹相同的谱面即使
+// SHA256 不同（如头部顺序或空白不同），物件与 BPM 变化也完全一致；没有指纹的曲目不参与分组
+#[tauri::command]
+async fn find_chart_duplicates_by_fingerprint(
+    state: State<'_, AppState>,
+) -> Result<Vec<Vec<Track>>, String> {
+    let tracks = state.tracks.lock().await;
+    let mut groups: HashMap<&str, Vec<Track>> = HashMap::new();
+    for track in tracks.iter() {
+        if let Some(fingerprint) = &track.chart_fingerprint {
+            groups.entry(fingerprint).or_default().push(track.clone());
+        }
+    }
+    let mut duplicates: Vec<Vec<Track>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort_by_key(|track| track.id);
+            group
+        })
+        .collect();
+    duplicates.sort_by_key(|group| group[0].id);
+    Ok(duplicates)
+}
+
+// 目录比较结果超过该条目数时只返回数量
+const DIRECTORY_DIFF_TRACK_LIMIT: usize = 5000;
+
+// 两个目录的曲目差异，以 SHA256 判断是否为同一谱面
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "kind")]
+enum DirectoryDiff {
+    Full {
+        only_in_a: Vec<Track>,
+        only_in_b: Vec<Track>,
+        // (A 中的曲目, B 中的曲目)，可用于比较同一谱面两份副本的元数据
+        in_both: Vec<(Track, Track)>,
+    },
+    // 条目数超过 DIRECTORY_DIFF_TRACK_LIMIT 时返回
+    Summary(DirectoryDiffSummary),
+}
+
+// 目录差异中各类曲目的数量
+#[derive(Clone, serde::Serialize)]
+struct DirectoryDiffSummary {
+    only_in_a: u32,
+    only_in_b: u32,
+    in_both: u32,
+}
+
+// 比较两个目录：仅在 A 中、仅在 B 中与两者都有的曲目。B 中有多个相同 SHA256 的曲目时
+// 与其中第一个配对
+#[tauri::command]
+async fn compare_directories(
+    dir_a: String,
+    dir_b: String,
+    state: State<'_, AppState>,
+) -> Result<DirectoryDiff, String> {
+    diff_directories(&state, &dir_a, &dir_b).await
+}
+
+async fn diff_directories(
+    state: &AppState,
+    dir_a: &str,
+    dir_b: &str,
+) -> Result<DirectoryDiff, String> {
+    let library = state.lock_library().await;
+    let directory_tracks = |name: &str| {
+        library
+            .directory_index
+            .get(name)
+            .map(|range| &library.tracks[range.clone()])
+            .ok_or_else(|| {
+                CabinetError::DirectoryNotFound {
+                    name: name.to_string(),
+                }
+                .context("while comparing directories")
+                .to_string()
+            })
+    };
+    let tracks_a = directory_tracks(dir_a)?;
+    let tracks_b = directory_tracks(dir_b)?;
+
+    let mut by_sha256_b: HashMap<&str, &Track> = HashMap::new();
+    for track in tracks_b {
+        by_sha256_b.entry(track.sha256.as_str()).or_insert(track);
+    }
+    let sha256_a: HashSet<&str> = tracks_a.iter().map(|track| track.sha256.as_str()).collect();
+
+    let (in_both, only_in_a): (Vec<&Track>, Vec<&Track>) = tracks_a
+        .iter()
+        .partition(|track| by_sha256_b.contains_key(track.sha256.as_str()));
+    let only_in_b: Vec<&Track> = tracks_b
+        .iter()
+        .filter(|track| !sha256_a.contains(track.sha256.as_str()))
+        .collect();
+
+    if in_both.len() + only_in_a.len() + only_in_b.len() > DIRECTORY_DIFF_TRACK_LIMIT {
+        return Ok(DirectoryDiff::Summary(DirectoryDiffSummary {
+            only_in_a: only_in_a.len() as u32,
+            only_in_b: only_in_b.len() as u32,
+            in_both: in_both.len() as u32,
+        }));
+    }
+    Ok(DirectoryDiff::Full {
+        only_in_a: only_in_a.into_iter().cloned().collect(),
+        only_in_b: only_in_b.into_iter().cloned().collect(),
+        in_both: in_both
+            .into_iter()
+            .map(|track| (track.clone(), by_sha256_b[track.sha256.as_str()].clone()))
+            .collect(),
+    })
+}
+
+// 将曲目的物件布局导出为 SVG 图像（重新读取谱面文件解析）
+#[tauri::command]
+async fn export_chart_svg(
+    track_id: u32,
+    output_path: PathBuf,
+    options: chart_svg::SvgOptions,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let (path, content) = read_track_file(&state, track_id)
+        .await
+        .map_err(|e| e.context("while exporting chart SVG").to_string())?;
+    let chart = parse_chart_file(&path, &content)?;
+    let svg = chart_svg::render_chart_svg(&chart, &options)?;
+    fs::write(&output_path, svg)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 比较两个曲目（差值为 B - A）
+#[tauri::command]
+async fn compare_charts(
+    track_id_a: u32,
+    track_id_b: u32,
+    state: State<'_, AppState>,
+) -> Result<similarity::ChartDiff, String> {
+    let library = state.lock_library().await;
+    let track = |id| {
+        library
+            .id_index
+            .get(&id)
+            .map(|&index| &library.tracks[index])
+            .ok_or_else(|| {
+                CabinetError::TrackNotFound { id }
+                    .context("while comparing charts")
+                    .to_string()
+            })
+    };
+    Ok(similarity::compare_tracks(
+        track(track_id_a)?,
+        track(track_id_b)?,
+    ))
+}
+
+// 辅助函数：读取曲目的谱面文件，返回 (文件路径, 文件内容)
+async fn read_track_file(
+    state: &AppState,
+    track_id: u32,
+) -> Result<(PathBuf, Vec<u8>), CabinetError> {
+    let path = {
+        let library = state.lock_library().await;
+        library
+            .id_index
+            .get(&track_id)
+            .map(|&index| library.tracks[index].absolute_path.clone())
+            .ok_or(CabinetError::TrackNotFound { id: track_id })?
+    };
+    let content = fs::read(&path).await?;
+    Ok((path, content))
+}
+
+// 分页获取全部曲目（按目录分段顺序）
+#[tauri::command]
+async fn get_tracks_flat(
+    offset: u32,
+    limit: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(tracks
+        .iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .cloned()
+        .collect())
+}
+
+// 模糊搜索结果
+#[derive(Clone, serde::Serialize)]
+struct FuzzyMatch {
+    track: Track,
+    edit_distance: u32,
+}
+
+// 按艺术家模糊搜索（Levenshtein 编辑距离）
+#[tauri::command]
+async fn fuzzy_search_by_artist(
+    query: String,
+    max_distance: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<FuzzyMatch>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(fuzzy_search(tracks.iter(), &query, max_distance, |track| {
+        &track.artist
+    }))
+}
+
+// 按流派模糊搜索（Levenshtein 编辑距离）
+#[tauri::command]
+async fn fuzzy_search_by_genre(
+    query: String,
+    max_distance: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<FuzzyMatch>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(fuzzy_search(tracks.iter(), &query, max_distance, |track| {
+        &track.genre
+    }))
+}
+
+// 辅助函数：对指定字段做小写化后的模糊匹配，按编辑距离升序、字段字母序排序
+fn fuzzy_search<'a>(
+    tracks: impl Iterator<Item = &'a Track>,
+    query: &str,
+    max_distance: u32,
+    field: impl Fn(&Track) -> &String,
+) -> Vec<FuzzyMatch> {
+    let query = query.to_lowercase();
+    let mut matches: Vec<FuzzyMatch> = tracks
+        .filter_map(|track| {
+            let distance = strsim::levenshtein(&query, &field(track).to_lowercase()) as u32;
+            (distance <= max_distance).then(|| FuzzyMatch {
+                track: track.clone(),
+                edit_distance: distance,
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        a.edit_distance
+            .cmp(&b.edit_distance)
+            .then_with(|| field(&a.track).cmp(field(&b.track)))
+            .then_with(|| a.track.title.cmp(&b.track.title))
+    });
+    matches
+}
+
+// 艺术家信息（用于自动补全）
+#[derive(Clone, serde::Serialize)]
+struct ArtistInfo {
+    name: String,
+    track_count: u32,
+}
+
+// 流派信息（用于自动补全）
+#[derive(Clone, serde::Serialize)]
+struct GenreInfo {
+    name: String,
+    track_count: u32,
+}
+
+// 获取全部艺术家（忽略大小写去重）
+#[tauri::command]
+async fn get_all_artists(state: State<'_, AppState>) -> Result<Vec<ArtistInfo>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(
+        collect_distinct_names(tracks.iter().map(|track| track.artist.as_str()))
+            .into_iter()
+            .map(|(name, track_count)| ArtistInfo { name, track_count })
+            .collect(),
+    )
+}
+
+// 获取全部流派（忽略大小写去重）
+#[tauri::command]
+async fn get_all_genres(state: State<'_, AppState>) -> Result<Vec<GenreInfo>, String> {
+    let tracks = state.tracks.lock().await;
+    Ok(
+        collect_distinct_names(tracks.iter().map(|track| track.genre.as_str()))
+            .into_iter()
+            .map(|(name, track_count)| GenreInfo { name, track_count })
+            .collect(),
+    )
+}
+
+// 艺术家统计
+#[derive(Clone, serde::Serialize)]
+struct ArtistAnalytics {
+    artist: String,
+    track_count: u32,
+    avg_note_count: f64,
+    // 代表性 BPM 的平均值
+    avg_bpm: f64,
+    // 不重复的流派（忽略大小写，保留第一次出现的写法）
+    genres: Vec<String>,
+    key_modes: Vec<String>,
+    // 该艺术家全部曲目在会话历史与进行中的会话里的游玩次数之和
+    total_play_count: u32,
+}
+
+// 按规范化艺术家名统计曲目，按曲目数降序排列
+#[tauri::command]
+async fn get_artist_analytics(state: State<'_, AppState>) -> Result<Vec<ArtistAnalytics>, String> {
+    Ok(artist_analytics(&state).await)
+}
+
+// 辅助函数：按规范化后的艺术家名称分组统计，按曲目数降序排列
+async fn artist_analytics(state: &AppState) -> Vec<ArtistAnalytics> {
+    let play_counts = play_counts(state).await;
+    let tracks = state.tracks.lock().await;
+    let mut groups: HashMap<String, (ArtistAnalytics, HashSet<String>)> = HashMap::new();
+    for track in tracks.iter() {
+        let (analytics, genre_keys) =
+            groups
+                .entry(normalize_name(&track.artist))
+                .or_insert_with(|| {
+                    (
+                        ArtistAnalytics {
+                            artist: track.artist.clone(),
+                            track_count: 0,
+                            avg_note_count: 0.0,
+                            avg_bpm: 0.0,
+                            genres: Vec::new(),
+                            key_modes: Vec::new(),
+                            total_play_count: 0,
+                        },
+                        HashSet::new(),
+                    )
+                });
+        // 先累加总和，最后再除以曲目数
+        analytics.track_count += 1;
+        analytics.avg_note_count += track.note_count as f64;
+        analytics.avg_bpm += track.effective_bpm;
+        analytics.total_play_count += play_counts.get(&track.id).copied().unwrap_or(0);
+        if genre_keys.insert(normalize_name(&track.genre)) {
+            analytics.genres.push(track.genre.clone());
+        }
+        let key_mode = track.key_mode.as_str().to_string();
+        if !analytics.key_modes.contains(&key_mode) {
+            analytics.key_modes.push(key_mode);
+        }
+    }
+    drop(tracks);
+
+    let mut result: Vec<ArtistAnalytics> = groups
+        .into_values()
+        .map(|(mut analytics, _)| {
+            analytics.avg_note_count /= analytics.track_count as f64;
+            analytics.avg_bpm /= analytics.track_count as f64;
+            analytics
+        })
+        .collect();
+    result.sort_by(|a, b| {
+        b.track_count
+            .cmp(&a.track_count)
+            .then_with(|| a.artist.cmp(&b.artist))
+    });
+    result
+}
+
+// 获取按 quantize_to 取整后的全部不重复 BPM 值（升序）
+#[tauri::command]
+async fn get_all_bpm_values(
+    quantize_to: f64,
+    state: State<'_, AppState>,
+) -> Result<Vec<f64>, String> {
+    if !(quantize_to.is_finite() && quantize_to > 0.0) {
+        return Err(CabinetError::InvalidArgument {
+            name: "quantize_to".into(),
+            reason: format!("{} is not a positive number", quantize_to),
+        }
+        .context("while getting BPM values")
+        .to_string());
+    }
+    let tracks = state.tracks.lock().await;
+    let mut values: Vec<f64> = tracks
+        .iter()
+        .map(|track| (track.bpm / quantize_to).round() * quantize_to)
+        .filter(|bpm| bpm.is_finite())
+        .collect();
+    values.sort_by(f64::total_cmp);
+    values.dedup();
+    Ok(values)
+}
+
+// 辅助函数：按 normalize_name 的规范化形式去重计数，
+// 以出现次数最多的写法作为显示名称，结果按名称字母序排序
+fn collect_distinct_names<'a>(names: impl Iterator<Item = &'a str>) -> Vec<(String, u32)> {
+    // 规范化名称 -> (各写法出现次数, 总数)
+    let mut groups: HashMap<String, (HashMap<&'a str, u32>, u32)> = HashMap::new();
+    for name in names {
+        let name = name.trim();
+        let (variants, total) = groups.entry(normalize_name(name)).or_default();
+        *variants.entry(name).or_default() += 1;
+        *total += 1;
+    }
+
+    let mut result: Vec<(String, String, u32)> = groups
+        .into_iter()
+        .map(|(key, (variants, total))| {
+            let canonical = variants
+                .into_iter()
+                .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_default();
+            (key, canonical, total)
+        })
+        .collect();
+    result.sort_by(|a, b| a.0.cmp(&b.0));
+    result
+        .into_iter()
+        .map(|(_, name, count)| (name, count))
+        .collect()
+}
+
+// 导入冲突：因去重策略被跳过的文件及与其哈希相同的已存在曲目
+#[derive(Clone, serde::Serialize)]
+struct ImportConflict {
+    skipped_path: PathBuf,
+    existing_track_id: u32,
+    existing_directory: String,
+    sha256: String,
+    // 被跳过的曲目及其导入目标目录，供 resolve_conflict_keep_new 使用
+    #[serde(skip)]
+    skipped_track: Track,
+    #[serde(skip)]
+    target_directory: String,
+}
+
+// 获取最近一次导入产生的冲突
+#[tauri::command]
+async fn get_import_conflicts(state: State<'_, AppState>) -> Result<Vec<ImportConflict>, String> {
+    Ok(state.import_conflicts.lock().await.clone())
+}
+
+// 解决冲突：以被跳过的新文件替换全部同哈希的已存在曲目
+#[tauri::command]
+async fn resolve_conflict_keep_new(
+    sha256: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    keep_conflicting_track(&state, &sha256).await
+}
+
+// 辅助函数：移除与冲突曲目哈希相同的全部曲目，再将冲突曲目插入到其导入目录
+async fn keep_conflicting_track(state: &AppState, sha256: &str) -> Result<(), String> {
+    let mut library = state.lock_library().await;
+    let mut conflicts = state.import_conflicts.lock().await;
+    let position = conflicts
+        .iter()
+        .position(|conflict| conflict.sha256 == sha256)
+        .ok_or_else(|| {
+            CabinetError::ConflictNotFound {
+                sha256: sha256.to_string(),
+            }
+            .context("while resolving conflict")
+            .to_string()
+        })?;
+    let target_directory = &conflicts[position].target_directory;
+    if !library.directory_index.contains_key(target_directory) {
+        return Err(CabinetError::DirectoryNotFound {
+            name: target_directory.clone(),
+        }
+        .context("while resolving conflict")
+        .to_string());
+    }
+
+    let conflict = conflicts.remove(position);
+    conflicts.retain(|other| other.sha256 != sha256);
+
+    let existing = library
+        .sha256_index
+        .get(sha256)
+        .cloned()
+        .unwrap_or_default();
+    for id in existing {
+        library.remove_track(id);
+    }
+    let mut track = conflict.skipped_track;
+    track.id = state.next_id.fetch_add(1, Ordering::Relaxed);
+    library.insert_into_directory(&conflict.target_directory, track)
+}
+
+// 检测路径所在存储介质的类型
+#[tauri::command]
+async fn get_storage_type(path: String) -> Result<String, String> {
+    let storage_type = storage::detect_storage_type(Path::new(&path)).await;
+    Ok(format!("{:?}", storage_type))
+}
+
+// 获取最近的完整扫描统计（按时间先后排序）
+#[tauri::command]
+async fn get_scan_history(state: State<'_, AppState>) -> Result<Vec<ScanSummary>, String> {
+    Ok(state.scan_history.lock().await.clone())
+}
+
+// 获取最近一次完整扫描的统计（包括资源占用）
+#[tauri::command]
+async fn get_last_scan_stats(state: State<'_, AppState>) -> Result<ScanSummary, String> {
+    state
+        .scan_history
+        .lock()
+        .await
+        .last()
+        .cloned()
+        .ok_or_else(|| {
+            CabinetError::NoCompletedScan
+                .context("while getting last scan stats")
+                .to_string()
+        })
+}
+
+// 为目录下的每个曲目在谱面旁写入 <stem>.cabinet.json，返回写入的文件数
+#[tauri::command]
+async fn write_sidecars(directory: String, state: State<'_, AppState>) -> Result<u32, String> {
+    let tracks = {
+        let tracks = state.tracks.lock().await;
+        let directory_index = state.directory_index.lock().await;
+        let range = directory_index.get(&directory).ok_or_else(|| {
+            CabinetError::DirectoryNotFound {
+                name: directory.clone(),
+            }
+            .context("while writing sidecars")
+            .to_string()
+        })?;
+        tracks[range.clone()].to_vec()
+    };
+
+    for track in &tracks {
+        sidecar::write_sidecar(track).await?;
+    }
+    Ok(tracks.len() as u32)
+}
+
+// 艺术家与流派的大小写统一方式
+#[derive(Clone, Copy, serde::Deserialize)]
+enum NormalizationStrategy {
+    // 每个单词首字母大写，其余小写
+    TitleCase,
+    LowerCase,
+    UpperCase,
+    // 以同一名称（忽略大小写）第一次出现时的写法为准
+    PreserveFirst,
+}
+
+// 统一全部曲目的艺术家与流派大小写，并为修改过的曲目写入 sidecar，返回修改的曲目数
+#[tauri::command]
+async fn normalize_track_metadata(
+    strategy: NormalizationStrategy,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    let modified = normalize_casing(&state, strategy).await;
+    for track in &modified {
+        sidecar::write_sidecar(track).await?;
+    }
+    Ok(modified.len() as u32)
+}
+
+// 辅助函数：在一次加锁内统一全部曲目的艺术家与流派大小写，返回修改过的曲目
+async fn normalize_casing(state: &AppState, strategy: NormalizationStrategy) -> Vec<Track> {
+    let mut modified = Vec::new();
+    let mut library = state.lock_library().await;
+    let mut first_artists = HashMap::new();
+    let mut first_genres = HashMap::new();
+    for index in 0..library.tracks.len() {
+        let original = library.tracks[index].clone();
+        let artist = apply_casing(&original.artist, strategy, &mut first_artists);
+        let genre = apply_casing(&original.genre, strategy, &mut first_genres);
+        if artist == original.artist && genre == original.genre {
+            continue;
+        }
+
+        let updated = Track {
+            artist,
+            genre,
+            ..original
+        };
+        library.update_track(updated.clone());
+        modified.push(updated);
+    }
+    modified
+}
+
+// 辅助函数：按统一方式转换名称大小写，first_seen 记录 PreserveFirst 下各名称的首个写法
+fn apply_casing(
+    value: &str,
+    strategy: NormalizationStrategy,
+    first_seen: &mut HashMap<String, String>,
+) -> String {
+    match strategy {
+        NormalizationStrategy::TitleCase => value
+            .split(' ')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first
+                        .to_uppercase()
+                        .chain(chars.flat_map(char::to_lowercase))
+                        .collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" "),
+        NormalizationStrategy::LowerCase => value.to_lowercase(),
+        NormalizationStrategy::UpperCase => value.to_uppercase(),
+        NormalizationStrategy::PreserveFirst => first_seen
+            .entry(normalize_name(value))
+            .or_insert_with(|| value.to_string())
+            .clone(),
+    }
+}
+
+// 批量重新解析的结果统计
+#[derive(Clone, serde::Serialize)]
+struct RecomputeReport {
+    updated: u32,
+    unchanged: u32,
+    failed: u32,
+}
+
+// 以 concurrency 个并发任务重新读取并解析目录中的全部谱面，只更新内容有变化的曲目。
+// 与谱面一致的 sidecar 优先于解析结果，因此其中的修改会被保留
+#[tauri::command]
+async fn batch_recompute_metadata(
+    window: Window,
+    directory: String,
+    concurrency: u8,
+    state: State<'_, AppState>,
+) -> Result<RecomputeReport, String> {
+    let (report, updated) = recompute_with_lock(&state, &directory, |tracks| {
+        recompute_tracks(tracks, concurrency, |progress| {
+            window
+                .emit("recompute_progress", progress)
+                .map_err(|e| e.to_string())
+        })
+    })
+    .await?;
+    for track in updated {
+        emit_library_event(&window, LibraryEvent::TrackUpdated(track)).await?;
+    }
+    Ok(report)
+}
+
+// 辅助函数：锁定目录中的全部曲目后执行 recompute，结束时无论成功与否都解除锁定，
+// 再将内容有变化的曲目写回曲库（重新解析期间被删除的曲目会被忽略），返回统计与实际更新的曲目
+async fn recompute_with_lock<F, Fut>(
+    state: &AppState,
+    directory: &str,
+    recompute: F,
+) -> Result<(RecomputeReport, Vec<Track>), String>
+where
+    F: FnOnce(Vec<Track>) -> Fut,
+    Fut: std::future::Future<Output = Result<(RecomputeReport, Vec<Track>), String>>,
+{
+    let (tracks, ids) = {
+        let library = state.lock_library().await;
+        let range = library.directory_index.get(directory).ok_or_else(|| {
+            CabinetError::DirectoryNotFound {
+                name: directory.to_string(),
+            }
+            .context("while recomputing metadata")
+            .to_string()
+        })?;
+        let tracks = library.tracks[range.clone()].to_vec();
+        let ids: Vec<u32> = tracks.iter().map(|track| track.id).collect();
+        state.locked_track_ids.lock().await.extend(&ids);
+        (tracks, ids)
+    };
+
+    let result = recompute(tracks).await;
+
+    let mut library = state.lock_library().await;
+    let mut locked_track_ids = state.locked_track_ids.lock().await;
+    for id in &ids {
+        locked_track_ids.remove(id);
+    }
+    let (report, changed) = result?;
+    let mut updated = Vec::with_capacity(changed.len());
+    for track in changed {
+        if library.update_track(track.clone()).is_some() {
+            updated.push(track);
+        }
+    }
+    Ok((report, updated))
+}
+
+// 辅助函数：并发重新解析曲目，每处理一个曲目以百分比调用一次 on_progress，
+// 返回统计与内容有变化的曲目
+async fn recompute_tracks(
+    tracks: Vec<Track>,
+    concurrency: u8,
+    mut on_progress: impl FnMut(f64) -> Result<(), String>,
+) -> Result<(RecomputeReport, Vec<Track>), String> {
+    let total = tracks.len();
+
+    let mut report = RecomputeReport {
+        updated: 0,
+        unchanged: 0,
+        failed: 0,
+    };
+    let mut changed = Vec::new();
+    let mut results = stream::iter(tracks)
+        .map(|track| async move {
+            let recomputed = process_single_file(&track.absolute_path).await?;
+            Ok::<_, String>((track, recomputed))
+        })
+        .buffer_unordered(concurrency.max(1) as usize);
+    let mut processed = 0;
+    while let Some(result) = results.next().await {
+        match result {
+            Ok((stored, recomputed)) => {
+                // ID 与包 ID 由导入流程分配，不参与比较
+                let recomputed = Track {
+                    id: stored.id,
+                    pack_id: stored.pack_id,
+                    ..recomputed
+                };
+                if serde_json::to_value(&stored).ok() == serde_json::to_value(&recomputed).ok() {
+                    report.unchanged += 1;
+                } else {
+                    report.updated += 1;
+                    changed.push(recomputed);
+                }
+            }
+            Err(_) => report.failed += 1,
+        }
+
+        processed += 1;
+        on_progress((processed as f64 / total as f64) * 100.0)?;
+    }
+    Ok((report, changed))
+}
+
+// 手动编辑的曲目元数据，未提供的字段保持不变
+#[derive(Clone, serde::Deserialize)]
+struct TrackMetadataEdit {
+    title: Option<String>,
+    artist: Option<String>,
+    genre: Option<String>,
+}
+
+// 辅助函数：将手动编辑应用到曲目，并记录被修改的字段
+fn apply_metadata_edit(track: &mut Track, edit: TrackMetadataEdit) {
+    let fields = [
+        ("title", edit.title, &mut track.title),
+        ("artist", edit.artist, &mut track.artist),
+        ("genre", edit.genre, &mut track.genre),
+    ];
+    for (field, value, target) in fields {
+        if let Some(value) = value {
+            *target = value;
+            if !track
+                .user_edited_fields
+                .iter()
+                .any(|edited| edited == field)
+            {
+                track.user_edited_fields.push(field.to_string());
+            }
+        }
+    }
+    track.metadata_source = MetadataSource::UserEdited;
+}
+
+// 修改曲目的标题 / 艺术家 / 流派并写入 sidecar，使之后的重新导入保留修改；
+// 曲目正在被 batch_recompute_metadata 处理时返回 TrackLocked
+#[tauri::command]
+async fn update_track_metadata(
+    window: Window,
+    track_id: u32,
+    edit: TrackMetadataEdit,
+    state: State<'_, AppState>,
+) -> Result<Track, String> {
+    let updated = edit_track_metadata(&state, track_id, edit)
+        .await
+        .map_err(|e| e.context("while updating track metadata").to_string())?;
+    sidecar::write_sidecar(&updated).await?;
+    emit_library_event(&window, LibraryEvent::TrackUpdated(updated.clone())).await?;
+    Ok(updated)
+}
+
+// 辅助函数：将手动编辑应用到曲库中的曲目并返回修改后的曲目，曲目被锁定时返回 TrackLocked
+async fn edit_track_metadata(
+    state: &AppState,
+    track_id: u32,
+    edit: TrackMetadataEdit,
+) -> Result<Track, CabinetError> {
+    let mut library = state.lock_library().await;
+    if state.locked_track_ids.lock().await.contains(&track_id) {
+        return Err(CabinetError::TrackLocked { id: track_id });
+    }
+    let index = *library
+        .id_index
+        .get(&track_id)
+        .ok_or(CabinetError::TrackNotFound { id: track_id })?;
+    let mut updated = library.tracks[index].clone();
+    apply_metadata_edit(&mut updated, edit);
+    library.update_track(updated.clone());
+    Ok(updated)
+}
+
+// 获取正在被批量重新解析、暂不允许编辑的曲目 ID（升序）
+#[tauri::command]
+async fn get_locked_tracks(state: State<'_, AppState>) -> Result<Vec<u32>, String> {
+    let mut ids: Vec<u32> = state
+        .locked_track_ids
+        .lock()
+        .await
+        .iter()
+        .copied()
+        .collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+// 开始新的游玩会话，返回会话 ID；已有进行中的会话时返回错误
+#[tauri::command]
+async fn start_session(state: State<'_, AppState>) -> Result<u32, String> {
+    begin_session(&state).await
+}
+
+// 辅助函数：没有进行中的会话时开始新会话，返回其 ID
+async fn begin_session(state: &AppState) -> Result<u32, String> {
+    let mut active_session = state.active_session.lock().await;
+    if let Some(active) = active_session.as_ref() {
+        return Err(CabinetError::SessionAlreadyActive {
+            id: active.session_id,
+        }
+        .context("while starting session")
+        .to_string());
+    }
+    let session_id = state.next_session_id.fetch_add(1, Ordering::Relaxed);
+    *active_session = Some(session::PlaySession::start(session_id));
+    Ok(session_id)
+}
+
+// 结束进行中的游玩会话并将其写入会话历史文件
+#[tauri::command]
+async fn end_session(
+    session_id: u32,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    close_session(&state, session_id).await?;
+    let session_history = state.session_history.lock().await;
+    session::save_sessions(&sessions_file_path(&app)?, &session_history).await
+}
+
+// 辅助函数：结束进行中的会话并追加到会话历史
+async fn close_session(state: &AppState, session_id: u32) -> Result<(), String> {
+    let mut active_session = state.active_session.lock().await;
+    let mut session = active_session
+        .take_if(|session| session.session_id == session_id)
+        .ok_or_else(|| {
+            CabinetError::SessionNotActive { id: session_id }
+                .context("while ending session")
+                .to_string()
+        })?;
+    session.end();
+    state.session_history.lock().await.push(session);
+    Ok(())
+}
+
+// 在进行中的游玩会话中记录一次游玩
+#[tauri::command]
+async fn record_play_in_session(
+    session_id: u32,
+    track_id: u32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    record_play(&state, session_id, track_id).await
+}
+
+// 辅助函数：在进行中的会话中追加一次游玩
+async fn record_play(state: &AppState, session_id: u32, track_id: u32) -> Result<(), String> {
+    if !state.id_index.lock().await.contains_key(&track_id) {
+        return Err(CabinetError::TrackNotFound { id: track_id }
+            .context("while recording play")
+            .to_string());
+    }
+    let mut active_session = state.active_session.lock().await;
+    let session = active_session
+        .as_mut()
+        .filter(|session| session.session_id == session_id)
+        .ok_or_else(|| {
+            CabinetError::SessionNotActive { id: session_id }
+                .context("while recording play")
+                .to_string()
+        })?;
+    session.tracks_played.push(track_id);
+    Ok(())
+}
+
+// 获取最近结束的 limit 个游玩会话（从新到旧），不包括进行中的会话
+#[tauri::command]
+async fn get_session_history(
+    limit: u32,
+    state: State<'_, AppState>,
+) -> Result<Vec<session::PlaySession>, String> {
+    Ok(recent_sessions(&state, limit).await)
+}
+
+// 辅助函数：从新到旧取最近结束的 limit 个会话
+async fn recent_sessions(state: &AppState, limit: u32) -> Vec<session::PlaySession> {
+    state
+        .session_history
+        .lock()
+        .await
+        .iter()
+        .rev()
+        .take(limit as usize)
+        .cloned()
+        .collect()
+}
+
+// 播放列表
+#[derive(Clone, serde::Serialize)]
+struct Playlist {
+    id: u32,
+    name: String,
+    // 按播放顺序排列的曲目 ID（可重复）
+    track_ids: Vec<u32>,
+}
+
+// 新建播放列表，返回其 ID
+#[tauri::command]
+async fn create_playlist(
+    name: String,
+    track_ids: Vec<u32>,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    let id = state.next_playlist_id.fetch_add(1, Ordering::Relaxed);
+    state.playlists.lock().await.insert(
+        id,
+        Playlist {
+            id,
+            name,
+            track_ids,
+        },
+    );
+    Ok(id)
+}
+
+// 获取全部播放列表（按 ID 升序）
+#[tauri::command]
+async fn get_playlists(state: State<'_, AppState>) -> Result<Vec<Playlist>, String> {
+    let mut playlists: Vec<Playlist> = state.playlists.lock().await.values().cloned().collect();
+    playlists.sort_by_key(|playlist| playlist.id);
+    Ok(playlists)
+}
+
+// 将播放列表导出为扩展 M3U 文件，供外部播放器使用，返回写入的条目数。
+// 条目指向曲目的预览音频，没有预览音频时指向谱面文件；已被删除的曲目会被跳过
+#[tauri::command]
+async fn export_playlist_to_m3u(
+    playlist_id: u32,
+    output_path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    let tracks = {
+        let library = state.lock_library().await;
+        let playlists = state.playlists.lock().await;
+        let playlist = playlists.get(&playlist_id).ok_or_else(|| {
+            CabinetError::PlaylistNotFound { id: playlist_id }
+                .context("while exporting playlist")
+                .to_string()
+        })?;
+        library.tracks_by_ids(&playlist.track_ids)
+    };
+
+    let mut entries = Vec::with_capacity(tracks.len());
+    for track in &tracks {
+        entries.push(m3u_entry(track).await);
+    }
+    fs::write(&output_path, m3u::write_m3u(&entries))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(entries.len() as u32)
+}
+
+// 辅助函数：曲目的 M3U 条目，时长取所指向文件（预览音频或谱面）的时长
+async fn m3u_entry(track: &Track) -> m3u::M3uEntry {
+    let preview = fs::read(&track.absolute_path)
+        .await
+        .ok()
+        .and_then(|content| parse_chart_file(&track.absolute_path, &content).ok())
+        .and_then(|chart| chart.header.preview)
+        .and_then(|preview| audio::resolve_audio_path(&track.absolute_path, &preview));
+    let (path, duration_ms) = match preview {
+        Some(path) => (path, track.preview_duration_ms.map(u64::from)),
+        None => (track.absolute_path.clone(), track.chart_duration_ms),
+    };
+    m3u::M3uEntry {
+        duration_secs: duration_ms.map_or(-1, |ms| (ms as f64 / 1000.0).round() as i64),
+        title: format!("{} - {}", track.artist, track.title),
+        path,
+    }
+}
+
+// M3U 导入结果
+#[derive(Clone, serde::Serialize)]
+struct M3uImportResult {
+    playlist_id: u32,
+    // 加入播放列表的条目数
+    matched: u32,
+    // 无法对应到曲目而被忽略的条目数
+    unmatched: u32,
+}
+
+// 由 M3U 文件新建播放列表（以文件名为播放列表名）。
+// 条目依次按完整路径、文件名、文件名主干（不含扩展名）匹配谱面，后两者只在恰好匹配一个谱面时采用；
+// 无法匹配的条目被忽略并计入 unmatched
+#[tauri::command]
+async fn import_playlist_from_m3u(
+    path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<M3uImportResult, String> {
+    let content = fs::read(&path).await.map_err(|e| e.to_string())?;
+    let entries = m3u::read_m3u_paths(&String::from_utf8_lossy(&content));
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let library = state.lock_library().await;
+    let track_ids: Vec<u32> = entries
+        .iter()
+        .filter_map(|entry| match_m3u_entry(&library.tracks, entry))
+        .collect();
+    let matched = track_ids.len() as u32;
+    let unmatched = (entries.len() - track_ids.len()) as u32;
+    if unmatched > 0 {
+        tracing::info!(path = %path.display(), unmatched, "ignored unmatched M3U entries");
+    }
+    let id = state.next_playlist_id.fetch_add(1, Ordering::Relaxed);
+    state.playlists.lock().await.insert(
+        id,
+        Playlist {
+            id,
+            name,
+            track_ids,
+        },
+    );
+    Ok(M3uImportResult {
+        playlist_id: id,
+        matched,
+        unmatched,
+    })
+}
+
+// BulkTagApplied 曲库事件的内容
+#[derive(Clone, serde::Serialize)]
+struct BulkTagApplied {
+    tag_id: u32,
+    // 新打上标签的曲目 ID（不含原本已有该标签的曲目）
+    track_ids: Vec<u32>,
+}
+
+// 为曲目批量打上标签，返回新打上标签的曲目数；任一曲目不存在时不做任何修改。
+// 全部修改在一次加锁内完成，完成后发送一次 BulkTagApplied 曲库事件
+#[tauri::command]
+async fn bulk_tag_tracks(
+    window: Window,
+    track_ids: Vec<u32>,
+    tag_id: u32,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    let newly_tagged = tag_tracks(&state, tag_id, &track_ids).await?;
+    emit_bulk_tag_applied(&window, tag_id, newly_tagged).await
+}
+
+// 辅助函数：校验曲目全部存在后打上标签，返回新打上标签的曲目 ID
+async fn tag_tracks(state: &AppState, tag_id: u32, track_ids: &[u32]) -> Result<Vec<u32>, String> {
+    let library = state.lock_library().await;
+    if let Some(&id) = track_ids
+        .iter()
+        .find(|id| !library.id_index.contains_key(id))
+    {
+        return Err(CabinetError::TrackNotFound { id }
+            .context(format!("while applying tag {}", tag_id))
+            .to_string());
+    }
+    Ok(apply_tag(state, tag_id, track_ids).await)
+}
+
+// 为指定艺术家（忽略大小写）的全部曲目打上标签，返回新打上标签的曲目数
+#[tauri::command]
+async fn bulk_tag_by_artist(
+    window: Window,
+    artist: String,
+    tag_id: u32,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    let library = state.lock_library().await;
+    let track_ids = library
+        .artist_index
+        .get(&normalize_name(&artist))
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let newly_tagged = apply_tag(&state, tag_id, track_ids).await;
+    drop(library);
+    emit_bulk_tag_applied(&window, tag_id, newly_tagged).await
+}
+
+// 为指定流派（忽略大小写）的全部曲目打上标签，返回新打上标签的曲目数
+#[tauri::command]
+async fn bulk_tag_by_genre(
+    window: Window,
+    genre: String,
+    tag_id: u32,
+    state: State<'_, AppState>,
+) -> Result<u32, String> {
+    let library = state.lock_library().await;
+    let track_ids = library
+        .genre_index
+        .get(&normalize_name(&genre))
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let newly_tagged = apply_tag(&state, tag_id, track_ids).await;
+    drop(library);
+    emit_bulk_tag_applied(&window, tag_id, newly_tagged).await
+}
+
+// 获取打上指定标签的曲目（按 ID 升序）
+#[tauri::command]
+async fn get_tracks_by_tag(tag_id: u32, state: State<'_, AppState>) -> Result<Vec<Track>, String> {
+    let library = state.lock_library().await;
+    let mut track_ids: Vec<u32> = state
+        .track_tags
+        .lock()
+        .await
+        .get(&tag_id)
+        .map(|tagged| tagged.iter().copied().collect())
+        .unwrap_or_default();
+    track_ids.sort_unstable();
+    Ok(library.tracks_by_ids(&track_ids))
+}
+
+// 辅助函数：在一次加锁内为曲目打上标签，返回新打上标签的曲目 ID
+async fn apply_tag(state: &AppState, tag_id: u32, track_ids: &[u32]) -> Vec<u32> {
+    let mut track_tags = state.track_tags.lock().await;
+    let tagged = track_tags.entry(tag_id).or_default();
+    track_ids
+        .iter()
+        .copied()
+        .filter(|&id| tagged.insert(id))
+        .collect()
+}
+
+// 辅助函数：发送 BulkTagApplied 曲库事件，返回新打上标签的曲目数
+async fn emit_bulk_tag_applied(
+    window: &Window,
+    tag_id: u32,
+    track_ids: Vec<u32>,
+) -> Result<u32, String> {
+    let count = track_ids.len() as u32;
+    emit_library_event(
+        window,
+        LibraryEvent::BulkTagApplied(BulkTagApplied { tag_id, track_ids }),
+    )
+    .await?;
+    Ok(count)
+}
+
+// 辅助函数：查找与 M3U 条目对应的曲目 ID
+fn match_m3u_entry(tracks: &[Track], entry: &Path) -> Option<u32> {
+    // 只在恰好有一个曲目满足条件时返回该曲目
+    let unique = |matches: &dyn Fn(&Track) -> bool| {
+        let mut found = tracks.iter().filter(|track| matches(track));
+        match (found.next(), found.next()) {
+            (Some(track), None) => Some(track),
+            _ => None,
+        }
+    };
+    tracks
+        .iter()
+        .find(|track| track.absolute_path == entry)
+        .or_else(|| {
+            let file_name = entry.file_name()?;
+            unique(&|track| track.absolute_path.file_name() == Some(file_name))
+        })
+        .or_else(|| {
+            let file_stem = entry.file_stem()?;
+            unique(&|track| track.absolute_path.file_stem() == Some(file_stem))
+        })
+        .map(|track| track.id)
+}
+
+// 辅助函数：会话历史文件的路径
+fn sessions_file_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(session::SESSIONS_FILE_NAME))
+        .map_err(|e| e.to_string())
+}
+
+// 可改为硬链接的一组重复文件
+#[derive(Clone, serde::Serialize)]
+struct HardlinkCandidate {
+    sha256: String,
+    paths: Vec<PathBuf>,
+    // 除第一个文件外其余文件改为硬链接后节省的空间
+    potential_savings_bytes: u64,
+}
+
+// 找出目录中内容相同（SHA256 相同）的文件，供 apply_hardlinks 使用，按可节省空间降序排列
+#[tauri::command]
+async fn suggest_hardlinks(
+    directory: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<HardlinkCandidate>, String> {
+    hardlink_candidates(&state, &directory).await
+}
+
+// 辅助函数：按 SHA256 对目录中的曲目分组，找出至少有两个不同路径的组
+async fn hardlink_candidates(
+    state: &AppState,
+    directory: &str,
+) -> Result<Vec<HardlinkCandidate>, String> {
+    let tracks = directory_tracks(state, directory).await?;
+    let mut groups: HashMap<String, (Vec<PathBuf>, u64)> = HashMap::new();
+    for track in tracks {
+        let (paths, file_size) = groups.entry(track.sha256).or_default();
+        if !paths.contains(&track.absolute_path) {
+            paths.push(track.absolute_path);
+        }
+        *file_size = track.file_size;
+    }
+
+    let mut candidates: Vec<HardlinkCandidate> = groups
+        .into_iter()
+        .filter(|(_, (paths, _))| paths.len() >= 2)
+        .map(|(sha256, (paths, file_size))| HardlinkCandidate {
+            potential_savings_bytes: (paths.len() as u64 - 1) * file_size,
+            sha256,
+            paths,
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.potential_savings_bytes
+            .cmp(&a.potential_savings_bytes)
+            .then_with(|| a.sha256.cmp(&b.sha256))
+    });
+    Ok(candidates)
+}
+
+// 将每个 SHA256 对应的重复文件替换为指向第一个文件的硬链接（仅 Unix），返回节省的字节数。
+// 替换前会重新计算磁盘上文件的哈希，内容已变化的文件会被跳过
+#[tauri::command]
+async fn apply_hardlinks(
+    candidates: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    if !cfg!(unix) {
+        return Err(CabinetError::UnsupportedPlatform {
+            feature: "Hardlinking".into(),
+        }
+        .context("while applying hardlinks")
+        .to_string());
+    }
+    link_duplicates(&state, candidates).await
+}
+
+// 辅助函数：为每个 SHA256 将其余路径替换为指向第一个路径的硬链接，返回节省的字节数
+async fn link_duplicates(state: &AppState, candidates: Vec<String>) -> Result<u64, String> {
+    let groups: Vec<(String, Vec<PathBuf>)> = {
+        let library = state.lock_library().await;
+        candidates
+            .into_iter()
+            .map(|sha256| {
+                let mut paths: Vec<PathBuf> = Vec::new();
+                for track in library
+                    .tracks_by_ids(library.sha256_index.get(&sha256).map_or(&[], Vec::as_slice))
+                {
+                    if !paths.contains(&track.absolute_path) {
+                        paths.push(track.absolute_path);
+                    }
+                }
+                (sha256, paths)
+            })
+            .collect()
+    };
+
+    let mut saved = 0;
+    for (sha256, paths) in groups {
+        let Some((original, duplicates)) = paths.split_first() else {
+            continue;
+        };
+        if file_sha256(original).await.as_deref() != Some(sha256.as_str()) {
+            continue;
+        }
+        for duplicate in duplicates {
+            if file_sha256(duplicate).await.as_deref() != Some(sha256.as_str()) {
+                continue;
+            }
+            let size = fs::metadata(duplicate)
+                .await
+                .map_err(|e| e.to_string())?
+                .len();
+            // 先在同一目录创建硬链接再覆盖，避免失败时丢失文件
+            let temp_path = duplicate.with_extension("cabinet-link");
+            fs::hard_link(original, &temp_path)
+                .await
+                .map_err(|e| e.to_string())?;
+            fs::rename(&temp_path, duplicate)
+                .await
+                .map_err(|e| e.to_string())?;
+            saved += size;
+        }
+    }
+    Ok(saved)
+}
+
+// 辅助函数：计算磁盘上文件的 SHA256，读取失败时为 None
+async fn file_sha256(path: &Path) -> Option<String> {
+    let content = fs::read(path).await.ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(&content);
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+// 测量路径所在存储的读取与哈希吞吐量，并给出推荐的扫描配置
+#[tauri::command]
+async fn benchmark_scan(
+    path: PathBuf,
+    duration_secs: u64,
+    state: State<'_, AppState>,
+) -> Result<benchmark::BenchmarkResult, String> {
+    let config = state.config.lock().await.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        benchmark::run_benchmark(&path, Duration::from_secs(duration_secs), config)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// 检查扫描配置而不保存，返回全部违反的约束（`<字段>: <说明>`），配置有效时为空列表
+#[tauri::command]
+async fn validate_config(config: ScanConfig) -> Result<Vec<String>, String> {
+    Ok(match bms_scan::validate_scan_config(&config) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.iter().map(ToString::to_string).collect(),
+    })
+}
+
+// 获取扫描配置
+#[tauri::command]
+async fn get_scan_config(state: State<'_, AppState>) -> Result<ScanConfig, String> {
+    Ok(state.config.lock().await.clone())
+}
+
+// 添加额外扫描的扩展名（去掉开头的 `.`，统一为小写），已存在时不做任何事
+#[tauri::command]
+async fn add_scan_extension(
+    ext: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+    if ext.is_empty() {
+        return Err(CabinetError::InvalidArgument {
+            name: "extension".into(),
+            reason: "extension is empty".into(),
+        }
+        .context("while adding scan extension")
+        .to_string());
+    }
+    let config = {
+        let mut config = state.config.lock().await;
+        if config.is_target_extension(&ext) {
+            return Ok(());
+        }
+        config.extra_extensions.push(ext);
+        config.clone()
+    };
+    save_config(&app, &config).await
+}
+
+// 移除额外扫描的扩展名（内置的谱面扩展名不能移除）
+#[tauri::command]
+async fn remove_scan_extension(
+    ext: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let ext = ext.trim().trim_start_matches('.');
+    let config = {
+        let mut config = state.config.lock().await;
+        let before = config.extra_extensions.len();
+        config
+            .extra_extensions
+            .retain(|extra| !extra.eq_ignore_ascii_case(ext));
+        if config.extra_extensions.len() == before {
+            return Err(CabinetError::ExtensionNotFound {
+                extension: ext.to_string(),
+            }
+            .context("while removing scan extension")
+            .to_string());
+        }
+        config.clone()
+    };
+    save_config(&app, &config).await
+}
+
+// 添加扫描时排除的目录模式（glob 语法），已存在时不做任何事
+#[tauri::command]
+async fn add_exclude_pattern(
+    pattern: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pattern = pattern.trim().to_string();
+    glob::Pattern::new(&pattern).map_err(|e| {
+        CabinetError::InvalidArgument {
+            name: "pattern".into(),
+            reason: e.to_string(),
+        }
+        .context("while adding exclude pattern")
+        .to_string()
+    })?;
+    let config = {
+        let mut config = state.config.lock().await;
+        if config.exclude_patterns.contains(&pattern) {
+            return Ok(());
+        }
+        config.exclude_patterns.push(pattern);
+        config.clone()
+    };
+    save_config(&app, &config).await
+}
+
+// 移除扫描时排除的目录模式
+#[tauri::command]
+async fn remove_exclude_pattern(
+    pattern: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let pattern = pattern.trim();
+    let config = {
+        let mut config = state.config.lock().await;
+        let before = config.exclude_patterns.len();
+        config
+            .exclude_patterns
+            .retain(|existing| existing != pattern);
+        if config.exclude_patterns.len() == before {
+            return Err(CabinetError::ExcludePatternNotFound {
+                pattern: pattern.to_string(),
+            }
+            .context("while removing exclude pattern")
+            .to_string());
+        }
+        config.clone()
+    };
+    save_config(&app, &config).await
+}
+
+// 扫描配置文件名（位于应用配置目录）
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+// 辅助函数：扫描配置文件的路径
+fn config_file_path<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .map_err(|e| e.to_string())
+}
+
+// 辅助函数：读取保存的扫描配置，不存在或无效时返回 None
+fn load_config<R: Runtime>(app: &AppHandle<R>) -> Option<ScanConfig> {
+    let content = std::fs::read_to_string(config_file_path(app).ok()?).ok()?;
+    toml::from_str(&content).ok()
+}
+
+// 辅助函数：保存扫描配置
+async fn save_config<R: Runtime>(app: &AppHandle<R>, config: &ScanConfig) -> Result<(), String> {
+    let path = config_file_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let content = toml::to_string(config).map_err(|e| e.to_string())?;
+    fs::write(path, content).await.map_err(|e| e.to_string())
+}
+
+// 更新扫描配置，配置无效时返回全部违反的约束（以 `; ` 分隔）且不做修改
+#[tauri::command]
+async fn update_config(
+    config: ScanConfig,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    apply_config(&state, config, |config| async move {
+        save_config(&app, &config).await
+    })
+    .await
+}
+
+// 辅助函数：校验配置，有效时更新状态并以 save 保存；无效时既不修改状态也不调用 save
+async fn apply_config<F, Fut>(state: &AppState, config: ScanConfig, save: F) -> Result<(), String>
+where
+    F: FnOnce(ScanConfig) -> Fut,
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    bms_scan::validate_scan_config(&config).map_err(|errors| {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    })?;
+    if let Some(id_start) = config.id_start {
+        state.next_id.fetch_max(id_start, Ordering::Relaxed);
+    }
+    *state.config.lock().await = config.clone();
+    save(config).await
+}
+
+#[tauri::command]
+async fn handle_dropped_files(
+    window: Window,
+    files: Vec<PathBuf>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.import_conflicts.lock().await.clear();
+    for path in files {
+        if path.is_dir() {
+            process_directory(&window, &state, path).await?;
+        } else if let Some(ext) = path.extension() {
+            if ext == "zip" {
+                // TODO:
+                // process_archive(&window, &state, path).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// 添加目录主逻辑，返回导入、因重复跳过与解析失败的谱面数
+#[tauri::command]
+async fn add_directory(
+    window: Window,
+    path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<AddDirectoryReport, String> {
+    state.import_conflicts.lock().await.clear();
+    process_directory(&window, &state, path).await
+}
+
+// scan_file_list 的导入结果
+#[derive(Clone, serde::Serialize)]
+struct FileListReport {
+    imported: u32,
+    // 读取或解析失败而被跳过的文件
+    failed_paths: Vec<PathBuf>,
+}
+
+// 将分散在多处的谱面文件逐个导入到虚拟目录 virtual_directory_name（不遍历目录，
+// 已存在的同名目录被替换）。任一路径不是谱面文件时不导入任何文件；
+// 读取或解析失败的文件被跳过并在结果中列出，不影响其余文件
+#[tauri::command]
+async fn scan_file_list(
+    window: Window,
+    paths: Vec<PathBuf>,
+    virtual_directory_name: String,
+    state: State<'_, AppState>,
+) -> Result<FileListReport, String> {
+    let config = state.config.lock().await.clone();
+    if let Some(path) = paths.iter().find(|path| !config.is_target_file(path)) {
+        return Err(CabinetError::InvalidArgument {
+            name: "path".into(),
+            reason: format!("{} is not a chart file", path.display()),
+        }
+        .context("while scanning file list")
+        .to_string());
+    }
+
+    state.import_conflicts.lock().await.clear();
+    let _scan = begin_scan(&state).await;
+    let (tracks, failed_paths) = process_file_list(&state, &paths, |progress| {
+        window
+            .emit("import_progress", progress)
+            .map_err(|e| e.to_string())
+    })
+    .await?;
+
+    let imported = commit_directory(
+        &window,
+        &state,
+        &virtual_directory_name,
+        tracks,
+        config.dedup_strategy,
+    )
+    .await?;
+    emit_library_event(
+        &window,
+        LibraryEvent::DirectoryAdded(virtual_directory_name),
+    )
+    .await?;
+    schedule_consistency_check(&window, &state).await;
+    Ok(FileListReport {
+        imported,
+        failed_paths,
+    })
+}
+
+// 辅助函数：逐个解析文件并更新导入进度（每个文件之后调用一次 on_progress），
+// 返回解析得到的曲目与读取或解析失败的路径
+async fn process_file_list(
+    state: &AppState,
+    paths: &[PathBuf],
+    mut on_progress: impl FnMut(f64) -> Result<(), String>,
+) -> Result<(Vec<Track>, Vec<PathBuf>), String> {
+    let mut tracks = Vec::with_capacity(paths.len());
+    let mut failed_paths = Vec::new();
+    for (processed, path) in paths.iter().enumerate() {
+        match process_single_file(path).await {
+            Ok(track) => tracks.push(track),
+            Err(e) => {
+                tracing::warn!(error = %e, path = %path.display(), "skipping chart that failed to parse");
+                failed_paths.push(path.clone());
+            }
+        }
+
+        let progress = ((processed + 1) as f64 / paths.len() as f64) * 100.0;
+        *state.current_progress.lock().await = progress;
+        on_progress(progress)?;
+    }
+    Ok((tracks, failed_paths))
+}
+
+// 导入预演结果
+#[derive(Clone, serde::Serialize)]
+struct DryRunReport {
+    would_add: u32,
+    // 按当前的去重策略会被跳过的谱面数
+    would_skip_duplicate: u32,
+    // 读取或解析失败的谱面数
+    would_fail: u32,
+    // 会被导入的曲目（尚未分配 ID，id 均为 0）
+    new_tracks: Vec<TrackSummary>,
+}
+
+// 按 add_directory 的流程扫描并去重，但不修改曲目表、目录根路径与导入冲突，
+// 也不发送曲库事件，返回会导入的曲目
+#[tauri::command]
+async fn dry_run_add_directory(
+    window: Window,
+    path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<DryRunReport, String> {
+    let _scan = begin_scan(&state).await;
+    let config = state.config.lock().await.clone();
+    let dir_name = directory_name(&path)
+        .map_err(|e| e.context("while dry-running directory import").to_string())?;
+    let (tracks, failed) = collect_directory_tracks(&window, &state, &path, &config).await?;
+
+    let (kept, skipped) = state.lock_library().await.preview_replace_directory(
+        &dir_name,
+        tracks,
+        config.dedup_strategy,
+    );
+    Ok(DryRunReport {
+        would_add: kept.len() as u32,
+        would_skip_duplicate: skipped.len() as u32,
+        would_fail: failed,
+        new_tracks: kept.iter().map(TrackSummary::from).collect(),
+    })
+}
+
+// 在限定时间内扫描目录并导入，超时则只导入已扫描到的谱面；读取或解析失败的谱面被跳过
+#[tauri::command]
+async fn add_directory_with_timeout(
+    window: Window,
+    path: PathBuf,
+    timeout_secs: u64,
+    state: State<'_, AppState>,
+) -> Result<Vec<Track>, String> {
+    state.import_conflicts.lock().await.clear();
+    let dir_name =
+        directory_name(&path).map_err(|e| e.context("while adding directory").to_string())?;
+
+    let config = state.config.lock().await.clone();
+    let dedup_strategy = config.dedup_strategy;
+    let storage_type = storage::detect_storage_type(&path).await;
+    let handle = bms_scan::scan_directory_recursive(path.clone(), storage_type, config)
+        .await
+        .map_err(|e| e.to_string())?;
+    *state.current_scan.lock().await = Some(handle.is_completed.clone());
+    let summary = handle.summary.clone();
+    let files = match handle.timeout(Duration::from_secs(timeout_secs)).await {
+        ScanHandleResult::Completed { results } => {
+            if let Some(summary) = summary.get() {
+                let mut scan_history = state.scan_history.lock().await;
+                if scan_history.len() >= SCAN_HISTORY_LIMIT {
+                    scan_history.remove(0);
+                }
+                scan_history.push(summary.clone());
+                drop(scan_history);
+                emit_library_event(&window, LibraryEvent::ScanCompleted(summary.clone())).await?;
+            }
+            results
+        }
+        ScanHandleResult::TimedOut { partial_results } => partial_results,
+    };
+
+    let (mut tracks, _failed) = tracks_from_files(files).await;
+    assign_pack_ids(&mut tracks, &path);
+    state
+        .directory_roots
+        .lock()
+        .await
+        .insert(dir_name.clone(), path);
+    commit_directory(&window, &state, &dir_name, tracks, dedup_strategy).await?;
+
+    let tracks = {
+        let tracks = state.tracks.lock().await;
+        let directory_index = state.directory_index.lock().await;
+        directory_index
+            .get(&dir_name)
+            .map(|range| tracks[range.clone()].to_vec())
+            .unwrap_or_default()
+    };
+    emit_library_event(&window, LibraryEvent::DirectoryAdded(dir_name)).await?;
+    for track in tracks.iter().filter(|track| track.integrity_warning) {
+        emit_library_event(&window, LibraryEvent::IntegrityWarning(track.id)).await?;
+    }
+    schedule_consistency_check(&window, &state).await;
+    Ok(tracks)
+}
+
+// 导入预览
+#[derive(Clone, serde::Serialize)]
+struct ImportPreview {
+    // 曲目表中尚不存在（按 SHA256）的谱面数
+    new_tracks: u32,
+    // 与曲目表或本次扫描中其他谱面重复的谱面数
+    duplicate_tracks: u32,
+    // 引用的 #WAVxx 音源中找不到文件的数量（仅 BMS）
+    missing_keysounds_estimate: u32,
+    total_size_bytes: u64,
+    // 读取失败而被跳过的文件数
+    failed_files: u32,
+    preview_id: String,
+}
+
+// 导入向导第一步：扫描目录并返回预览，扫描结果暂存到 confirm_import 或 cancel_import 为止
+// （最长 PENDING_IMPORT_TTL）
+#[tauri::command]
+async fn prepare_import(
+    path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<ImportPreview, String> {
+    directory_name(&path).map_err(|e| e.context("while preparing import").to_string())?;
+
+    let config = state.config.lock().await.clone();
+    let storage_type = storage::detect_storage_type(&path).await;
+    let handle = bms_scan::scan_directory_recursive(path.clone(), storage_type, config)
+        .await
+        .map_err(|e| e.to_string())?;
+    *state.current_scan.lock().await = Some(handle.is_completed.clone());
+    let scanned = handle.collect().await;
+
+    // 仅元数据模式扫描的文件需要读取内容才能得到哈希，读取失败的文件被跳过（记录日志并计数）
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(1));
+    let mut files = Vec::with_capacity(scanned.len());
+    let mut failed_files = 0;
+    for mut file_info in scanned {
+        match file_info.load_content(semaphore.clone()).await {
+            Ok(()) => files.push(file_info),
+            Err(e) => {
+                tracing::warn!(error = %e, path = %file_info.absolute_path().display(), "skipping unreadable chart");
+                failed_files += 1;
+            }
+        }
+    }
+
+    let mut seen = state
+        .sha256_index
+        .lock()
+        .await
+        .keys()
+        .cloned()
+        .collect::<HashSet<_>>();
+    let mut new_tracks = 0;
+    let mut missing_keysounds_estimate = 0;
+    let mut total_size_bytes = 0;
+    for file_info in &files {
+        if seen.insert(file_info.sha256_hex()) {
+            new_tracks += 1;
+        }
+        missing_keysounds_estimate +=
+            missing_keysounds(file_info.absolute_path(), file_info.content_or_empty()).len() as u32;
+        total_size_bytes += file_info.size;
+    }
+
+    // 暂存期间不持有文件内容，confirm_import 时重新读取
+    for file_info in &mut files {
+        file_info.content = None;
+    }
+
+    let preview_id = uuid::Uuid::new_v4().to_string();
+    let preview = ImportPreview {
+        new_tracks,
+        duplicate_tracks: files.len() as u32 - new_tracks,
+        missing_keysounds_estimate,
+        total_size_bytes,
+        failed_files,
+        preview_id: preview_id.clone(),
+    };
+    let mut pending_imports = state.pending_imports.lock().await;
+    pending_imports.retain(|_, pending| pending.created_at.elapsed() < PENDING_IMPORT_TTL);
+    pending_imports.insert(
+        preview_id,
+        PendingImport {
+            path,
+            files,
+            created_at: Instant::now(),
+        },
+    );
+    Ok(preview)
+}
+
+// 导入向导第二步：按指定的去重策略（DedupStrategy 的名称）提交暂存的导入
+#[tauri::command]
+async fn confirm_import(
+    window: Window,
+    preview_id: String,
+    dedup_strategy: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let dedup_strategy: DedupStrategy = serde_json::from_value(serde_json::Value::String(
+        dedup_strategy.clone(),
+    ))
+    .map_err(|_| {
+        CabinetError::UnknownValue {
+            kind: "dedup strategy".into(),
+            value: dedup_strategy.clone(),
+        }
+        .context("while confirming import")
+        .to_string()
+    })?;
+    let PendingImport { path, files, .. } = state
+        .pending_imports
+        .lock()
+        .await
+        .remove(&preview_id)
+        .filter(|pending| pending.created_at.elapsed() < PENDING_IMPORT_TTL)
+        .ok_or_else(|| {
+            CabinetError::PendingImportNotFound {
+                id: preview_id.clone(),
+            }
+            .context("while confirming import")
+            .to_string()
+        })?;
+    let dir_name =
+        directory_name(&path).map_err(|e| e.context("while confirming import").to_string())?;
+
+    state.import_conflicts.lock().await.clear();
+    let (mut tracks, _failed) = tracks_from_files(files).await;
+    assign_pack_ids(&mut tracks, &path);
+    state
+        .directory_roots
+        .lock()
+        .await
+        .insert(dir_name.clone(), path);
+    commit_directory(&window, &state, &dir_name, tracks, dedup_strategy).await?;
+
+    emit_library_event(&window, LibraryEvent::DirectoryAdded(dir_name)).await?;
+    schedule_consistency_check(&window, &state).await;
+    Ok(())
+}
+
+// 放弃暂存的导入
+#[tauri::command]
+async fn cancel_import(preview_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .pending_imports
+        .lock()
+        .await
+        .remove(&preview_id)
+        .map(|_| ())
+        .ok_or_else(|| {
+            CabinetError::PendingImportNotFound { id: preview_id }
+                .context("while cancelling import")
+                .to_string()
+        })
+}
+
+// 辅助函数：由扫描结果生成曲目（仅元数据模式扫描的文件会先读取内容），返回曲目与失败的谱面数。
+// 读取或解析失败的谱面被跳过（记录日志并计数），与 collect_directory_tracks 相同
+async fn tracks_from_files(files: Vec<FileInfo>) -> (Vec<Track>, u32) {
+    let mut tracks = Vec::with_capacity(files.len());
+    let mut failed = 0;
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(1));
+    for mut file_info in files {
+        match track_from_file_info(&mut file_info, semaphore.clone()).await {
+            Ok(track) => tracks.push(track),
+            Err(e) => {
+                tracing::warn!(error = %e, path = %file_info.absolute_path().display(), "skipping chart that failed to parse");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        tracing::warn!(failed, "some charts were skipped");
+    }
+    (tracks, failed)
+}
+
+// 辅助函数：由单个扫描结果生成曲目
+async fn track_from_file_info(
+    file_info: &mut FileInfo,
+    semaphore: Arc<tokio::sync::Semaphore>,
+) -> Result<Track, String> {
+    file_info
+        .load_content(semaphore)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mtime = file_mtime(file_info.absolute_path()).await;
+    let mut track = track_from_content(
+        file_info.absolute_path(),
+        file_info.content_or_empty(),
+        file_info.sha256_hex(),
+        mtime,
+    )?;
+    track.integrity_warning = file_info.integrity_warning;
+    track.missing_keysounds =
+        missing_keysounds(file_info.absolute_path(), file_info.content_or_empty());
+    Ok(track)
+}
+
+// 辅助函数：列出 BMS 谱面中找不到文件的 #WAVxx 定义（键名统一为大写）
+fn missing_keysounds(chart_path: &Path, content: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(content)
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line
+                .trim()
+                .strip_prefix('#')?
+                .split_once(char::is_whitespace)?;
+            let is_wav = key.len() == 5 && key.get(..3)?.eq_ignore_ascii_case("WAV");
+            is_wav.then(|| (key.to_ascii_uppercase(), value.trim()))
+        })
+        .filter(|(_, file_name)| {
+            !file_name.is_empty() && audio::resolve_audio_path(chart_path, file_name).is_none()
+        })
+        .map(|(key, _)| key)
+        .collect()
+}
+
+// 全曲库缺失音源统计
+#[derive(Clone, serde::Serialize)]
+struct GlobalMissingReport {
+    tracks_with_missing: u32,
+    total_missing_files: u32,
+    // (曲目 ID, 找不到文件的 #WAVxx 键名)，按 ID 升序
+    missing_by_track: Vec<(u32, Vec<String>)>,
+}
+
+// 辅助函数：汇总曲目中记录的缺失音源
+fn global_missing_report(tracks: &[Track]) -> GlobalMissingReport {
+    let mut missing_by_track: Vec<(u32, Vec<String>)> = tracks
+        .iter()
+        .filter(|track| !track.missing_keysounds.is_empty())
+        .map(|track| (track.id, track.missing_keysounds.clone()))
+        .collect();
+    missing_by_track.sort_by_key(|(id, _)| *id);
+    GlobalMissingReport {
+        tracks_with_missing: missing_by_track.len() as u32,
+        total_missing_files: missing_by_track
+            .iter()
+            .map(|(_, keys)| keys.len() as u32)
+            .sum(),
+        missing_by_track,
+    }
+}
+
+// 获取全曲库的缺失音源统计（使用导入时记录的结果）
+#[tauri::command]
+async fn get_global_missing_keysounds(
+    state: State<'_, AppState>,
+) -> Result<GlobalMissingReport, String> {
+    let library = state.lock_library().await;
+    Ok(global_missing_report(&library.tracks))
+}
+
+// 重新读取每个曲目的谱面文件检查音源是否存在，并返回更新后的统计；
+// 读取失败的曲目（如由内存导入的曲目）保留原有结果
+#[tauri::command]
+async fn recheck_all_keysounds(state: State<'_, AppState>) -> Result<GlobalMissingReport, String> {
+    Ok(recheck_keysounds(&state).await)
+}
+
+// 辅助函数：重新检查全部曲目的音源并写回曲库
+async fn recheck_keysounds(state: &AppState) -> GlobalMissingReport {
+    let paths: Vec<(u32, PathBuf)> = {
+        let library = state.lock_library().await;
+        library
+            .tracks
+            .iter()
+            .map(|track| (track.id, track.absolute_path.clone()))
+            .collect()
+    };
+
+    let mut results = Vec::with_capacity(paths.len());
+    for (id, path) in paths {
+        if let Ok(content) = fs::read(&path).await {
+            results.push((id, missing_keysounds(&path, &content)));
+        }
+    }
+
+    let mut library = state.lock_library().await;
+    for (id, missing) in results {
+        if let Some(&index) = library.id_index.get(&id) {
+            library.tracks[index].missing_keysounds = missing;
+            library.track_cache.invalidate(id);
+        }
+    }
+    global_missing_report(&library.tracks)
+}
+
+// 由内存中的谱面内容导入曲目（不读写磁盘），absolute_path 为虚拟路径 `<directory>/<name>`，
+// name 的扩展名决定按 BMS 还是 BMSON 解析
+#[tauri::command]
+async fn import_track_from_bytes(
+    name: String,
+    directory: String,
+    content: Vec<u8>,
+    state: State<'_, AppState>,
+) -> Result<Track, String> {
+    insert_track_from_bytes(&state, &name, &directory, &content).await
+}
+
+// 辅助函数：解析谱面内容，分配新 ID 后插入到目录（目录不存在时创建）
+async fn insert_track_from_bytes(
+    state: &AppState,
+    name: &str,
+    directory: &str,
+    content: &[u8],
+) -> Result<Track, String> {
+    let path = Path::new(directory).join(name);
+    let mut track = track_from_bytes(&path, content, 0)?;
+    track.id = state.next_id.fetch_add(1, Ordering::Relaxed);
+
+    let mut library = state.lock_library().await;
+    library.insert_track(directory, track.clone())?;
+    Ok(track)
+}
+
+// 被丢弃时将扫描标记为完成，使提前返回的扫描也会被标记
+struct ScanCompletionGuard(Arc<AtomicBool>);
+
+impl Drop for ScanCompletionGuard {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+// 辅助函数：为不经由 ScanHandle 的扫描登记新的完成标记并重置导入进度
+async fn begin_scan(state: &AppState) -> ScanCompletionGuard {
+    let completed = Arc::new(AtomicBool::new(false));
+    *state.current_scan.lock().await = Some(completed.clone());
+    *state.current_progress.lock().await = 0.0;
+    ScanCompletionGuard(completed)
+}
+
+// 最近一次扫描是否已完成，尚未进行过扫描时为 true
+#[tauri::command]
+async fn is_scan_complete(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(scan_complete(&state).await)
+}
+
+// 辅助函数：读取最近一次扫描的完成标记
+async fn scan_complete(state: &AppState) -> bool {
+    state
+        .current_scan
+        .lock()
+        .await
+        .as_ref()
+        .is_none_or(|completed| completed.load(Ordering::Acquire))
+}
+
+// 获取 add_directory 的导入进度（百分比）
+#[tauri::command]
+async fn get_scan_progress(state: State<'_, AppState>) -> Result<f64, String> {
+    Ok(*state.current_progress.lock().await)
+}
+
+// add_directory 的导入结果，各项与 dry_run_add_directory 预演结果中的计数对应
+#[derive(Clone, serde::Serialize)]
+struct AddDirectoryReport {
+    added: u32,
+    // 按去重策略被跳过的谱面数
+    skipped_duplicate: u32,
+    // 读取或解析失败而被跳过的谱面数
+    failed: u32,
+}
+
+// 处理目录的异步函数：扫描并导入目录中的谱面，替换同名目录
+async fn process_directory(
+    window: &Window,
+    state: &State<'_, AppState>,
+    path: PathBuf,
+) -> Result<AddDirectoryReport, String> {
+    let _scan = begin_scan(state).await;
+    let config = state.config.lock().await.clone();
+    let dir_name =
+        directory_name(&path).map_err(|e| e.context("while adding directory").to_string())?;
+    let (tracks, failed) = collect_directory_tracks(window, state, &path, &config).await?;
+    let total = tracks.len() as u32;
+
+    state
+        .directory_roots
+        .lock()
+        .await
+        .insert(dir_name.clone(), path);
+    let added = commit_directory(window, state, &dir_name, tracks, config.dedup_strategy).await?;
+
+    emit_library_event(window, LibraryEvent::DirectoryAdded(dir_name)).await?;
+    schedule_consistency_check(window, state).await;
+    Ok(AddDirectoryReport {
+        added,
+        skipped_duplicate: total - added,
+        failed,
+    })
+}
+
+// 辅助函数：以导入的曲目替换目录，为按去重策略跳过的谱面发送 DedupSkipped 事件并记录导入冲突，
+// 返回目录中实际导入的曲目数
+async fn commit_directory(
+    window: &Window,
+    state: &AppState,
+    dir_name: &str,
+    tracks: Vec<Track>,
+    dedup_strategy: DedupStrategy,
+) -> Result<u32, String> {
+    let conflicts = state
+        .replace_directory(dir_name.to_string(), tracks, dedup_strategy)
+        .await;
+    for conflict in &conflicts {
+        emit_library_event(
+            window,
+            LibraryEvent::DedupSkipped(conflict.skipped_path.clone()),
+        )
+        .await?;
+    }
+    state.import_conflicts.lock().await.extend(conflicts);
+    Ok(state
+        .directory_index
+        .lock()
+        .await
+        .get(dir_name)
+        .map_or(0, |range| range.len() as u32))
+}
+
+// 辅助函数：遍历目录并解析其中的谱面（同时更新导入进度），返回曲目与失败的谱面数。
+// 读取或解析失败的谱面被跳过（记录日志并计数），不影响其余谱面；
+// add_directory 与 dry_run_add_directory 共用，因此两者的计数一致
+async fn collect_directory_tracks(
+    window: &Window,
+    state: &AppState,
+    path: &Path,
+    config: &ScanConfig,
+) -> Result<(Vec<Track>, u32), String> {
+    let mut tracks = Vec::new();
+    let total_files = count_chart_files(path, config).await?;
+    let mut processed = 0;
+    let mut failed = 0;
+
+    let entries = bms_scan::walk_dir_async(path.to_path_buf(), config);
+    tokio::pin!(entries);
+    while let Some(entry) = entries.next().await {
+        let entry_path = entry.path();
+        if !config.is_target_file(&entry_path) {
+            continue;
+        }
+        match process_single_file(&entry_path).await {
+            Ok(track) => tracks.push(track),
+            Err(e) => {
+                tracing::warn!(error = %e, path = %entry_path.display(), "skipping chart that failed to parse");
+                failed += 1;
+            }
+        }
+
+        // 更新进度
+        processed += 1;
+        let progress = (processed as f64 / total_files as f64) * 100.0;
+        *state.current_progress.lock().await = progress;
+        window
+            .emit("import_progress", progress)
+            .map_err(|e| e.to_string())?;
+    }
+    if failed > 0 {
+        tracing::warn!(failed, directory = %path.display(), "some charts were skipped");
+    }
+
+    assign_pack_ids(&mut tracks, path);
+    Ok((tracks, failed))
+}
+
+// 辅助函数：以目录的文件名作为曲库中的目录名
+fn directory_name(path: &Path) -> Result<String, CabinetError> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| CabinetError::InvalidDirectoryName {
+            path: path.display().to_string(),
+        })
+}
+
+// 监视目录（无需先导入），其中出现新的谱面文件时自动导入到以目录名为键的目录下，
+// 每导入或更新一个文件发送一次 TrackAdded / TrackUpdated 曲库事件
+#[tauri::command]
+async fn watch_directory(
+    window: Window,
+    path: PathBuf,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let Some((dir_name, mut changes)) = start_watching(&state, path).await? else {
+        return Ok(());
+    };
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let state = window.state::<AppState>();
+            let debounce = Duration::from_millis(state.config.lock().await.debounce_ms);
+            let Some(paths) = watcher::next_batch(&mut changes, debounce).await else {
+                break;
+            };
+            let events = auto_import_files(&state, &dir_name, paths).await;
+            if events.is_empty() {
+                continue;
+            }
+            for event in events {
+                let _ = emit_library_event(&window, event).await;
+            }
+            schedule_consistency_check(&window, &state).await;
+        }
+    });
+    Ok(())
+}
+
+// 辅助函数：登记目录的监视器与目录根路径，返回目录名与变更路径的接收端；
+// 目录已在监视中时返回 None
+async fn start_watching(
+    state: &AppState,
+    path: PathBuf,
+) -> Result<Option<(String, mpsc::UnboundedReceiver<PathBuf>)>, String> {
+    if !path.is_dir() {
+        return Err(CabinetError::DirectoryNotFound {
+            name: path.display().to_string(),
+        }
+        .context("while watching directory")
+        .to_string());
+    }
+    let dir_name =
+        directory_name(&path).map_err(|e| e.context("while watching directory").to_string())?;
+
+    let mut watched_directories = state.watched_directories.lock().await;
+    if watched_directories.contains_key(&path) {
+        return Ok(None);
+    }
+    let (directory_watcher, changes) = watcher::watch(&path)?;
+    watched_directories.insert(path.clone(), directory_watcher);
+    drop(watched_directories);
+
+    state
+        .directory_roots
+        .lock()
+        .await
+        .entry(dir_name.clone())
+        .or_insert(path);
+    Ok(Some((dir_name, changes)))
+}
+
+// 停止监视目录，返回该目录此前是否正被监视。已导入的曲目保留
+#[tauri::command]
+async fn unwatch_directory(path: PathBuf, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(stop_watching(&state, &path).await)
+}
+
+// 辅助函数：移除目录的监视器。监视器被丢弃后通道关闭，watcher::next_batch 返回 None，
+// 监视任务随之结束
+async fn stop_watching(state: &AppState, path: &Path) -> bool {
+    state
+        .watched_directories
+        .lock()
+        .await
+        .remove(path)
+        .is_some()
+}
+
+// 辅助函数：导入监视到的谱面文件，已导入的同路径曲目在内容变化时原地更新（沿用原 ID），
+// 内容未变的修改事件在解析前即被跳过。读取或解析失败的文件（如仍在复制中）跳过，等待下一次变更事件。
+// 返回每个导入或更新的文件对应的 TrackAdded / TrackUpdated 曲库事件
+async fn auto_import_files(
+    state: &AppState,
+    directory: &str,
+    paths: Vec<PathBuf>,
+) -> Vec<LibraryEvent> {
+    let config = state.config.lock().await.clone();
+    let mut events = Vec::new();
+    for path in paths {
+        if !path.is_file() || !config.is_target_file(&path) {
+            continue;
+        }
+        let old_sha256 = {
+            let library = state.lock_library().await;
+            library
+                .tracks
+                .iter()
+                .find(|existing| existing.absolute_path == path)
+                .and_then(|existing| watcher::sha256_from_hex(&existing.sha256))
+        };
+        if let Some(old_sha256) = old_sha256 {
+            if !watcher::detect_real_change(old_sha256, &path)
+                .await
+                .unwrap_or(false)
+            {
+                continue;
+            }
+        }
+        let Ok(mut track) = process_single_file(&path).await else {
+            continue;
+        };
+
+        let mut library = state.lock_library().await;
+        let existing = library
+            .tracks
+            .iter()
+            .find(|existing| existing.absolute_path == path)
+            .map(|existing| (existing.id, existing.sha256.clone()));
+        let event = match existing {
+            Some((_, sha256)) if sha256 == track.sha256 => continue,
+            Some((id, _)) => {
+                track.id = id;
+                library.update_track(track.clone());
+                LibraryEvent::TrackUpdated(track)
+            }
+            None => {
+                track.id = state.next_id.fetch_add(1, Ordering::Relaxed);
+                if library.insert_track(directory, track.clone()).is_err() {
+                    continue;
+                }
+                LibraryEvent::TrackAdded(track)
+            }
+        };
+        drop(library);
+        events.push(event);
+    }
+    events
+}
+
+// 检测谱面文件的文本编码（只读取开头 4 KiB），用于排查乱码
+#[tauri::command]
+async fn detect_file_encoding(path: PathBuf) -> Result<encoding::EncodingInfo, String> {
+    let file = fs::File::open(&path).await.map_err(|e| e.to_string())?;
+    let mut sample = Vec::with_capacity(encoding::SAMPLE_SIZE);
+    file.take(encoding::SAMPLE_SIZE as u64)
+        .read_to_end(&mut sample)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(encoding::detect_encoding(&sample))
+}
+
+// 按检测到的编码将谱面文件转换为 UTF-8（无 BOM）并写入 output_path
+#[tauri::command]
+async fn transcode_bms_to_utf8(path: PathBuf, output_path: PathBuf) -> Result<(), String> {
+    let content = fs::read(&path).await.map_err(|e| e.to_string())?;
+    fs::write(&output_path, encoding::decode_to_utf8(&content))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 处理单个谱面文件
+async fn process_single_file(path: &Path) -> Result<Track, String> {
+    // 读取文件内容计算SHA256
+    let content = fs::read(path).await.map_err(|e| e.to_string())?;
+    let mtime = file_mtime(path).await;
+    let mut track = track_from_bytes(path, &content, mtime)?;
+    track.missing_keysounds = missing_keysounds(path, &content);
+    Ok(track)
+}
+
+// 辅助函数：计算内容的 SHA256 并解析为曲目，path 可以是不存在于磁盘上的虚拟路径
+fn track_from_bytes(path: &Path, content: &[u8], mtime: u64) -> Result<Track, String> {
+    let mut hasher = Sha256::new();
+    hasher.update(content);
+    let hash = format!("{:x}", hasher.finalize());
+    track_from_content(path, content, hash, mtime)
+}
+
+// 辅助函数：获取文件修改时间（Unix 时间戳，秒），失败时为 0
+async fn file_mtime(path: &Path) -> u64 {
+    fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+// 辅助函数：由已读取的谱面内容构建曲目
+fn track_from_content(
+    path: &Path,
+    content: &[u8],
+    hash: String,
+    mtime: u64,
+) -> Result<Track, String> {
+    // 存在与谱面内容一致的 sidecar 时直接使用其中的元数据
+    if let Some(track) = sidecar::read_sidecar(path).filter(|track| track.sha256 == hash) {
+        return Ok(Track {
+            id: 0,
+            absolute_path: path.to_path_buf(),
+            mtime,
+            ..track
+        });
+    }
+
+    // 解析谱面，缺失的头部字段使用占位值
+    let chart = parse_chart_file(path, content)?;
+    let chart_fingerprint = hex_string(&bms_parse::chart_fingerprint(&chart));
+    let ParsedChart {
+        header,
+        notes,
+        bga,
+        bpm_timeline,
+        stops,
+        scrolls,
+        measure_lengths,
+        key_mode,
+        chart_type,
+    } = chart;
+    let (min_scroll, max_scroll) = bms_parse::scroll_range(&scrolls);
+    let effective_bpm = effective_bpm(&bpm_timeline, &notes);
+    let counts = bms_parse::count_notes(&notes);
+    let chord_stats = bms_parse::chord_stats_from_notes(&notes);
+    let breakdown = bms_parse::lane_breakdown(&notes);
+    let (lane_breakdown, double_lane_breakdown) = if key_mode.is_double_play() {
+        (None, Some(breakdown))
+    } else {
+        (Some(breakdown.p1), None)
+    };
+    let preview_duration_ms = header
+        .preview
+        .as_deref()
+        .and_then(|preview| audio::resolve_audio_path(path, preview))
+        .and_then(|preview| audio::read_audio_duration_ms(&preview));
+    let total_measures = notes.iter().map(|note| note.measure + 1).max();
+    let estimated_duration_ms = total_measures.map(|total_measures| {
+        bpm_timeline
+            .duration_ms(total_measures, &measure_lengths)
+            .round() as u32
+    });
+    let chart_duration_ms = total_measures.map(|total_measures| {
+        bms_parse::compute_chart_duration_ms(
+            &bpm_timeline,
+            total_measures,
+            &stops,
+            &measure_lengths,
+        )
+    });
+    let note_density = match chart_duration_ms {
+        Some(duration_ms) if duration_ms > 0 => {
+            counts.note_count as f64 * 1000.0 / duration_ms as f64
+        }
+        _ => 0.0,
+    };
+    let peak_nps = bms_parse::peak_nps(&notes, &bpm_timeline, &stops, &measure_lengths);
+
+    // 标题或艺术家缺失时尝试从文件夹名推断
+    let (metadata_source, inferred) = if header.title.is_some() && header.artist.is_some() {
+        (MetadataSource::Parsed, None)
+    } else {
+        match metadata::infer_metadata_from_path(path) {
+            Some(inferred) => (MetadataSource::InferredFromPath, Some(inferred)),
+            None => (MetadataSource::Default, None),
+        }
+    };
+    let (inferred_title, inferred_artist, inferred_genre) = match inferred {
+        Some(inferred) => (Some(inferred.title), Some(inferred.artist), inferred.genre),
+        None => (None, None, None),
+    };
+
+    let artist = header
+        .artist
+        .or(inferred_artist)
+        .unwrap_or_else(|| "Unknown Artist".into());
+
+    let mut track = Track {
+        // ID 在插入 AppState 时分配
+        id: 0,
+        title: header.title.or(inferred_title).unwrap_or_else(|| {
+            path.file_stem()
+                .and_then(|n| n.to_str())
+                .unwrap_or("Unknown")
+                .to_string()
+        }),
+        album_id: Some(album_id_for(&artist, path)),
+        // 包 ID 依赖导入根目录，在 assign_pack_ids 中设置
+        pack_id: None,
+        artist,
+        genre: header
+            .genre
+            .or(inferred_genre)
+            .unwrap_or_else(|| "Unknown Genre".into()),
+        bpm: header.bpm.unwrap_or(bms_parse::DEFAULT_BPM),
+        bpm_min: bpm_timeline.min_bpm(),
+        bpm_max: bpm_timeline.max_bpm(),
+        effective_bpm,
+        play_level: header.play_level.unwrap_or(0),
+        key_mode,
+        chart_type,
+        note_count: counts.note_count,
+        ln_count: counts.ln_count,
+        scratch_count: counts.scratch_count,
+        bga_layer_count: bga.layer_count,
+        has_bga: bga.has_bga,
+        preview_duration_ms,
+        estimated_duration_ms,
+        chart_duration_ms,
+        note_density,
+        peak_nps,
+        has_variable_time_signature: measure_lengths
+            .values()
+            .any(|multiplier| *multiplier != 1.0),
+        has_scroll_changes: scrolls.iter().any(|event| event.scroll_multiplier != 1.0),
+        min_scroll,
+        max_scroll,
+        has_negative_scroll: min_scroll < 0.0,
+        sha256: hash,
+        chart_fingerprint: Some(chart_fingerprint),
+        absolute_path: path.to_path_buf(),
+        metadata_source,
+        mtime,
+        power_factor: 0.0,
+        file_size: content.len() as u64,
+        chord_stats,
+        lane_breakdown,
+        double_lane_breakdown,
+        gauge_type: rating::gauge_type_for(header.rank, header.difficulty),
+        material: header.material,
+        octfp: header.octfp,
+        octave: header.octave,
+        is_cdda_chart: !header.cdda_tracks.is_empty(),
+        cdda_tracks: header.cdda_tracks,
+        has_wavcmd: header.has_wavcmd,
+        integrity_warning: false,
+        missing_keysounds: Vec::new(),
+        user_edited_fields: Vec::new(),
+        notes: None,
+    };
+    track.power_factor = rating::compute_power_factor(&track);
+    Ok(track)
+}
+
+// 辅助函数：代表性 BPM，没有可演奏音符时取最低与最高 BPM 的平均值
+fn effective_bpm(bpm_timeline: &bms_parse::BpmTimeline, notes: &[NoteObject]) -> f64 {
+    bpm_timeline
+        .effective_bpm(notes)
+        .unwrap_or_else(|| (bpm_timeline.min_bpm() + bpm_timeline.max_bpm()) / 2.0)
+}
+
+// 辅助函数：按扩展名选择 BMS / BMSON 格式解析谱面内容
+fn parse_chart_file(path: &Path, content: &[u8]) -> Result<ParsedChart, String> {
+    let is_bmson = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("bmson"));
+    bms_parse::parse_chart(content, is_bmson).map_err(|e| e.to_string())
+}
+
+// 辅助函数：统计谱面文件数量
+async fn count_chart_files(path: &Path, config: &ScanConfig) -> Result<usize, String> {
+    let count = bms_scan::walk_dir_async(path.to_path_buf(), config)
+        .filter(|entry| future::ready(config.is_target_file(&entry.path())))
+        .count()
+        .await;
+    Ok(count)
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // 日志级别由 RUST_LOG 环境变量控制（如 RUST_LOG=be_music_cabinet_lib=debug）
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    tauri::Builder::default()
+        .setup(|app| {
+            let config = load_config(app.handle()).unwrap_or_default();
+            let sessions = sessions_file_path(app.handle())
+                .map(|path| session::load_sessions(&path))
+                .unwrap_or_default();
+            let next_session_id = sessions
+                .iter()
+                .map(|session| session.session_id + 1)
+                .max()
+                .unwrap_or(0);
+            // 超过保留时间的回收站条目在启动时永久删除，文件在下次修改回收站时更新
+            let mut trash = trash_file_path(app.handle())
+                .map(|path| trash::load_trash(&path))
+                .unwrap_or_default();
+            trash::prune_expired(&mut trash);
+            app.manage(AppState {
+                next_id: AtomicU32::new(config.id_start.unwrap_or(0)),
+                config: Mutex::new(config),
+                session_history: Mutex::new(sessions),
+                next_session_id: AtomicU32::new(next_session_id),
+                trash: Mutex::new(trash),
+                ..Default::default()
+            });
+
+            // 存在二进制索引时直接载入曲库，读取失败时保持空曲库
+            if let Ok(path) = binary_index_path(app.handle()) {
+                if path.is_file() {
+                    let state = app.state::<AppState>();
+                    match tauri::async_runtime::block_on(load_binary_index(&state, &path)) {
+                        Ok(count) => tracing::info!(count, "loaded library from binary index"),
+                        Err(e) => tracing::warn!(error = %e, "failed to load binary index"),
+                    }
+                }
+            }
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_directories,
+            get_tracks,
+            get_tracks_sorted,
+            get_tracks_page,
+            stream_tracks,
+            get_tracks_flat,
+            get_track_by_id,
+            track_exists,
+            get_track_detail,
+            get_albums,
+            get_album_tracks,
+            detect_packs,
+            get_pack_tracks,
+            remove_track,
+            remove_directory,
+            soft_delete_track,
+            restore_from_trash,
+            import_from_lr2_db,
+            empty_trash,
+            get_trash,
+            subscribe_to_events,
+            validate_index_consistency,
+            check_state_health,
+            reindex_library,
+            reset_state,
+            export_state_snapshot,
+            restore_state_snapshot,
+            migrate_library,
+            serialize_library_to_binary,
+            load_library_from_binary,
+            get_tracks_by_artist,
+            get_tracks_by_genre,
+            get_tracks_with_inferred_metadata,
+            get_uncategorized_tracks,
+            get_uncategorized_count,
+            get_tracks_by_power_factor_range,
+            get_tracks_by_nps_range,
+            get_tracks_with_cdda,
+            get_tracks_by_gauge_type,
+            get_score_grade,
+            get_genre_stats,
+            generate_library_report,
+            get_difficulty_stats,
+            relative_difficulty,
+            move_track_to_directory,
+            move_all_tracks,
+            get_bga_events,
+            get_scroll_events,
+            get_chord_stats,
+            export_chart_svg,
+            get_notes,
+            prefetch_tracks,
+            cancel_prefetch,
+            get_lane_breakdown,
+            recommend_similar_tracks,
+            recommend_from_history,
+            compare_charts,
+            compare_directories,
+            find_chart_duplicates_by_fingerprint,
+            get_import_conflicts,
+            resolve_conflict_keep_new,
+            get_storage_type,
+            benchmark_scan,
+            get_scan_history,
+            get_last_scan_stats,
+            write_sidecars,
+            detect_file_encoding,
+            transcode_bms_to_utf8,
+            watch_directory,
+            unwatch_directory,
+            get_global_missing_keysounds,
+            recheck_all_keysounds,
+            suggest_hardlinks,
+            apply_hardlinks,
+            normalize_track_metadata,
+            batch_recompute_metadata,
+            update_track_metadata,
+            get_locked_tracks,
+            start_session,
+            end_session,
+            record_play_in_session,
+            get_session_history,
+            create_playlist,
+            get_playlists,
+            export_playlist_to_m3u,
+            import_playlist_from_m3u,
+            bulk_tag_tracks,
+            bulk_tag_by_artist,
+            bulk_tag_by_genre,
+            get_tracks_by_tag,
+            get_scan_config,
+            update_config,
+            validate_config,
+            add_scan_extension,
+            remove_scan_extension,
+            add_exclude_pattern,
+            remove_exclude_pattern,
+            fuzzy_search_by_artist,
+            fuzzy_search_by_genre,
+            get_all_artists,
+            get_all_genres,
+            get_artist_analytics,
+            get_all_bpm_values,
+            handle_dropped_files,
+            add_directory,
+            dry_run_add_directory,
+            scan_file_list,
+            is_scan_complete,
+            get_scan_progress,
+            prepare_import,
+            confirm_import,
+            cancel_import,
+            import_track_from_bytes,
+            add_directory_with_timeout
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TrackBuilder};
+
+    // 以指定曲目新建一个目录，返回分配的曲目 ID
+    async fn add_tracks(state: &AppState, directory: &str, tracks: Vec<Track>) -> Vec<u32> {
+        state
+            .replace_directory(
+                directory.to_string(),
+                tracks,
+                DedupStrategy::AllowDuplicates,
+            )
+            .await;
+        let library = state.lock_library().await;
+        let range = library.directory_index[directory].clone();
+        library.tracks[range].iter().map(|track| track.id).collect()
+    }
+
+    // 缓存曲目（与 get_track_by_id 相同，在持有曲目表锁时写入）
+    async fn cache_track(state: &AppState, id: u32) {
+        let library = state.lock_library().await;
+        let index = library.id_index[&id];
+        library.track_cache.insert(library.tracks[index].clone());
+    }
+
+    #[tokio::test]
+    async fn track_cache_is_invalidated_on_update() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![TrackBuilder::new("a").title("Old").build()],
+        )
+        .await;
+        cache_track(&state, ids[0]).await;
+        assert_eq!(state.track_cache.get(ids[0]).unwrap().title, "Old");
+
+        let mut library = state.lock_library().await;
+        let mut updated = library.tracks[0].clone();
+        updated.title = "New".to_string();
+        library.update_track(updated);
+        assert!(state.track_cache.get(ids[0]).is_none());
+    }
+
+    #[tokio::test]
+    async fn track_cache_is_invalidated_on_removal() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").build(),
+                TrackBuilder::new("b").build(),
+            ],
+        )
+        .await;
+        cache_track(&state, ids[0]).await;
+        cache_track(&state, ids[1]).await;
+
+        state.lock_library().await.remove_track(ids[0]);
+        assert!(state.track_cache.get(ids[0]).is_none());
+        assert!(state.track_cache.get(ids[1]).is_some());
+
+        // 替换目录会移除其中原有的曲目
+        add_tracks(&state, "pack", vec![TrackBuilder::new("c").build()]).await;
+        assert!(state.track_cache.get(ids[1]).is_none());
+    }
+
+    #[tokio::test]
+    async fn track_cache_is_cleared_with_library() {
+        let state = AppState::default();
+        let ids = add_tracks(&state, "pack", vec![TrackBuilder::new("a").build()]).await;
+        cache_track(&state, ids[0]).await;
+        state.lock_library().await.rebuild_indexes();
+        assert!(state.track_cache.get(ids[0]).is_none());
+
+        cache_track(&state, ids[0]).await;
+        state.lock_library().await.clear();
+        assert!(state.track_cache.get(ids[0]).is_none());
+    }
+
+    #[tokio::test]
+    async fn relative_difficulty_is_a_z_score() {
+        let state = AppState::default();
+        let levels = [1, 3, 1, 3];
+        let tracks = levels
+            .iter()
+            .enumerate()
+            .map(|(i, &level)| TrackBuilder::new(&i.to_string()).play_level(level).build())
+            .collect();
+        let ids = add_tracks(&state, "pack", tracks).await;
+
+        let (mean, std_dev) = state.lock_library().await.difficulty_mean_std_dev();
+        assert_eq!((mean, std_dev), (2.0, 1.0));
+        assert_eq!(z_score(2.0, mean, std_dev), 0.0);
+        for (&id, expected) in ids.iter().zip([-1.0, 1.0, -1.0, 1.0]) {
+            assert_eq!(track_relative_difficulty(&state, id).await, Ok(expected));
+        }
+        assert!(track_relative_difficulty(&state, 9999).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn relative_difficulty_is_zero_for_uniform_levels() {
+        let state = AppState::default();
+        let tracks = (0..3)
+            .map(|i| TrackBuilder::new(&i.to_string()).play_level(7).build())
+            .collect();
+        let ids = add_tracks(&state, "pack", tracks).await;
+
+        let (mean, std_dev) = state.lock_library().await.difficulty_mean_std_dev();
+        assert_eq!((mean, std_dev), (7.0, 0.0));
+        assert_eq!(track_relative_difficulty(&state, ids[0]).await, Ok(0.0));
+    }
+
+    #[tokio::test]
+    async fn difficulty_stats_cache_is_invalidated() {
+        let state = AppState::default();
+        add_tracks(
+            &state,
+            "a",
+            vec![TrackBuilder::new("a").play_level(2).build()],
+        )
+        .await;
+        assert_eq!(
+            state.lock_library().await.difficulty_mean_std_dev(),
+            (2.0, 0.0)
+        );
+        add_tracks(
+            &state,
+            "b",
+            vec![TrackBuilder::new("b").play_level(4).build()],
+        )
+        .await;
+        assert_eq!(
+            state.lock_library().await.difficulty_mean_std_dev(),
+            (3.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let values = (1..=10).map(f64::from).collect();
+        assert_eq!(
+            nearest_rank_percentiles(values, [10.0, 50.0, 90.0]),
+            [1.0, 5.0, 9.0]
+        );
+    }
+
+    #[test]
+    fn m3u_entries_match_by_path_then_unique_name() {
+        let track = |id: u32, path: &str| {
+            TrackBuilder::new(&id.to_string())
+                .id(id)
+                .absolute_path(path)
+                .build()
+        };
+        let tracks = [
+            track(1, "/bms/a/song.bms"),
+            track(2, "/bms/b/song.bms"),
+            track(3, "/bms/c/unique.bme"),
+            track(4, "/bms/d/stem.bms"),
+        ];
+
+        // 路径完全一致时优先，即使文件名不唯一
+        assert_eq!(
+            match_m3u_entry(&tracks, Path::new("/bms/b/song.bms")),
+            Some(2)
+        );
+        // 文件名唯一
+        assert_eq!(
+            match_m3u_entry(&tracks, Path::new("/old/unique.bme")),
+            Some(3)
+        );
+        // 扩展名不同但文件主名唯一
+        assert_eq!(
+            match_m3u_entry(&tracks, Path::new("/old/stem.bme")),
+            Some(4)
+        );
+        // 文件名有多个候选时不匹配
+        assert_eq!(match_m3u_entry(&tracks, Path::new("/old/song.bms")), None);
+        assert_eq!(
+            match_m3u_entry(&tracks, Path::new("/old/missing.bms")),
+            None
+        );
+    }
+
+    #[test]
+    fn placeholder_and_empty_fields_are_uncategorized() {
+        // 谱面有标题与艺术家但缺少 #GENRE
+        let mut track = track_from_bytes(
+            Path::new("/bms/pack/song.bms"),
+            b"#TITLE Song\n#ARTIST Someone\n#BPM 120\n#00111:01\n",
+            0,
+        )
+        .unwrap();
+        assert_eq!(track.metadata_source, MetadataSource::Parsed);
+        assert_eq!(track.genre, "Unknown Genre");
+        assert!(!is_uncategorized(&track, "title"));
+        assert!(!is_uncategorized(&track, "artist"));
+        assert!(is_uncategorized(&track, "genre"));
+        assert!(!is_uncategorized(&track, "bpm"));
+
+        track.title = "Unknown".to_string();
+        track.artist = "  ".to_string();
+        assert!(is_uncategorized(&track, "title"));
+        assert!(is_uncategorized(&track, "artist"));
+
+        // 只有用户修改过的字段视为已整理，即使修改后的值与占位值相同
+        apply_metadata_edit(
+            &mut track,
+            TrackMetadataEdit {
+                title: None,
+                artist: Some("Unknown Artist".to_string()),
+                genre: None,
+            },
+        );
+        assert_eq!(track.user_edited_fields, ["artist"]);
+        assert!(is_uncategorized(&track, "title"));
+        assert!(!is_uncategorized(&track, "artist"));
+        assert!(is_uncategorized(&track, "genre"));
+    }
+
+    fn summary_fixture(id: u32) -> Track {
+        TrackBuilder::new(&format!("{:064x}", id))
+            .id(id)
+            .title("Title")
+            .artist("Artist")
+            .genre("Genre")
+            .bpm(150.0)
+            .bpm_min(120.0)
+            .bpm_max(180.0)
+            .play_level(12)
+            .note_count(1500)
+            .build()
+    }
+
+    #[test]
+    fn track_summary_copies_track_values() {
+        let track = summary_fixture(7);
+        let summary = serde_json::to_value(TrackSummary::from(&track)).unwrap();
+        assert_eq!(
+            summary,
+            serde_json::json!({
+                "id": 7,
+                "title": "Title",
+                "artist": "Artist",
+                "genre": "Genre",
+                "bpm_min": 120.0,
+                "bpm_max": 180.0,
+                "play_level": 12,
+                "note_count": 1500,
+                "sha256": track.sha256,
+            })
+        );
+    }
+
+    #[test]
+    fn track_summaries_serialize_at_most_half_the_size() {
+        let tracks: Vec<Track> = (0..10_000).map(summary_fixture).collect();
+        let summaries: Vec<TrackSummary> = tracks.iter().map(TrackSummary::from).collect();
+
+        let full = serde_json::to_vec(&tracks).unwrap().len();
+        let summary = serde_json::to_vec(&summaries).unwrap().len();
+        assert!(full >= summary * 2, "{} vs {} bytes", full, summary);
+    }
+
+    // 基准测试：100,000 个曲目时，get_tracks 在原先按目录分开的 HashMap<String, Vec<Track>>
+    // 与现在的扁平曲目表上的用时（分别取出单个大目录与逐个取出 1,000 个目录）。
+    // 两种布局下 get_tracks 都是复制一段连续的曲目，因此不要求加速；扁平表的收益在于
+    // get_tracks_flat 等跨目录的查询只需遍历一次。
+    // 运行方式：cargo test --release get_tracks_storage_benchmark -- --ignored --nocapture
+    #[tokio::test]
+    #[ignore]
+    async fn get_tracks_storage_benchmark() {
+        const TRACKS: u32 = 100_000;
+        for dirs in [1, 1_000] {
+            let tracks_per_dir = TRACKS / dirs;
+            let state = AppState::default();
+            let directories: Mutex<HashMap<String, Vec<Track>>> = Mutex::default();
+            for dir in 0..dirs {
+                let tracks: Vec<Track> = (0..tracks_per_dir)
+                    .map(|i| {
+                        TrackBuilder::new(&format!("{}-{}", dir, i))
+                            .title("Title")
+                            .build()
+                    })
+                    .collect();
+                let name = format!("dir{}", dir);
+                directories
+                    .lock()
+                    .await
+                    .insert(name.clone(), tracks.clone());
+                add_tracks(&state, &name, tracks).await;
+            }
+            let names: Vec<String> = (0..dirs).map(|dir| format!("dir{}", dir)).collect();
+
+            // 原先的 get_tracks
+            let start = Instant::now();
+            let mut nested = 0;
+            for name in &names {
+                let dirs = directories.lock().await;
+                nested += dirs.get(name).cloned().unwrap().len();
+            }
+            let nested_elapsed = start.elapsed();
+
+            let start = Instant::now();
+            let mut flat = 0;
+            for name in &names {
+                flat += directory_tracks(&state, name).await.unwrap().len();
+            }
+            let flat_elapsed = start.elapsed();
+
+            assert_eq!((nested, flat), (TRACKS as usize, TRACKS as usize));
+            println!(
+                "{} directories: nested {:?}, flat {:?}",
+                dirs, nested_elapsed, flat_elapsed
+            );
+        }
+    }
+
+    #[test]
+    fn distinct_names_group_by_normalized_name() {
+        // NFD 与 NFC 写法的「ポ」视为同一名称
+        let nfc = "Pop\u{30DD}";
+        let nfd = "Pop\u{30DB}\u{309A}";
+        let names = [nfc, nfc, nfd, "pop\u{30DD} ", "Other"];
+        assert_eq!(
+            collect_distinct_names(names.into_iter()),
+            vec![("Other".to_string(), 1), (nfc.to_string(), 4)]
+        );
+    }
+
+    #[test]
+    fn dedup_decision_keeps_newer_duplicates_only_when_overwriting() {
+        let track = |sha256: &str, path: &str, mtime: u64| {
+            let mut track = TrackBuilder::new(sha256).absolute_path(path).build();
+            track.mtime = mtime;
+            track
+        };
+        let batch = || {
+            vec![
+                track("a", "/new/a1.bms", 1),
+                track("a", "/new/a2.bms", 5),
+                track("b", "/new/b.bms", 10),
+                track("c", "/new/c.bms", 1),
+            ]
+        };
+        // 已存在的 b 修改时间为 3，c 为 3
+        let newest_existing = |sha256: &str| (sha256 != "a").then_some(3);
+
+        let decision = decide_dedup(batch(), DedupStrategy::SkipDuplicates, newest_existing);
+        let paths = |tracks: &[Track]| {
+            tracks
+                .iter()
+                .map(|track| track.absolute_path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paths(&decision.kept), ["/new/a1.bms"]);
+        assert_eq!(
+            paths(&decision.skipped),
+            ["/new/a2.bms", "/new/b.bms", "/new/c.bms"]
+        );
+        assert!(decision.replaced.is_empty());
+
+        let decision = decide_dedup(batch(), DedupStrategy::OverwriteWithNewer, newest_existing);
+        assert_eq!(paths(&decision.kept), ["/new/a2.bms", "/new/b.bms"]);
+        assert_eq!(paths(&decision.skipped), ["/new/a1.bms", "/new/c.bms"]);
+        assert_eq!(decision.replaced, ["b"]);
+
+        let decision = decide_dedup(batch(), DedupStrategy::AllowDuplicates, newest_existing);
+        assert_eq!(decision.kept.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn dry_run_dedup_matches_replace_directory() {
+        let track = |sha256: &str, path: &str, mtime: u64| {
+            let mut track = TrackBuilder::new(sha256).absolute_path(path).build();
+            track.mtime = mtime;
+            track
+        };
+        let state = AppState::default();
+        add_tracks(
+            &state,
+            "old",
+            vec![track("a", "/old/a.bms", 3), track("b", "/old/b.bms", 3)],
+        )
+        .await;
+        add_tracks(&state, "pack", vec![track("c", "/pack/c.bms", 3)]).await;
+        let batch = vec![
+            track("c", "/pack/c.bms", 3),
+            track("b", "/pack/b.bms", 9),
+            track("a", "/pack/a.bms", 1),
+        ];
+
+        let (kept, skipped) = state.lock_library().await.preview_replace_directory(
+            "pack",
+            batch.clone(),
+            DedupStrategy::OverwriteWithNewer,
+        );
+        let conflicts = state
+            .replace_directory("pack".to_string(), batch, DedupStrategy::OverwriteWithNewer)
+            .await;
+
+        let library = state.lock_library().await;
+        let imported: Vec<PathBuf> = library.tracks[library.directory_index["pack"].clone()]
+            .iter()
+            .map(|track| track.absolute_path.clone())
+            .collect();
+        let kept: Vec<PathBuf> = kept.into_iter().map(|track| track.absolute_path).collect();
+        assert_eq!(imported, kept);
+        assert_eq!(
+            imported,
+            [PathBuf::from("/pack/b.bms"), PathBuf::from("/pack/c.bms")]
+        );
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].skipped_path, skipped[0].absolute_path);
+        // 较新的 b 替换了 old 目录中的同哈希曲目
+        assert_eq!(library.directory_index["old"].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sidecar_edits_stay_categorized_after_reimport() {
+        let dir = TempDir::new();
+        let content = b"#TITLE Song\n#ARTIST Someone\n#BPM 120\n#00111:01\n";
+        let path = dir.write("pack/song.bms", content);
+        let mut track = process_single_file(&path).await.unwrap();
+        apply_metadata_edit(
+            &mut track,
+            TrackMetadataEdit {
+                title: None,
+                artist: None,
+                genre: Some("Trance".to_string()),
+            },
+        );
+        sidecar::write_sidecar(&track).await.unwrap();
+
+        // 重新导入时使用 sidecar 中的元数据，其中记录了被修改的字段
+        let reimported = process_single_file(&path).await.unwrap();
+        assert_eq!(reimported.genre, "Trance");
+        assert_eq!(reimported.user_edited_fields, ["genre"]);
+        assert!(!is_uncategorized(&reimported, "genre"));
+    }
+
+    #[tokio::test]
+    async fn file_list_imports_every_readable_chart() {
+        let dir = TempDir::new();
+        let mut paths: Vec<PathBuf> = (0..5)
+            .map(|i| {
+                let content = format!("#TITLE Song {}\n#ARTIST A\n#BPM 120\n#00111:01\n", i);
+                dir.write(&format!("dir{}/song{}.bms", i, i), content.as_bytes())
+            })
+            .collect();
+        let broken = dir.write("broken/song.bmson", b"not json");
+        let missing = dir.path().join("missing/song.bms");
+        paths.insert(2, broken.clone());
+        paths.push(missing.clone());
+
+        let state = AppState::default();
+        let mut reported = Vec::new();
+        let (tracks, failed_paths) = process_file_list(&state, &paths, |progress| {
+            reported.push(progress);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(failed_paths, [broken, missing]);
+        assert_eq!(reported.len(), paths.len());
+        assert_eq!(reported.last(), Some(&100.0));
+
+        add_tracks(&state, "selection", tracks).await;
+        let library = state.lock_library().await;
+        let mut titles: Vec<&str> = library.tracks[library.directory_index["selection"].clone()]
+            .iter()
+            .map(|track| track.title.as_str())
+            .collect();
+        titles.sort_unstable();
+        assert_eq!(titles, ["Song 0", "Song 1", "Song 2", "Song 3", "Song 4"]);
+    }
+
+    #[tokio::test]
+    async fn tracks_from_files_skips_and_counts_failures() {
+        let dir = TempDir::new();
+        for i in 0..3 {
+            let content = format!("#TITLE Song {}\n#ARTIST A\n#BPM 120\n#00111:01\n", i);
+            dir.write(&format!("pack/song{}.bms", i), content.as_bytes());
+        }
+        dir.write("pack/broken.bmson", b"not json");
+        let files = bms_scan::scan_simple(dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(files.len(), 4);
+
+        let (tracks, failed) = tracks_from_files(files).await;
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(failed, 1);
+    }
+
+    #[test]
+    fn invalid_directory_names_carry_context() {
+        assert_eq!(directory_name(Path::new("/bms/pack")).unwrap(), "pack");
+        let error = directory_name(Path::new("/")).unwrap_err();
+        assert!(matches!(error, CabinetError::InvalidDirectoryName { .. }));
+        assert_eq!(
+            error.context("while adding directory").to_string(),
+            "while adding directory: Invalid directory name: /"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn id_index_stays_consistent_under_concurrent_changes() {
+        let state = Arc::new(AppState::default());
+        let inserts: Vec<_> = (0..1000)
+            .map(|i| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let mut track = TrackBuilder::new(&i.to_string()).build();
+                    track.id = state.next_id.fetch_add(1, Ordering::Relaxed);
+                    let directory = format!("dir{}", i % 10);
+                    state
+                        .lock_library()
+                        .await
+                        .insert_track(&directory, track)
+                        .unwrap();
+                })
+            })
+            .collect();
+        for insert in inserts {
+            insert.await.unwrap();
+        }
+        assert!(state.lock_library().await.is_id_index_consistent());
+        assert_eq!(state.tracks.lock().await.len(), 1000);
+
+        let deletes: Vec<_> = (0..1000)
+            .step_by(2)
+            .map(|id| {
+                let state = state.clone();
+                tokio::spawn(async move { state.lock_library().await.remove_track(id).is_some() })
+            })
+            .collect();
+        for delete in deletes {
+            assert!(delete.await.unwrap());
+        }
+
+        let library = state.lock_library().await;
+        assert!(library.is_id_index_consistent());
+        assert_eq!(library.tracks.len(), 500);
+        assert!((0..1000).all(|id| library.id_index.contains_key(&id) == (id % 2 == 1)));
+    }
+
+    fn title_edit(title: &str) -> TrackMetadataEdit {
+        TrackMetadataEdit {
+            title: Some(title.to_string()),
+            artist: None,
+            genre: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn metadata_edits_are_rejected_while_recomputing() {
+        let state = AppState::default();
+        let ids = add_tracks(&state, "pack", vec![TrackBuilder::new("a").build()]).await;
+
+        let (state, ids) = (&state, &ids);
+        let (report, updated) = recompute_with_lock(state, "pack", |tracks| async move {
+            let result = edit_track_metadata(state, ids[0], title_edit("Edited")).await;
+            assert!(matches!(result, Err(CabinetError::TrackLocked { id }) if id == ids[0]));
+
+            let mut recomputed = tracks[0].clone();
+            recomputed.title = "Recomputed".to_string();
+            let report = RecomputeReport {
+                updated: 1,
+                unchanged: 0,
+                failed: 0,
+            };
+            Ok((report, vec![recomputed]))
+        })
+        .await
+        .unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(updated[0].title, "Recomputed");
+
+        // 结束后解除锁定
+        assert!(state.locked_track_ids.lock().await.is_empty());
+        let edited = edit_track_metadata(state, ids[0], title_edit("Edited"))
+            .await
+            .unwrap();
+        assert_eq!(edited.title, "Edited");
+    }
+
+    #[tokio::test]
+    async fn recompute_failure_releases_locks() {
+        let state = AppState::default();
+        let ids = add_tracks(&state, "pack", vec![TrackBuilder::new("a").build()]).await;
+
+        let result = recompute_with_lock(&state, "pack", |_| async {
+            assert!(state.locked_track_ids.lock().await.contains(&ids[0]));
+            Err("mocked failure".to_string())
+        })
+        .await;
+        assert_eq!(result.err().as_deref(), Some("mocked failure"));
+
+        assert!(state.locked_track_ids.lock().await.is_empty());
+        assert!(edit_track_metadata(&state, ids[0], title_edit("Edited"))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn invalid_config_is_rejected_before_saving() {
+        let state = AppState::default();
+        let saved = std::sync::Mutex::new(Vec::new());
+        let save = |config: ScanConfig| {
+            saved.lock().unwrap().push(config);
+            async { Ok(()) }
+        };
+
+        let invalid = ScanConfig {
+            max_depth: Some(0),
+            id_start: Some(100),
+            ..Default::default()
+        };
+        let error = apply_config(&state, invalid, save).await.unwrap_err();
+        assert!(error.contains("max_depth"), "{}", error);
+        assert!(saved.lock().unwrap().is_empty());
+        assert_eq!(state.config.lock().await.max_depth, None);
+        assert_eq!(state.next_id.load(Ordering::Relaxed), 0);
+
+        let valid = ScanConfig {
+            max_depth: Some(3),
+            ..Default::default()
+        };
+        apply_config(&state, valid, save).await.unwrap();
+        assert_eq!(saved.lock().unwrap().len(), 1);
+        assert_eq!(state.config.lock().await.max_depth, Some(3));
+    }
+
+    #[tokio::test]
+    async fn migrations_run_from_zero_to_latest_once() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "pack/song.bms",
+            b"#TITLE Song\n#BPM 120\n#MATERIAL pack\n#00111:0101\n#00112:01\n",
+        );
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![TrackBuilder::new("a").absolute_path(path).build()],
+        )
+        .await;
+
+        let report = migrate_tracks(&state, migrations::LATEST_SCHEMA_VERSION)
+            .await
+            .unwrap();
+        assert_eq!(report.migrated_tracks, 1);
+        assert_eq!(report.schema_version_before, 0);
+        assert_eq!(
+            report.schema_version_after,
+            migrations::LATEST_SCHEMA_VERSION
+        );
+        assert_eq!(
+            state.schema_version.load(Ordering::Acquire),
+            migrations::LATEST_SCHEMA_VERSION
+        );
+        let migrated = {
+            let library = state.lock_library().await;
+            library.tracks[library.id_index[&ids[0]]].clone()
+        };
+        assert_eq!(migrated.note_count, 3);
+        assert_eq!(migrated.material.as_deref(), Some("pack"));
+        assert!(migrated.chart_fingerprint.is_some());
+        assert!(migrated.peak_nps > 0.0);
+
+        // 再次执行不做任何事
+        let report = migrate_tracks(&state, migrations::LATEST_SCHEMA_VERSION)
+            .await
+            .unwrap();
+        assert_eq!(report.migrated_tracks, 0);
+        assert_eq!(
+            report.schema_version_before,
+            migrations::LATEST_SCHEMA_VERSION
+        );
+        assert!(migrate_tracks(&state, 0).await.is_err());
+    }
+
+    async fn artist_search(state: &AppState, artist: &str) -> Vec<u32> {
+        tracks_by_artist(state, artist, false)
+            .await
+            .iter()
+            .map(|track| track.id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn trashed_tracks_leave_searches_until_restored() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").artist("Alpha").build(),
+                TrackBuilder::new("b").artist("Alpha").build(),
+            ],
+        )
+        .await;
+
+        move_to_trash(&state, ids[0]).await.unwrap();
+        assert_eq!(artist_search(&state, "alpha").await, [ids[1]]);
+        assert!(!state.lock_library().await.id_index.contains_key(&ids[0]));
+        assert_eq!(state.trash.lock().await[0].directory, "pack");
+        assert!(move_to_trash(&state, ids[0]).await.is_err());
+
+        let restored = restore_trashed(&state, ids[0]).await.unwrap();
+        assert_eq!(restored.id, ids[0]);
+        let mut found = artist_search(&state, "alpha").await;
+        found.sort_unstable();
+        assert_eq!(found, ids);
+        assert!(state.trash.lock().await.is_empty());
+        assert!(restore_trashed(&state, ids[0]).await.is_err());
+        assert!(state
+            .lock_library()
+            .await
+            .quick_consistency_issues()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn restoring_recreates_a_removed_directory() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").build(),
+                TrackBuilder::new("b").build(),
+            ],
+        )
+        .await;
+        move_to_trash(&state, ids[0]).await.unwrap();
+        {
+            let mut library = state.lock_library().await;
+            let range = library.directory_index.remove("pack").unwrap();
+            library.remove_range(range);
+        }
+
+        restore_trashed(&state, ids[0]).await.unwrap();
+        let tracks = directory_tracks(&state, "pack").await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].id, ids[0]);
+        assert!(state
+            .lock_library()
+            .await
+            .quick_consistency_issues()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn consistency_check_repairs_an_unindexed_track() {
+        let state = AppState::default();
+        assert!(state.config.lock().await.auto_consistency_check);
+        add_tracks(&state, "pack", vec![TrackBuilder::new("a").build()]).await;
+        assert!(repair_inconsistencies(&state).await.is_empty());
+
+        state
+            .tracks
+            .lock()
+            .await
+            .push(TrackBuilder::new("b").id(9999).build());
+        let events = repair_inconsistencies(&state).await;
+        let types: Vec<&str> = events.iter().map(LibraryEvent::event_type).collect();
+        assert_eq!(types, ["ConsistencyWarning", "ReindexCompleted"]);
+        match &events[0] {
+            LibraryEvent::ConsistencyWarning(description) => {
+                assert!(description.contains("sha256_index"), "{description}")
+            }
+            _ => unreachable!(),
+        }
+
+        let library = state.lock_library().await;
+        assert!(library.quick_consistency_issues().is_empty());
+        assert!(library.sha256_index.contains_key("b"));
+        drop(library);
+        assert!(repair_inconsistencies(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn bulk_tagging_counts_only_newly_tagged_tracks() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").build(),
+                TrackBuilder::new("b").build(),
+                TrackBuilder::new("c").build(),
+            ],
+        )
+        .await;
+
+        assert_eq!(tag_tracks(&state, 1, &ids[..2]).await.unwrap().len(), 2);
+        assert_eq!(tag_tracks(&state, 1, &ids).await.unwrap(), [ids[2]]);
+        assert!(tag_tracks(&state, 1, &ids).await.unwrap().is_empty());
+        assert_eq!(tag_tracks(&state, 2, &ids).await.unwrap().len(), 3);
+
+        // 任一曲目不存在时不做任何修改
+        assert!(tag_tracks(&state, 3, &[ids[0], 9999]).await.is_err());
+        assert!(!state.track_tags.lock().await.contains_key(&3));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn bulk_tagging_is_never_partially_visible() {
+        let state = Arc::new(AppState::default());
+        let tracks = (0..1000)
+            .map(|i| TrackBuilder::new(&format!("{i}")).build())
+            .collect();
+        let ids = add_tracks(&state, "pack", tracks).await;
+
+        let reader = tokio::spawn({
+            let state = state.clone();
+            async move {
+                let mut seen = HashSet::new();
+                for _ in 0..1000 {
+                    let tags = state.track_tags.lock().await;
+                    seen.insert(tags.get(&1).map_or(0, HashSet::len));
+                    drop(tags);
+                    tokio::task::yield_now().await;
+                }
+                seen
+            }
+        });
+        tag_tracks(&state, 1, &ids).await.unwrap();
+        let seen = reader.await.unwrap();
+        assert!(seen.iter().all(|&len| len == 0 || len == 1000), "{seen:?}");
+    }
+
+    #[test]
+    fn fuzzy_search_sorts_by_distance_within_limit() {
+        let tracks = [
+            TrackBuilder::new("a").artist("DJ Smoothy").build(),
+            TrackBuilder::new("b").artist("dj smoothy").build(),
+            TrackBuilder::new("c").artist("DJ Smoothie").build(),
+            TrackBuilder::new("d").artist("DJ Smothy").build(),
+            TrackBuilder::new("e").artist("ABC").build(),
+        ];
+        fn artist(track: &Track) -> &String {
+            &track.artist
+        }
+        let found: Vec<(String, u32)> = fuzzy_search(tracks.iter(), "DJ Smoothy", 1, artist)
+            .into_iter()
+            .map(|found| (found.track.sha256, found.edit_distance))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("a".to_string(), 0),
+                ("b".to_string(), 0),
+                ("d".to_string(), 1)
+            ]
+        );
+
+        assert!(fuzzy_search(tracks.iter(), "ZZZ", 2, artist).is_empty());
+        let abc = fuzzy_search(tracks.iter(), "ABD", 1, artist);
+        assert_eq!(abc.len(), 1);
+        assert_eq!(abc[0].track.artist, "ABC");
+    }
+
+    #[tokio::test]
+    async fn artist_lookup_ignores_case_and_normalization() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").artist("DJ Example").build(),
+                TrackBuilder::new("b").artist("dj example").build(),
+                // NFC 与 NFD 形式的「ガ」
+                TrackBuilder::new("c").artist("\u{30AC}ッツ").build(),
+                TrackBuilder::new("d")
+                    .artist("\u{30AB}\u{3099}ッツ")
+                    .build(),
+                TrackBuilder::new("e").artist("Other").build(),
+            ],
+        )
+        .await;
+        let found = |tracks: Vec<Track>| -> Vec<u32> {
+            let mut found: Vec<u32> = tracks.iter().map(|track| track.id).collect();
+            found.sort_unstable();
+            found
+        };
+
+        assert_eq!(
+            found(tracks_by_artist(&state, "DJ EXAMPLE", false).await),
+            [ids[0], ids[1]]
+        );
+        assert_eq!(
+            found(tracks_by_artist(&state, "DJ Example", true).await),
+            [ids[0]]
+        );
+        assert_eq!(
+            found(tracks_by_artist(&state, "\u{30AB}\u{3099}ッツ", false).await),
+            [ids[2], ids[3]]
+        );
+        assert_eq!(
+            found(tracks_by_artist(&state, "\u{30AC}ッツ", true).await),
+            [ids[2]]
+        );
+        assert!(tracks_by_artist(&state, "nobody", false).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn genre_index_follows_additions_and_removals() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").genre("Trance").play_level(4).build(),
+                TrackBuilder::new("b").genre("TRANCE").play_level(8).build(),
+                TrackBuilder::new("c").genre("Jazz").play_level(1).build(),
+            ],
+        )
+        .await;
+
+        assert_eq!(tracks_by_genre(&state, "trance").await.len(), 2);
+        let stats: Vec<(String, u32, f64)> = genre_stats(&state)
+            .await
+            .into_iter()
+            .map(|stat| (stat.genre, stat.track_count, stat.avg_play_level))
+            .collect();
+        assert_eq!(
+            stats,
+            [("Trance".to_string(), 2, 6.0), ("Jazz".to_string(), 1, 1.0)]
+        );
+
+        state.lock_library().await.remove_track(ids[2]).unwrap();
+        assert!(tracks_by_genre(&state, "Jazz").await.is_empty());
+        assert_eq!(genre_stats(&state).await.len(), 1);
+        assert!(state
+            .lock_library()
+            .await
+            .health_report()
+            .details
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn moving_tracks_keeps_indexes_consistent() {
+        let state = AppState::default();
+        let a = add_tracks(
+            &state,
+            "a",
+            vec![
+                TrackBuilder::new("1").artist("X").build(),
+                TrackBuilder::new("2").artist("Y").build(),
+                TrackBuilder::new("3").genre("Z").build(),
+            ],
+        )
+        .await;
+        let b = add_tracks(&state, "b", vec![TrackBuilder::new("4").build()]).await;
+        add_tracks(&state, "c", vec![TrackBuilder::new("5").build()]).await;
+
+        let mut library = state.lock_library().await;
+        library.move_track(a[1], "b").unwrap();
+        assert!(library.move_track(a[0], "missing").is_err());
+        assert!(library.health_report().details.is_empty());
+        assert_eq!(library.directory_of(a[1]).as_deref(), Some("b"));
+        drop(library);
+        let ids = |tracks: Vec<Track>| -> Vec<u32> { tracks.iter().map(|t| t.id).collect() };
+        assert_eq!(
+            ids(directory_tracks(&state, "a").await.unwrap()),
+            [a[0], a[2]]
+        );
+        let mut in_b = ids(directory_tracks(&state, "b").await.unwrap());
+        in_b.sort_unstable();
+        assert_eq!(in_b, [a[1], b[0]]);
+        assert_eq!(artist_search(&state, "y").await, [a[1]]);
+
+        assert!(move_directory_tracks(&state, "a", "missing").await.is_err());
+        assert_eq!(move_directory_tracks(&state, "a", "c").await, Ok(2));
+        assert!(directory_tracks(&state, "a").await.unwrap().is_empty());
+        assert_eq!(directory_tracks(&state, "c").await.unwrap().len(), 3);
+        assert_eq!(tracks_by_genre(&state, "z").await[0].id, a[2]);
+        assert!(state
+            .lock_library()
+            .await
+            .health_report()
+            .details
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn import_applies_each_dedup_strategy() {
+        let track = |sha256: &str, path: &str, mtime: u64| {
+            let mut track = TrackBuilder::new(sha256).absolute_path(path).build();
+            track.mtime = mtime;
+            track
+        };
+        let import = |strategy| async move {
+            let state = AppState::default();
+            add_tracks(
+                &state,
+                "old",
+                vec![track("a", "/old/a.bms", 5), track("b", "/old/b.bms", 5)],
+            )
+            .await;
+            let conflicts = state
+                .replace_directory(
+                    "new".to_string(),
+                    vec![
+                        track("a", "/new/a.bms", 10),
+                        track("b", "/new/b.bms", 1),
+                        track("c", "/new/c.bms", 1),
+                    ],
+                    strategy,
+                )
+                .await;
+            let paths = |tracks: Vec<Track>| -> Vec<String> {
+                tracks
+                    .iter()
+                    .map(|track| track.absolute_path.to_string_lossy().into_owned())
+                    .collect()
+            };
+            let old = paths(directory_tracks(&state, "old").await.unwrap());
+            let new = paths(directory_tracks(&state, "new").await.unwrap());
+            assert!(state
+                .lock_library()
+                .await
+                .health_report()
+                .details
+                .is_empty());
+            let skipped: Vec<PathBuf> = conflicts.into_iter().map(|c| c.skipped_path).collect();
+            (old, new, skipped)
+        };
+
+        let (old, new, skipped) = import(DedupStrategy::AllowDuplicates).await;
+        assert_eq!(old, ["/old/a.bms", "/old/b.bms"]);
+        assert_eq!(new, ["/new/a.bms", "/new/b.bms", "/new/c.bms"]);
+        assert!(skipped.is_empty());
+
+        let (old, new, skipped) = import(DedupStrategy::SkipDuplicates).await;
+        assert_eq!(old, ["/old/a.bms", "/old/b.bms"]);
+        assert_eq!(new, ["/new/c.bms"]);
+        assert_eq!(
+            skipped,
+            [PathBuf::from("/new/a.bms"), PathBuf::from("/new/b.bms")]
+        );
+
+        let (old, new, skipped) = import(DedupStrategy::OverwriteWithNewer).await;
+        assert_eq!(old, ["/old/b.bms"]);
+        assert_eq!(new, ["/new/a.bms", "/new/c.bms"]);
+        assert_eq!(skipped, [PathBuf::from("/new/b.bms")]);
+    }
+
+    #[tokio::test]
+    async fn resolving_a_conflict_keeps_only_the_new_track() {
+        let state = AppState::default();
+        add_tracks(
+            &state,
+            "old",
+            vec![TrackBuilder::new("a").absolute_path("/old/a.bms").build()],
+        )
+        .await;
+        let conflicts = state
+            .replace_directory(
+                "new".to_string(),
+                vec![
+                    TrackBuilder::new("a").absolute_path("/new/a.bms").build(),
+                    TrackBuilder::new("b").absolute_path("/new/b.bms").build(),
+                ],
+                DedupStrategy::SkipDuplicates,
+            )
+            .await;
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].existing_directory, "old");
+        state.import_conflicts.lock().await.extend(conflicts);
+
+        keep_conflicting_track(&state, "a").await.unwrap();
+        let library = state.lock_library().await;
+        let ids = &library.sha256_index["a"];
+        assert_eq!(ids.len(), 1);
+        assert_eq!(library.directory_of(ids[0]).as_deref(), Some("new"));
+        assert_eq!(
+            library.tracks[library.id_index[&ids[0]]].absolute_path,
+            PathBuf::from("/new/a.bms")
+        );
+        assert!(library.health_report().details.is_empty());
+        drop(library);
+        assert!(directory_tracks(&state, "old").await.unwrap().is_empty());
+        assert!(state.import_conflicts.lock().await.is_empty());
+        assert!(keep_conflicting_track(&state, "a").await.is_err());
+    }
+
+    #[test]
+    fn missing_headers_are_inferred_from_the_folder() {
+        let content = b"#BPM 120\n#00111:01\n";
+        let track = track_from_bytes(
+            Path::new("/bms/[Trance] DJ Example - Song Name/_7.bms"),
+            content,
+            0,
+        )
+        .unwrap();
+        assert_eq!(track.metadata_source, MetadataSource::InferredFromPath);
+        assert_eq!(
+            (
+                track.artist.as_str(),
+                track.title.as_str(),
+                track.genre.as_str()
+            ),
+            ("DJ Example", "Song Name", "Trance")
+        );
+
+        let parsed = track_from_bytes(
+            Path::new("/bms/DJ Example - Song Name/_7.bms"),
+            b"#TITLE Real\n#ARTIST Someone\n#00111:01\n",
+            0,
+        )
+        .unwrap();
+        assert_eq!(parsed.metadata_source, MetadataSource::Parsed);
+        assert_eq!(parsed.title, "Real");
+    }
+
+    #[tokio::test]
+    async fn charts_by_one_artist_in_one_folder_form_an_album() {
+        let chart = |path: &str, artist: &str| {
+            let content = format!("#TITLE {path}\n#ARTIST {artist}\n#00111:01\n");
+            track_from_bytes(Path::new(path), content.as_bytes(), 0).unwrap()
+        };
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                chart("/bms/song/_5.bms", "DJ Example"),
+                chart("/bms/song/_7.bms", "dj example"),
+                chart("/bms/song/_other.bms", "Someone"),
+                chart("/bms/other/_7.bms", "DJ Example"),
+            ],
+        )
+        .await;
+
+        let albums = directory_albums(&state, "pack").await.unwrap();
+        let groups: Vec<(&str, usize)> = albums
+            .iter()
+            .map(|album| (album.folder_name.as_str(), album.tracks.len()))
+            .collect();
+        // 曲目按路径排序后导入
+        assert_eq!(groups, [("other", 1), ("song", 2), ("song", 1)]);
+        assert_eq!(albums[1].tracks, [ids[1], ids[2]]);
+        assert_eq!(
+            albums[1].album_id,
+            album_id_for("DJ EXAMPLE", Path::new("/bms/song/x.bms"))
+        );
+        assert!(directory_albums(&state, "missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn library_report_fills_every_field() {
+        let state = AppState::default();
+        let tracks = (0..20u32)
+            .map(|i| {
+                let mut track = TrackBuilder::new(&i.to_string())
+                    .artist(&format!("Artist {}", i % 12))
+                    .genre(&format!("Genre {}", i % 3))
+                    .bpm(100.0 + (i * 7 % 20) as f64 * 5.0)
+                    .note_count(500 + i * 37 % 20 * 50)
+                    .key_mode(if i % 4 == 0 {
+                        KeyMode::Key14
+                    } else {
+                        KeyMode::Key7
+                    })
+                    .build();
+                track.file_size = 1000;
+                track
+            })
+            .collect();
+        add_tracks(&state, "pack", tracks).await;
+        for elapsed_seconds in [1.5, 2.0] {
+            state.scan_history.lock().await.push(ScanSummary {
+                root_path: "/bms".into(),
+                timestamp: 0,
+                total_files_found: 20,
+                total_bytes_read: 0,
+                elapsed_seconds,
+                errors_count: 0,
+                storage_type: storage::StorageType::SSD,
+                worker_count: 1,
+                peak_memory_kb: 0,
+                total_disk_reads_kb: 0,
+                cpu_time_ms: 0,
+            });
+        }
+
+        let report = library_report(&state).await;
+        assert_eq!(report.total_tracks, 20);
+        assert_eq!(report.unique_artists, 12);
+        assert_eq!(report.unique_genres, 3);
+        assert!(
+            report.bpm_percentiles.is_sorted(),
+            "{:?}",
+            report.bpm_percentiles
+        );
+        assert!(report.bpm_percentiles[0] < report.bpm_percentiles[4]);
+        assert!(report.note_count_percentiles.is_sorted());
+        assert!(report.note_count_percentiles[0] < report.note_count_percentiles[4]);
+        assert_eq!(report.key_mode_distribution["7K"], 15);
+        assert_eq!(report.key_mode_distribution["14K"], 5);
+        assert_eq!(report.chart_type_distribution.values().sum::<u32>(), 20);
+        assert_eq!(report.top_artists.len(), 10);
+        assert!(report.top_artists[..8].iter().all(|(_, count)| *count == 2));
+        assert_eq!(report.total_scan_time_secs, 3.5);
+        assert_eq!(report.total_library_size_bytes, 20_000);
+    }
+
+    #[tokio::test]
+    async fn packs_group_charts_by_top_level_folder() {
+        let dir = TempDir::new();
+        let mut tracks: Vec<Track> = [
+            "pack_a/song1/_7.bms",
+            "pack_a/song2/_7.bms",
+            "pack_b/_5.bms",
+            "pack_b/_7.bms",
+            "loose.bms",
+        ]
+        .iter()
+        .map(|name| {
+            let content = format!("#TITLE {name}\n#ARTIST A\n#00111:01\n");
+            let path = dir.write(name, content.as_bytes());
+            track_from_bytes(&path, content.as_bytes(), 0).unwrap()
+        })
+        .collect();
+        assign_pack_ids(&mut tracks, dir.path());
+        let state = AppState::default();
+        add_tracks(&state, "bms", tracks).await;
+        assert!(directory_packs(&state, "bms").await.is_err());
+        state
+            .directory_roots
+            .lock()
+            .await
+            .insert("bms".to_string(), dir.path().to_path_buf());
+
+        let packs = directory_packs(&state, "bms").await.unwrap();
+        let summary: Vec<(&str, u32)> = packs
+            .iter()
+            .map(|pack| (pack.name.as_str(), pack.track_count))
+            .collect();
+        let root_name = dir.path().file_name().unwrap().to_str().unwrap();
+        assert_eq!(summary.len(), 3);
+        assert!(summary.contains(&("pack_a", 2)));
+        assert!(summary.contains(&("pack_b", 2)));
+        assert!(summary.contains(&(root_name, 1)));
+
+        let pack_a = packs.iter().find(|pack| pack.name == "pack_a").unwrap();
+        assert_eq!(
+            pack_a.directory_path,
+            dir.path().join("pack_a").to_string_lossy()
+        );
+        let library = state.lock_library().await;
+        assert_eq!(library.tracks_by_ids(&pack_a.track_ids).len(), 2);
+        assert!(library
+            .tracks_by_ids(&pack_a.track_ids)
+            .iter()
+            .all(|track| track.pack_id == Some(pack_a.id)));
+    }
+
+    #[tokio::test]
+    async fn reset_leaves_an_empty_library() {
+        let state = AppState::default();
+        let a = add_tracks(&state, "a", vec![TrackBuilder::new("a").build()]).await;
+        let b = add_tracks(&state, "b", vec![TrackBuilder::new("b").build()]).await;
+        move_to_trash(&state, a[0]).await.unwrap();
+        tag_tracks(&state, 1, &b).await.unwrap();
+        *state.current_progress.lock().await = 0.5;
+
+        reset_app_state(&state).await;
+        assert!(state.directory_index.lock().await.is_empty());
+        let library = state.lock_library().await;
+        assert!(library.tracks.is_empty());
+        assert!(library.id_index.is_empty());
+        assert!(library.sha256_index.is_empty());
+        assert!(library.artist_index.is_empty());
+        assert!(library.genre_index.is_empty());
+        drop(library);
+        assert!(state.trash.lock().await.is_empty());
+        assert!(state.track_tags.lock().await.is_empty());
+        assert_eq!(*state.current_progress.lock().await, 0.0);
+        assert_eq!(state.next_id.load(Ordering::Relaxed), 0);
+
+        let ids = add_tracks(&state, "c", vec![TrackBuilder::new("c").build()]).await;
+        assert_eq!(ids, [0]);
+    }
+
+    #[tokio::test]
+    async fn snapshot_restores_the_exported_library() {
+        let state = AppState::default();
+        add_tracks(
+            &state,
+            "a",
+            vec![
+                TrackBuilder::new("1").title("One").artist("X").build(),
+                TrackBuilder::new("2").title("Two").bpm(180.0).build(),
+            ],
+        )
+        .await;
+        add_tracks(&state, "b", vec![TrackBuilder::new("3").genre("G").build()]).await;
+        let original = state.tracks.lock().await.clone();
+
+        let dir = TempDir::new();
+        let path = dir.path().join("snapshot.json");
+        snapshot::write_snapshot(&path, &build_state_snapshot(&state).await)
+            .await
+            .unwrap();
+        reset_app_state(&state).await;
+        assert!(state.tracks.lock().await.is_empty());
+
+        assert_eq!(restore_snapshot(&state, &path).await, Ok(3));
+        assert!(*state.tracks.lock().await == original);
+        assert!(state
+            .lock_library()
+            .await
+            .health_report()
+            .details
+            .is_empty());
+
+        // 再次恢复时按 SHA-256 覆盖而不是重复插入
+        assert_eq!(restore_snapshot(&state, &path).await, Ok(3));
+        assert!(*state.tracks.lock().await == original);
+    }
+
+    #[tokio::test]
+    async fn tracks_are_found_by_id_until_removed() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![TrackBuilder::new("a").title("Song").build()],
+        )
+        .await;
+        let stored = state.tracks.lock().await[0].clone();
+
+        // 第二次查找命中缓存
+        for _ in 0..2 {
+            assert!(track_by_id(&state, ids[0]).await == Some(stored.clone()));
+        }
+        assert!(state.id_index.lock().await.contains_key(&ids[0]));
+        assert!(track_by_id(&state, 9999).await.is_none());
+
+        state.lock_library().await.remove_track(ids[0]).unwrap();
+        assert!(track_by_id(&state, ids[0]).await.is_none());
+        assert!(!state.id_index.lock().await.contains_key(&ids[0]));
+    }
+
+    #[tokio::test]
+    async fn casing_normalization_makes_names_consistent() {
+        let fixture = || {
+            ["DJ example", "dj example", "DJ Example", "other ARTIST"]
+                .iter()
+                .enumerate()
+                .map(|(i, artist)| {
+                    TrackBuilder::new(&i.to_string())
+                        .artist(artist)
+                        .genre(if i % 2 == 0 { "TRANCE" } else { "trance" })
+                        .build()
+                })
+                .collect::<Vec<_>>()
+        };
+        let names = |tracks: &[Track]| -> (Vec<String>, Vec<String>) {
+            (
+                tracks.iter().map(|track| track.artist.clone()).collect(),
+                tracks.iter().map(|track| track.genre.clone()).collect(),
+            )
+        };
+
+        let state = AppState::default();
+        add_tracks(&state, "pack", fixture()).await;
+        assert_eq!(
+            normalize_casing(&state, NormalizationStrategy::PreserveFirst)
+                .await
+                .len(),
+            3
+        );
+        let (artists, genres) = names(&state.tracks.lock().await);
+        assert_eq!(
+            artists,
+            ["DJ example", "DJ example", "DJ example", "other ARTIST"]
+        );
+        assert!(genres.iter().all(|genre| genre == "TRANCE"));
+        assert_eq!(artist_search(&state, "dj EXAMPLE").await.len(), 3);
+        assert!(state
+            .lock_library()
+            .await
+            .health_report()
+            .details
+            .is_empty());
+
+        let state = AppState::default();
+        add_tracks(&state, "pack", fixture()).await;
+        assert_eq!(
+            normalize_casing(&state, NormalizationStrategy::TitleCase)
+                .await
+                .len(),
+            4
+        );
+        let (artists, genres) = names(&state.tracks.lock().await);
+        assert_eq!(
+            artists,
+            ["Dj Example", "Dj Example", "Dj Example", "Other Artist"]
+        );
+        assert!(genres.iter().all(|genre| genre == "Trance"));
+        assert!(normalize_casing(&state, NormalizationStrategy::TitleCase)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn recompute_reports_updated_unchanged_and_failed_tracks() {
+        let dir = TempDir::new();
+        let mut tracks = Vec::new();
+        for name in ["same.bms", "changed.bms", "deleted.bms"] {
+            let path = dir.write(name, b"#TITLE Song\n#ARTIST A\n#BPM 120\n#00111:01\n");
+            tracks.push(process_single_file(&path).await.unwrap());
+        }
+        let state = AppState::default();
+        let ids = add_tracks(&state, "pack", tracks).await;
+        dir.write(
+            "changed.bms",
+            b"#TITLE New\n#ARTIST A\n#BPM 150\n#00111:01\n",
+        );
+        std::fs::remove_file(dir.path().join("deleted.bms")).unwrap();
+
+        let mut progress = Vec::new();
+        let (report, updated) = recompute_with_lock(&state, "pack", |tracks| {
+            recompute_tracks(tracks, 2, |value| {
+                progress.push(value);
+                Ok(())
+            })
+        })
+        .await
+        .unwrap();
+        assert_eq!((report.updated, report.unchanged, report.failed), (1, 1, 1));
+        assert_eq!(progress.last(), Some(&100.0));
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].title, "New");
+
+        // 按路径排序后 changed.bms 在最前
+        let stored = track_by_id(&state, ids[0]).await.unwrap();
+        assert_eq!((stored.title.as_str(), stored.bpm), ("New", 150.0));
+        assert_eq!(track_by_id(&state, ids[2]).await.unwrap().title, "Song");
+    }
+
+    #[tokio::test]
+    async fn health_report_detects_corrupted_indexes() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").artist("X").genre("G").build(),
+                TrackBuilder::new("b").artist("Y").genre("G").build(),
+            ],
+        )
+        .await;
+        let mut library = state.lock_library().await;
+        let report = library.health_report();
+        assert!(report.id_index_ok && report.sha256_index_ok);
+        assert!(report.artist_index_ok && report.genre_index_ok);
+        assert!(report.details.is_empty());
+
+        // 孤立的 id 索引条目与缺失的艺术家索引条目
+        library.id_index.insert(9999, 42);
+        library.artist_index.remove("y");
+        // 指向不存在曲目的流派索引条目
+        library.genre_index.get_mut("g").unwrap().push(8888);
+        let report = library.health_report();
+        assert!(!report.id_index_ok);
+        assert!(report.sha256_index_ok);
+        assert!(!report.artist_index_ok);
+        assert!(!report.genre_index_ok);
+        assert_eq!(report.orphan_count, 2);
+        assert_eq!(report.inconsistency_count, 1);
+        assert!(report
+            .details
+            .iter()
+            .any(|detail| detail.contains(&format!("track {} is not indexed", ids[1]))));
+
+        library.rebuild_indexes();
+        assert!(library.health_report().details.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tracks_imported_from_bytes_are_indexed() {
+        let state = AppState::default();
+        let content = b"#TITLE Song\n#ARTIST A\n#BPM 120\n#00111:01\n";
+        let track = insert_track_from_bytes(&state, "song.bms", "virtual", content)
+            .await
+            .unwrap();
+        assert_eq!(track.title, "Song");
+        assert_eq!(track.absolute_path, Path::new("virtual").join("song.bms"));
+        assert_eq!(track.sha256, hex_string(&Sha256::digest(content)));
+
+        assert!(track_by_id(&state, track.id).await == Some(track.clone()));
+        let library = state.lock_library().await;
+        assert_eq!(library.sha256_index[&track.sha256], [track.id]);
+        assert_eq!(library.directory_of(track.id).as_deref(), Some("virtual"));
+        drop(library);
+
+        assert!(
+            insert_track_from_bytes(&state, "bad.bmson", "virtual", b"not json")
+                .await
+                .is_err()
+        );
+        assert_eq!(state.tracks.lock().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn artist_analytics_group_tracks_by_artist() {
+        let track = |sha256: &str, artist: &str, genre: &str, notes: u32, bpm: f64| {
+            let mut track = TrackBuilder::new(sha256)
+                .artist(artist)
+                .genre(genre)
+                .note_count(notes)
+                .build();
+            track.effective_bpm = bpm;
+            track
+        };
+        let state = AppState::default();
+        add_tracks(
+            &state,
+            "pack",
+            vec![
+                track("1", "DJ Example", "Trance", 1000, 150.0),
+                track("2", "dj example", "TRANCE", 2000, 170.0),
+                track("3", "DJ Example", "Jazz", 3000, 190.0),
+                track("4", "Other", "Trance", 500, 120.0),
+            ],
+        )
+        .await;
+
+        let analytics = artist_analytics(&state).await;
+        assert_eq!(analytics.len(), 2);
+        assert_eq!(analytics[0].artist, "DJ Example");
+        assert_eq!(analytics[0].track_count, 3);
+        assert_eq!(analytics[0].avg_note_count, 2000.0);
+        assert_eq!(analytics[0].avg_bpm, 170.0);
+        assert_eq!(analytics[0].genres, ["Trance", "Jazz"]);
+        assert_eq!(analytics[0].key_modes, ["7K"]);
+        assert_eq!(analytics[1].artist, "Other");
+        assert_eq!(analytics[1].track_count, 1);
+        assert_eq!(analytics[1].genres, ["Trance"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn duplicate_files_are_hardlinked() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TempDir::new();
+        let chart = b"#TITLE Song\n#ARTIST A\n#BPM 120\n#00111:01\n";
+        let mut tracks = Vec::new();
+        for name in ["a/song.bms", "b/song.bms", "c/song.bms", "other.bms"] {
+            let content: &[u8] = if name == "other.bms" {
+                b"#TITLE Other\n"
+            } else {
+                chart
+            };
+            let path = dir.write(name, content);
+            tracks.push(process_single_file(&path).await.unwrap());
+        }
+        let state = AppState::default();
+        add_tracks(&state, "pack", tracks).await;
+
+        let candidates = hardlink_candidates(&state, "pack").await.unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].paths.len(), 3);
+        assert_eq!(
+            candidates[0].potential_savings_bytes,
+            2 * chart.len() as u64
+        );
+
+        let sha256 = candidates[0].sha256.clone();
+        assert_eq!(
+            link_duplicates(&state, vec![sha256]).await,
+            Ok(2 * chart.len() as u64)
+        );
+        let inode = |name: &str| std::fs::metadata(dir.path().join(name)).unwrap().ino();
+        assert_eq!(inode("a/song.bms"), inode("b/song.bms"));
+        assert_eq!(inode("a/song.bms"), inode("c/song.bms"));
+        assert_ne!(inode("a/song.bms"), inode("other.bms"));
+        assert_eq!(std::fs::read(dir.path().join("c/song.bms")).unwrap(), chart);
+    }
+
+    #[tokio::test]
+    async fn id_start_makes_scanned_ids_repeatable() {
+        let dir = TempDir::new();
+        for i in 0..5 {
+            let content = format!("#TITLE Song {}\n#ARTIST A\n#00111:01\n", i);
+            dir.write(&format!("pack{}/song{}.bms", i % 2, i), content.as_bytes());
+        }
+        let scan = || async {
+            let state = AppState::default();
+            let config = ScanConfig {
+                id_start: Some(1000),
+                ..Default::default()
+            };
+            apply_config(&state, config, |_| async { Ok(()) })
+                .await
+                .unwrap();
+            let files = bms_scan::scan_simple(dir.path().to_path_buf())
+                .await
+                .unwrap()
+                .collect()
+                .await;
+            let (tracks, _) = tracks_from_files(files).await;
+            state
+                .replace_directory("bms".to_string(), tracks, DedupStrategy::AllowDuplicates)
+                .await;
+            directory_tracks(&state, "bms")
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|track| (track.id, track.absolute_path))
+                .collect::<Vec<_>>()
+        };
+
+        let first = scan().await;
+        assert_eq!(first.len(), 5);
+        assert_eq!(first[0].0, 1000);
+        assert_eq!(first, scan().await);
+    }
+
+    #[tokio::test]
+    async fn missing_keysounds_are_counted_and_rechecked() {
+        let dir = TempDir::new();
+        dir.write("pack/kick.wav", b"RIFF");
+        dir.write(
+            "pack/a.bms",
+            b"#TITLE A\n#WAV01 kick.wav\n#WAV02 snare.wav\n#wav03 hat.wav\n#00111:01\n",
+        );
+        dir.write("pack/b.bms", b"#TITLE B\n#WAV01 kick.wav\n#00111:01\n");
+        dir.write("pack/c.bms", b"#TITLE C\n#WAV0A crash.wav\n#00111:01\n");
+        let files = bms_scan::scan_simple(dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let (tracks, _) = tracks_from_files(files).await;
+        let state = AppState::default();
+        let ids = add_tracks(&state, "pack", tracks).await;
+
+        let report = global_missing_report(&state.tracks.lock().await);
+        assert_eq!(report.tracks_with_missing, 2);
+        assert_eq!(report.total_missing_files, 3);
+        assert_eq!(
+            report.missing_by_track,
+            [
+                (ids[0], vec!["WAV02".to_string(), "WAV03".to_string()]),
+                (ids[2], vec!["WAV0A".to_string()]),
+            ]
+        );
+
+        dir.write("pack/snare.wav", b"RIFF");
+        dir.write("pack/crash.wav", b"RIFF");
+        let report = recheck_keysounds(&state).await;
+        assert_eq!(report.tracks_with_missing, 1);
+        assert_eq!(report.total_missing_files, 1);
+        assert_eq!(
+            track_by_id(&state, ids[0]).await.unwrap().missing_keysounds,
+            ["WAV03"]
+        );
+    }
+
+    #[tokio::test]
+    async fn watched_directories_import_new_charts() {
+        let dir = TempDir::new();
+        let drop_zone = dir.path().join("drop");
+        std::fs::create_dir(&drop_zone).unwrap();
+        let state = AppState::default();
+        let (name, mut changes) = start_watching(&state, drop_zone.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "drop");
+        assert!(start_watching(&state, drop_zone.clone())
+            .await
+            .unwrap()
+            .is_none());
+
+        dir.write("drop/song.bms", b"#TITLE Song\n#ARTIST A\n#00111:01\n");
+        dir.write("drop/notes.txt", b"readme");
+        let mut imported = Vec::new();
+        while imported.is_empty() {
+            let paths = tokio::time::timeout(
+                Duration::from_secs(5),
+                watcher::next_batch(&mut changes, Duration::from_millis(100)),
+            )
+            .await
+            .expect("no change event within 5 seconds")
+            .unwrap();
+            imported.extend(auto_import_files(&state, &name, paths).await);
+        }
+        assert!(matches!(&imported[0], LibraryEvent::TrackAdded(track) if track.title == "Song"));
+
+        let tracks = directory_tracks(&state, "drop").await.unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].absolute_path, drop_zone.join("song.bms"));
+        assert!(stop_watching(&state, &drop_zone).await);
+    }
+
+    #[tokio::test]
+    async fn sessions_record_plays_into_history() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").build(),
+                TrackBuilder::new("b").build(),
+            ],
+        )
+        .await;
+
+        let session_id = begin_session(&state).await.unwrap();
+        assert!(begin_session(&state).await.is_err());
+        for &id in &[ids[0], ids[1], ids[0]] {
+            record_play(&state, session_id, id).await.unwrap();
+        }
+        assert!(record_play(&state, session_id, 9999).await.is_err());
+        assert!(record_play(&state, session_id + 1, ids[0]).await.is_err());
+        assert!(recent_sessions(&state, 10).await.is_empty());
+        assert_eq!(play_counts(&state).await[&ids[0]], 2);
+
+        assert!(close_session(&state, session_id + 1).await.is_err());
+        close_session(&state, session_id).await.unwrap();
+        let history = recent_sessions(&state, 10).await;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].tracks_played, [ids[0], ids[1], ids[0]]);
+        assert!(history[0]
+            .end_unix
+            .is_some_and(|end| end >= history[0].start_unix));
+        assert!(state.active_session.lock().await.is_none());
+
+        let next = begin_session(&state).await.unwrap();
+        assert_ne!(next, session_id);
+        close_session(&state, next).await.unwrap();
+        let history = recent_sessions(&state, 1).await;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].session_id, next);
+    }
+
+    #[tokio::test]
+    async fn prefetched_notes_are_served_without_disk_reads() {
+        let dir = TempDir::new();
+        let mut tracks = Vec::new();
+        for i in 0..6 {
+            let path = dir.write(
+                &format!("song{i}.bms"),
+                b"#TITLE Song\n#BPM 120\n#00111:01010101\n",
+            );
+            let mut track = process_single_file(&path).await.unwrap();
+            track.notes = None;
+            tracks.push(track);
+        }
+        let state = AppState::default();
+        let ids = add_tracks(&state, "pack", tracks).await;
+
+        assert!(enqueue_prefetch(&state, ids[..5].to_vec()).await);
+        assert!(!enqueue_prefetch(&state, vec![ids[0]]).await);
+        run_prefetch(&state).await;
+        assert!(!state.prefetch.lock().await.running);
+
+        // 文件被删除后仍可由内存中的结果取得音符
+        for i in 0..6 {
+            std::fs::remove_file(dir.path().join(format!("song{i}.bms"))).unwrap();
+        }
+        for &id in &ids[..5] {
+            assert_eq!(load_notes(&state, id).await.unwrap().len(), 4);
+        }
+        assert!(load_notes(&state, ids[5]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn cancelled_prefetch_reads_nothing() {
+        let dir = TempDir::new();
+        let path = dir.write("song.bms", b"#TITLE Song\n#00111:01\n");
+        let mut track = process_single_file(&path).await.unwrap();
+        track.notes = None;
+        let state = AppState::default();
+        let ids = add_tracks(&state, "pack", vec![track]).await;
+
+        assert!(enqueue_prefetch(&state, ids.clone()).await);
+        state.prefetch.lock().await.queue.clear();
+        run_prefetch(&state).await;
+        assert!(state.tracks.lock().await[0].notes.is_none());
+    }
+
+    #[tokio::test]
+    async fn scan_completion_is_observable_by_polling() {
+        let state = AppState::default();
+        assert!(scan_complete(&state).await);
+
+        let guard = begin_scan(&state).await;
+        assert!(!scan_complete(&state).await);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(guard);
+        });
+        let mut polls = 0;
+        while !scan_complete(&state).await {
+            polls += 1;
+            assert!(polls < 100, "scan never completed");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(polls > 0);
+
+        let dir = TempDir::new();
+        for i in 0..20 {
+            dir.write(&format!("pack{}/song{}.bms", i % 4, i), b"#TITLE Song\n");
+        }
+        let handle = bms_scan::scan_directory_recursive(
+            dir.path().to_path_buf(),
+            storage::StorageType::SSD,
+            ScanConfig::default(),
+        )
+        .await
+        .unwrap();
+        *state.current_scan.lock().await = Some(handle.is_completed.clone());
+        let mut polls = 0;
+        while !scan_complete(&state).await {
+            polls += 1;
+            assert!(polls < 500, "scan never completed");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(handle.collect().await.len(), 20);
+        // 完成标记保留到下一次扫描开始
+        assert!(scan_complete(&state).await);
+    }
+
+    #[tokio::test]
+    async fn binary_index_round_trips_a_large_library() {
+        let state = AppState::default();
+        for pack in 0..10 {
+            let tracks = (0..100)
+                .map(|i| {
+                    TrackBuilder::new(&format!("{pack}-{i}"))
+                        .title(&format!("Song {i}"))
+                        .artist(&format!("Artist {}", i % 7))
+                        .bpm(100.0 + i as f64)
+                        .play_level(i % 12)
+                        .note_count(u32::from(i) * 10)
+                        .absolute_path(format!("/bms/pack{pack}/song{i}.bms"))
+                        .build()
+                })
+                .collect();
+            add_tracks(&state, &format!("pack{pack}"), tracks).await;
+        }
+        state
+            .directory_roots
+            .lock()
+            .await
+            .insert("pack0".to_string(), PathBuf::from("/bms/pack0"));
+
+        let dir = TempDir::new();
+        let path = dir.path().join("library.bincab");
+        let size = snapshot::write_binary_index(&path, &build_state_snapshot(&state).await)
+            .await
+            .unwrap();
+        assert_eq!(size, std::fs::metadata(&path).unwrap().len());
+
+        let loaded = AppState::default();
+        add_tracks(&loaded, "stale", vec![TrackBuilder::new("x").build()]).await;
+        assert_eq!(load_binary_index(&loaded, &path).await, Ok(1000));
+        assert!(*loaded.tracks.lock().await == *state.tracks.lock().await);
+        assert!(loaded.directory_index.lock().await.get("stale").is_none());
+        assert_eq!(
+            loaded.directory_roots.lock().await["pack0"],
+            PathBuf::from("/bms/pack0")
+        );
+        assert_eq!(loaded.next_id.load(Ordering::Relaxed), 1000);
+        assert!(loaded
+            .lock_library()
+            .await
+            .health_report()
+            .details
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn directory_diff_splits_partially_overlapping_directories() {
+        let state = AppState::default();
+        let track = |path: &str, sha256: &str| {
+            TrackBuilder::new(sha256)
+                .absolute_path(format!("/bms/{path}.bms"))
+                .build()
+        };
+        add_tracks(
+            &state,
+            "a",
+            vec![
+                track("a1", "shared1"),
+                track("a2", "shared2"),
+                track("a3", "onlya"),
+            ],
+        )
+        .await;
+        add_tracks(
+            &state,
+            "b",
+            vec![
+                track("b1", "shared1"),
+                track("b2", "shared2"),
+                track("b3", "onlyb1"),
+                track("b4", "onlyb2"),
+            ],
+        )
+        .await;
+
+        let Ok(DirectoryDiff::Full {
+            only_in_a,
+            only_in_b,
+            in_both,
+        }) = diff_directories(&state, "a", "b").await
+        else {
+            panic!("expected a full diff");
+        };
+        let sha256s = |tracks: &[Track]| -> Vec<String> {
+            tracks.iter().map(|track| track.sha256.clone()).collect()
+        };
+        assert_eq!(sha256s(&only_in_a), ["onlya"]);
+        assert_eq!(sha256s(&only_in_b), ["onlyb1", "onlyb2"]);
+        let pairs: Vec<(&str, &str)> = in_both
+            .iter()
+            .map(|(a, b)| {
+                (
+                    a.absolute_path.to_str().unwrap(),
+                    b.absolute_path.to_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("/bms/a1.bms", "/bms/b1.bms"),
+                ("/bms/a2.bms", "/bms/b2.bms")
+            ]
+        );
+
+        assert!(diff_directories(&state, "a", "missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reindex_repairs_a_corrupted_id_index() {
+        let state = AppState::default();
+        let ids = add_tracks(
+            &state,
+            "pack",
+            vec![
+                TrackBuilder::new("a").artist("X").genre("G").build(),
+                TrackBuilder::new("b").artist("Y").genre("G").build(),
+                TrackBuilder::new("c").artist("Y").genre("H").build(),
+            ],
+        )
+        .await;
+        {
+            let mut library = state.lock_library().await;
+            // 两个 id 索引条目互换位置，并丢失一条流派索引
+            library.id_index.insert(ids[0], 1);
+            library.id_index.insert(ids[1], 0);
+            library.genre_index.clear();
+            assert!(!library.health_report().details.is_empty());
+        }
+
+        let report = rebuild_indexes(&state).await;
+        assert_eq!(report.id_index_rebuilt, 3);
+        assert_eq!(report.sha256_index_rebuilt, 3);
+        assert_eq!(report.artist_index_rebuilt, 2);
+        assert_eq!(report.genre_index_rebuilt, 2);
+
+        assert!(state
+            .lock_library()
+            .await
+            .health_report()
+            .details
+            .is_empty());
+        for (id, sha256) in ids.iter().zip(["a", "b", "c"]) {
+            assert_eq!(track_by_id(&state, *id).await.unwrap().sha256, sha256);
+        }
+        assert_eq!(tracks_by_genre(&state, "G").await.len(), 2);
+    }
+
+    // 按 LR2 的 song 表结构创建测试用数据库，写入 count 行
+    async fn lr2_fixture(path: &Path, count: u32) {
+        use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions};
+        let mut connection = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .connect()
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE song (hash TEXT, sha256 TEXT, title TEXT, artist TEXT, \
+             bpm REAL, notes_count INTEGER, path TEXT)",
+        )
+        .execute(&mut connection)
+        .await
+        .unwrap();
+        for i in 0..count {
+            sqlx::query("INSERT INTO song VALUES (?, ?, ?, ?, ?, ?, ?)")
+                .bind(format!("{i:032x}"))
+                .bind(format!("{i:064X}"))
+                .bind(format!("Song {i}"))
+                .bind("LR2 Artist")
+                .bind(120.0 + f64::from(i))
+                .bind(i64::from(i) * 100)
+                .bind(format!("LR2files/Song{i}/song.bms"))
+                .execute(&mut connection)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn lr2_database_rows_become_tracks() {
+        let dir = TempDir::new();
+        let path = dir.path().join("LR2files.db");
+        lr2_fixture(&path, 10).await;
+
+        let state = AppState::default();
+        let report = import_lr2_songs(&state, &path, "lr2").await.unwrap();
+        assert_eq!(
+            (report.imported, report.skipped_duplicate, report.failed),
+            (10, 0, 0)
+        );
+        let library = state.lock_library().await;
+        let tracks = &library.tracks[library.directory_index["lr2"].clone()];
+        assert_eq!(tracks.len(), 10);
+        let track = tracks.iter().find(|track| track.title == "Song 3").unwrap();
+        assert_eq!(track.artist, "LR2 Artist");
+        assert_eq!(track.bpm, 123.0);
+        assert_eq!(track.note_count, 300);
+        assert_eq!(track.sha256, format!("{:064x}", 3));
+        assert_eq!(track.absolute_path, Path::new("LR2files/Song3/song.bms"));
+        assert!(track.metadata_source == MetadataSource::Lr2Database);
+        assert!(library.health_report().details.is_empty());
+        drop(library);
+
+        let report = import_lr2_songs(&state, &path, "lr2").await.unwrap();
+        assert_eq!(
+            (report.imported, report.skipped_duplicate, report.failed),
+            (0, 10, 0)
+        );
+        assert!(
+            import_lr2_songs(&state, &dir.path().join("missing.db"), "lr2")
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! 本模块用于读取 Lunatic Rave 2 的曲库数据库（`LR2files.db`，SQLite）
+//!
+//! 只读取 `song` 表中导入曲目所需的列：`title`、`artist`、`bpm`、`notes_count`、`sha256`
+//! 与 `path`（LR2 自身使用的 MD5 列 `hash` 不参与导入）。缺少的列按 `NULL` 处理，
+//! 数据库以只读方式打开。
+
+use std::path::{Path, PathBuf};
+
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteRow},
+    ConnectOptions, Row,
+};
+
+/// `song` 表中的一行
+#[derive(Debug, Clone, Default)]
+pub struct Lr2Song {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub bpm: Option<f64>,
+    pub notes_count: Option<u32>,
+    /// 小写十六进制的 SHA256，为空时视为缺失
+    pub sha256: Option<String>,
+    /// 谱面文件路径（LR2 中通常为相对于 LR2 安装目录的路径）
+    pub path: Option<PathBuf>,
+}
+
+/// 读取数据库中的全部曲目（按行号顺序）
+pub async fn read_lr2_songs(path: &Path) -> Result<Vec<Lr2Song>, String> {
+    let mut connection = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(|e| e.to_string())?;
+    let rows = sqlx::query("SELECT * FROM song ORDER BY rowid")
+        .fetch_all(&mut connection)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows.iter().map(song_from_row).collect())
+}
+
+// 辅助函数：按列名取值，列不存在或类型不符时为 None
+fn song_from_row(row: &SqliteRow) -> Lr2Song {
+    let text = |column: &str| {
+        row.try_get::<Option<String>, _>(column)
+            .ok()
+            .flatten()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    Lr2Song {
+        title: text("title"),
+        artist: text("artist"),
+        bpm: row.try_get::<Option<f64>, _>("bpm").ok().flatten(),
+        notes_count: row
+            .try_get::<Option<i64>, _>("notes_count")
+            .ok()
+            .flatten()
+            .and_then(|count| u32::try_from(count).ok()),
+        sha256: text("sha256").map(|sha256| sha256.to_ascii_lowercase()),
+        path: text("path").map(PathBuf::from),
+    }
+}
@@ -0,0 +1,100 @@
+//! 本模块用于读写扩展 M3U 播放列表（`.m3u8`，UTF-8）
+//!
+//! 写出的文件以 `#EXTM3U` 开头，每个条目为一行 `#EXTINF:<秒数>,<显示名>` 加一行绝对路径；
+//! 读取时忽略全部 `#` 开头的行，只取出条目路径。
+
+use std::path::{Path, PathBuf};
+
+/// 播放列表中的一个条目
+#[derive(Debug, Clone)]
+pub struct M3uEntry {
+    /// 时长（秒），未知时为 -1
+    pub duration_secs: i64,
+    /// 显示名，通常为 `<艺术家> - <标题>`
+    pub title: String,
+    pub path: PathBuf,
+}
+
+/// 生成扩展 M3U 内容；Windows 上路径分隔符统一为 `\`
+pub fn write_m3u(entries: &[M3uEntry]) -> String {
+    let mut content = String::from("#EXTM3U\n");
+    for entry in entries {
+        // 显示名中的换行会破坏文件结构
+        let title = entry.title.replace(['\r', '\n'], " ");
+        content.push_str(&format!("#EXTINF:{},{}\n", entry.duration_secs, title));
+        content.push_str(&normalize_separators(&entry.path));
+        content.push('\n');
+    }
+    content
+}
+
+/// 读取 M3U 内容中的条目路径（按出现顺序），跳过空行与 `#` 开头的行
+pub fn read_m3u_paths(content: &str) -> Vec<PathBuf> {
+    content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('\u{feff}'))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect()
+}
+
+// 辅助函数：路径的字符串形式，Windows 上将 `/` 替换为 `\`
+fn normalize_separators(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        path.replace('/', "\\")
+    } else {
+        path.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_paths_are_read_back_in_order() {
+        let entries = [
+            M3uEntry {
+                duration_secs: 125,
+                title: "Artist - Title".to_string(),
+                path: PathBuf::from("/bms/pack/song a.bms"),
+            },
+            M3uEntry {
+                duration_secs: -1,
+                title: "第二首".to_string(),
+                path: PathBuf::from("/bms/其他/b.bme"),
+            },
+        ];
+        let content = write_m3u(&entries);
+        assert!(content.starts_with("#EXTM3U\n#EXTINF:125,Artist - Title\n"));
+        let paths = read_m3u_paths(&content);
+        let expected: Vec<_> = entries
+            .iter()
+            .map(|entry| PathBuf::from(normalize_separators(&entry.path)))
+            .collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn newlines_in_title_do_not_break_entries() {
+        let entries = [M3uEntry {
+            duration_secs: 0,
+            title: "line1\r\nline2".to_string(),
+            path: PathBuf::from("/bms/a.bms"),
+        }];
+        let content = write_m3u(&entries);
+        assert_eq!(content.lines().count(), 3);
+        assert_eq!(read_m3u_paths(&content).len(), 1);
+    }
+
+    #[test]
+    fn comments_blank_lines_and_bom_are_skipped() {
+        let content =
+            "\u{feff}#EXTM3U\r\n\r\n#EXTINF:1,a\r\n  /bms/a.bms  \r\n# comment\r\n/bms/b.bms";
+        assert_eq!(
+            read_m3u_paths(content),
+            [PathBuf::from("/bms/a.bms"), PathBuf::from("/bms/b.bms")]
+        );
+    }
+}
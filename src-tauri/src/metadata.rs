@@ -0,0 +1,117 @@
+//! 本模块用于从 BMS 包的文件夹命名推断曲目元数据
+//!
+//! 当谱面缺少 `#TITLE` / `#ARTIST` 时，按常见的文件夹命名方式
+//! （`[流派] 艺术家 - 标题`、`艺术家 - 标题`、`艺术家_标题`）补全信息。
+
+use std::{path::Path, sync::LazyLock};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// 曲目元数据的来源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetadataSource {
+    /// 来自谱面头部
+    Parsed,
+    /// 来自文件夹名推断
+    InferredFromPath,
+    /// 来自 LR2 曲库数据库（`import_from_lr2_db`）
+    Lr2Database,
+    /// 由用户修改（`update_track_metadata`）
+    UserEdited,
+    /// 使用占位值
+    #[default]
+    Default,
+}
+
+/// 从路径推断出的元数据
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferredMeta {
+    pub artist: String,
+    pub title: String,
+    /// 仅 `[流派] 艺术家 - 标题` 形式能得到流派
+    pub genre: Option<String>,
+}
+
+/// 文件夹命名规则，按优先级排列
+static PATTERNS: LazyLock<[Regex; 3]> = LazyLock::new(|| {
+    [
+        Regex::new(r"^\[(?P<genre>[^\]]+)\]\s*(?P<artist>.+?)\s+-\s+(?P<title>.+)$").unwrap(),
+        Regex::new(r"^(?P<artist>.+?)\s+-\s+(?P<title>.+)$").unwrap(),
+        Regex::new(r"^(?P<artist>[^_]+)_(?P<title>.+)$").unwrap(),
+    ]
+});
+
+/// 根据谱面所在文件夹的名称推断艺术家、标题与流派
+///
+/// `path` 为谱面文件路径，匹配其直接父目录的名称；均不匹配时返回 `None`。
+pub fn infer_metadata_from_path(path: &Path) -> Option<InferredMeta> {
+    let folder_name = path.parent()?.file_name()?.to_str()?.trim();
+
+    PATTERNS.iter().find_map(|pattern| {
+        let captures = pattern.captures(folder_name)?;
+        let field = |name| {
+            captures
+                .name(name)
+                .map(|value| value.as_str().trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Some(InferredMeta {
+            artist: field("artist")?,
+            title: field("title")?,
+            genre: field("genre"),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(path: &str) -> Option<InferredMeta> {
+        infer_metadata_from_path(Path::new(path))
+    }
+
+    #[test]
+    fn genre_artist_title_folder() {
+        assert_eq!(
+            infer("/bms/[Trance] DJ Example - Song Name/_7.bms"),
+            Some(InferredMeta {
+                artist: "DJ Example".into(),
+                title: "Song Name".into(),
+                genre: Some("Trance".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn artist_title_folder() {
+        assert_eq!(
+            infer("/bms/DJ Example - Song - Remix/_7.bms"),
+            Some(InferredMeta {
+                artist: "DJ Example".into(),
+                title: "Song - Remix".into(),
+                genre: None,
+            })
+        );
+    }
+
+    #[test]
+    fn underscore_folder() {
+        assert_eq!(
+            infer("/bms/Artist_Song_Title/_7.bms"),
+            Some(InferredMeta {
+                artist: "Artist".into(),
+                title: "Song_Title".into(),
+                genre: None,
+            })
+        );
+    }
+
+    #[test]
+    fn unmatched_folders_infer_nothing() {
+        assert_eq!(infer("/bms/SongName/_7.bms"), None);
+        assert_eq!(infer("/bms/[Trance]/_7.bms"), None);
+        assert_eq!(infer("_7.bms"), None);
+    }
+}
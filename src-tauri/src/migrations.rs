@@ -0,0 +1,149 @@
+//! 本模块定义曲库的迁移步骤
+//!
+//! 由旧版本导入、经快照或 sidecar 恢复的曲目可能缺少之后新增的字段。每个迁移步骤读取谱面
+//! 重新解析，只补全仍缺失的值，因此对已是最新的曲目重复执行也不会改变结果。
+//! 第 `n` 个步骤（[`MIGRATIONS`]`[n - 1]`）将曲库从版本 `n - 1` 升级到版本 `n`。
+
+use crate::{
+    bms_parse::{self, ParsedChart},
+    hex_string, Track,
+};
+
+/// 迁移步骤：以解析结果补全曲目中缺失的字段，返回曲目是否被修改
+pub type Migration = fn(&mut Track, &ParsedChart) -> bool;
+
+/// 按版本顺序排列的全部迁移步骤
+pub const MIGRATIONS: [Migration; 4] = [
+    migration_001_add_note_count,
+    migration_002_add_material_headers,
+    migration_003_add_chart_fingerprint,
+    migration_004_add_peak_nps,
+];
+
+/// 最新的曲库版本
+pub const LATEST_SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// 版本 1：补全音符数、长条数与皿音符数（原为 0 而谱面中有可演奏音符时）
+pub fn migration_001_add_note_count(track: &mut Track, chart: &ParsedChart) -> bool {
+    if track.note_count != 0 {
+        return false;
+    }
+    let counts = bms_parse::count_notes(&chart.notes);
+    if counts.note_count == 0 {
+        return false;
+    }
+    track.note_count = counts.note_count;
+    track.ln_count = counts.ln_count;
+    track.scratch_count = counts.scratch_count;
+    true
+}
+
+/// 版本 2：补全 `#MATERIAL`、`#OCTFP` 与 `#OCTAVE`
+pub fn migration_002_add_material_headers(track: &mut Track, chart: &ParsedChart) -> bool {
+    let mut changed = false;
+    if track.material.is_none() && chart.header.material.is_some() {
+        track.material = chart.header.material.clone();
+        changed = true;
+    }
+    if track.octfp.is_none() && chart.header.octfp.is_some() {
+        track.octfp = chart.header.octfp;
+        changed = true;
+    }
+    if track.octave.is_none() && chart.header.octave.is_some() {
+        track.octave = chart.header.octave;
+        changed = true;
+    }
+    changed
+}
+
+/// 版本 3：补全谱面指纹
+pub fn migration_003_add_chart_fingerprint(track: &mut Track, chart: &ParsedChart) -> bool {
+    if track.chart_fingerprint.is_some() {
+        return false;
+    }
+    track.chart_fingerprint = Some(hex_string(&bms_parse::chart_fingerprint(chart)));
+    true
+}
+
+/// 版本 4：补全峰值 NPS（原为 0 而谱面中有可演奏音符时）
+pub fn migration_004_add_peak_nps(track: &mut Track, chart: &ParsedChart) -> bool {
+    if track.peak_nps != 0.0 {
+        return false;
+    }
+    let peak_nps = bms_parse::peak_nps(
+        &chart.notes,
+        &chart.bpm_timeline,
+        &chart.stops,
+        &chart.measure_lengths,
+    );
+    if peak_nps == 0.0 {
+        return false;
+    }
+    track.peak_nps = peak_nps;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TrackBuilder;
+
+    const CHART: &[u8] = b"#TITLE Song\n#BPM 120\n#MATERIAL pack\n#OCTFP 1\n#OCTAVE -1\n\
+#00111:0101\n#00112:01\n#00116:01\n#00251:0101\n";
+
+    fn chart() -> ParsedChart {
+        bms_parse::parse_chart(CHART, false).unwrap()
+    }
+
+    #[test]
+    fn note_counts_are_filled_once() {
+        let chart = chart();
+        let mut track = TrackBuilder::new("a").build();
+        assert!(migration_001_add_note_count(&mut track, &chart));
+        let counts = bms_parse::count_notes(&chart.notes);
+        assert!(counts.note_count > 0);
+        assert_eq!(track.note_count, counts.note_count);
+        assert_eq!(track.ln_count, counts.ln_count);
+        assert_eq!(track.scratch_count, counts.scratch_count);
+        assert!(!migration_001_add_note_count(&mut track, &chart));
+
+        // 已有的值不会被覆盖
+        let mut track = TrackBuilder::new("a").note_count(7).build();
+        assert!(!migration_001_add_note_count(&mut track, &chart));
+        assert_eq!(track.note_count, 7);
+    }
+
+    #[test]
+    fn material_headers_are_filled_once() {
+        let chart = chart();
+        let mut track = TrackBuilder::new("a").build();
+        assert!(migration_002_add_material_headers(&mut track, &chart));
+        assert_eq!(track.material.as_deref(), Some("pack"));
+        assert_eq!(track.octfp, Some(1));
+        assert_eq!(track.octave, Some(-1));
+        assert!(!migration_002_add_material_headers(&mut track, &chart));
+    }
+
+    #[test]
+    fn chart_fingerprint_is_filled_once() {
+        let chart = chart();
+        let mut track = TrackBuilder::new("a").build();
+        assert!(migration_003_add_chart_fingerprint(&mut track, &chart));
+        assert_eq!(
+            track.chart_fingerprint,
+            Some(hex_string(&bms_parse::chart_fingerprint(&chart)))
+        );
+        assert!(!migration_003_add_chart_fingerprint(&mut track, &chart));
+    }
+
+    #[test]
+    fn peak_nps_is_filled_once() {
+        let chart = chart();
+        let mut track = TrackBuilder::new("a").build();
+        assert!(migration_004_add_peak_nps(&mut track, &chart));
+        assert!(track.peak_nps > 0.0);
+        let peak_nps = track.peak_nps;
+        assert!(!migration_004_add_peak_nps(&mut track, &chart));
+        assert_eq!(track.peak_nps, peak_nps);
+    }
+}
@@ -0,0 +1,170 @@
+//! 本模块用于计算谱面的难度指标
+//!
+//! 提供综合音符数、BPM、长条与皿比例的“力度”（power factor）指标、
+//! 分数评级（AAA-E）以及由判定等级与难度推断的推荐血条类型。
+
+use serde::{Deserialize, Serialize};
+
+use crate::Track;
+
+/// 力度指标的取值范围
+const POWER_FACTOR_MAX: f64 = 100.0;
+
+/// 计算曲目的力度指标（`0.0..=100.0`）
+///
+/// `pf = note_count × bpm_max × (1 + ln_ratio × 0.5) × (1 + scratch_count / note_count) / 60000`，
+/// 其中 `ln_ratio = ln_count / note_count`。
+pub fn compute_power_factor(track: &Track) -> f64 {
+    if track.note_count == 0 {
+        return 0.0;
+    }
+    let note_count = track.note_count as f64;
+    let ln_ratio = track.ln_count as f64 / note_count;
+    let scratch_ratio = track.scratch_count as f64 / note_count;
+
+    let pf = note_count * track.bpm_max * (1.0 + ln_ratio * 0.5) * (1.0 + scratch_ratio) / 60000.0;
+    pf.clamp(0.0, POWER_FACTOR_MAX)
+}
+
+/// 分数评级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum ScoreGrade {
+    AAA,
+    AA,
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+/// 各评级的最低得分率（百分比），依次为 AAA 到 D，低于 D 的为 E
+///
+/// 与 beatmania IIDX 相同，以 1/9 为间隔（AAA 为 8/9）。
+const GRADE_THRESHOLDS: [(f64, ScoreGrade); 6] = [
+    (800.0 / 9.0, ScoreGrade::AAA),
+    (700.0 / 9.0, ScoreGrade::AA),
+    (600.0 / 9.0, ScoreGrade::A),
+    (500.0 / 9.0, ScoreGrade::B),
+    (400.0 / 9.0, ScoreGrade::C),
+    (300.0 / 9.0, ScoreGrade::D),
+];
+
+/// 由得分率（占理论最高分的百分比）计算评级
+pub fn score_grade(score: f64) -> ScoreGrade {
+    GRADE_THRESHOLDS
+        .iter()
+        .find(|(threshold, _)| score >= *threshold)
+        .map_or(ScoreGrade::E, |(_, grade)| *grade)
+}
+
+/// 推荐的血条类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GaugeType {
+    #[default]
+    Normal,
+    Hard,
+    ExHard,
+    Hazard,
+}
+
+impl GaugeType {
+    /// 由名称解析（忽略大小写，允许 `ex_hard` / `ex-hard` 写法）
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name
+            .to_ascii_lowercase()
+            .replace(['_', '-', ' '], "")
+            .as_str()
+        {
+            "normal" => Some(GaugeType::Normal),
+            "hard" => Some(GaugeType::Hard),
+            "exhard" => Some(GaugeType::ExHard),
+            "hazard" => Some(GaugeType::Hazard),
+            _ => None,
+        }
+    }
+}
+
+/// 由 `#RANK` 与 `#DIFFICULTY` 推断血条类型
+///
+/// - VERY HARD 判定（`#RANK 0`）的 INSANE 谱面：Hazard
+/// - 其余 VERY HARD 判定：ExHard
+/// - HARD 判定（`#RANK 1`）或 ANOTHER / INSANE 谱面（`#DIFFICULTY` 4、5）：Hard
+/// - 其他：Normal
+pub fn gauge_type_for(rank: Option<u8>, difficulty: Option<u8>) -> GaugeType {
+    let hard_chart = matches!(difficulty, Some(4 | 5));
+    match rank {
+        Some(0) if difficulty == Some(5) => GaugeType::Hazard,
+        Some(0) => GaugeType::ExHard,
+        Some(1) => GaugeType::Hard,
+        _ if hard_chart => GaugeType::Hard,
+        _ => GaugeType::Normal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TrackBuilder;
+
+    #[test]
+    fn power_factor_follows_formula() {
+        // 600 × 150 / 60000 = 1.5
+        let plain = TrackBuilder::new("a")
+            .note_count(600)
+            .bpm_max(150.0)
+            .build();
+        assert!((compute_power_factor(&plain) - 1.5).abs() < 1e-9);
+
+        // 长条比例 0.5、皿比例 0.25：1.5 × 1.25 × 1.25
+        let track = TrackBuilder::new("b")
+            .note_count(600)
+            .bpm_max(150.0)
+            .ln_count(300)
+            .scratch_count(150)
+            .build();
+        assert!((compute_power_factor(&track) - 1.5 * 1.25 * 1.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn power_factor_is_bounded() {
+        assert_eq!(compute_power_factor(&TrackBuilder::new("a").build()), 0.0);
+        let dense = TrackBuilder::new("b")
+            .note_count(1_000_000)
+            .bpm_max(999.0)
+            .build();
+        assert_eq!(compute_power_factor(&dense), POWER_FACTOR_MAX);
+    }
+
+    #[test]
+    fn score_grade_thresholds_are_inclusive() {
+        assert_eq!(score_grade(100.0), ScoreGrade::AAA);
+        assert_eq!(score_grade(800.0 / 9.0), ScoreGrade::AAA);
+        assert_eq!(score_grade(800.0 / 9.0 - 1e-9), ScoreGrade::AA);
+        assert_eq!(score_grade(600.0 / 9.0), ScoreGrade::A);
+        assert_eq!(score_grade(300.0 / 9.0), ScoreGrade::D);
+        assert_eq!(score_grade(300.0 / 9.0 - 1e-9), ScoreGrade::E);
+        assert_eq!(score_grade(0.0), ScoreGrade::E);
+    }
+
+    #[test]
+    fn gauge_type_from_rank_and_difficulty() {
+        assert_eq!(gauge_type_for(Some(0), Some(5)), GaugeType::Hazard);
+        assert_eq!(gauge_type_for(Some(0), Some(4)), GaugeType::ExHard);
+        assert_eq!(gauge_type_for(Some(1), Some(1)), GaugeType::Hard);
+        assert_eq!(gauge_type_for(Some(3), Some(4)), GaugeType::Hard);
+        assert_eq!(gauge_type_for(None, Some(5)), GaugeType::Hard);
+        assert_eq!(gauge_type_for(Some(2), Some(3)), GaugeType::Normal);
+        assert_eq!(gauge_type_for(None, None), GaugeType::Normal);
+    }
+
+    #[test]
+    fn gauge_type_from_name() {
+        assert_eq!(GaugeType::from_name("NORMAL"), Some(GaugeType::Normal));
+        assert_eq!(GaugeType::from_name("ex_hard"), Some(GaugeType::ExHard));
+        assert_eq!(GaugeType::from_name("Ex-Hard"), Some(GaugeType::ExHard));
+        assert_eq!(GaugeType::from_name("hazard"), Some(GaugeType::Hazard));
+        assert_eq!(GaugeType::from_name("easy"), None);
+    }
+}
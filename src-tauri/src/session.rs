@@ -0,0 +1,98 @@
+//! 本模块定义游玩会话及其历史记录的读写
+//!
+//! 会话由 `start_session` 开始、`end_session` 结束，期间每次游玩记录一个曲目 ID。
+//! 结束的会话追加到应用数据目录下的 [`SESSIONS_FILE_NAME`] 中（JSON），启动时读回。
+
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// 会话历史文件名（位于应用数据目录）
+pub const SESSIONS_FILE_NAME: &str = "sessions.json";
+
+/// 一次游玩会话
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaySession {
+    pub session_id: u32,
+    /// 开始时间（Unix 时间戳，秒）
+    pub start_unix: u64,
+    /// 结束时间，会话仍在进行时为 `None`
+    pub end_unix: Option<u64>,
+    /// 按游玩顺序记录的曲目 ID（可重复）
+    pub tracks_played: Vec<u32>,
+}
+
+impl PlaySession {
+    /// 以当前时间开始一个新会话
+    pub fn start(session_id: u32) -> Self {
+        Self {
+            session_id,
+            start_unix: now_unix(),
+            end_unix: None,
+            tracks_played: Vec::new(),
+        }
+    }
+
+    /// 以当前时间结束会话
+    pub fn end(&mut self) {
+        self.end_unix = Some(now_unix());
+    }
+}
+
+/// 读取会话历史，文件不存在或内容无效时返回空列表
+pub fn load_sessions(path: &Path) -> Vec<PlaySession> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+/// 写入会话历史（覆盖原文件）
+pub async fn save_sessions(path: &Path, sessions: &[PlaySession]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let content = serde_json::to_vec(sessions).map_err(|e| e.to_string())?;
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 辅助函数：当前时间（Unix 时间戳，秒）
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[tokio::test]
+    async fn sessions_file_round_trips() {
+        let dir = TempDir::new();
+        let path = dir.path().join("data").join(SESSIONS_FILE_NAME);
+        assert!(load_sessions(&path).is_empty());
+
+        let mut session = PlaySession::start(3);
+        session.tracks_played = vec![1, 2, 1];
+        session.end();
+        save_sessions(&path, std::slice::from_ref(&session))
+            .await
+            .unwrap();
+
+        let loaded = load_sessions(&path);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].session_id, 3);
+        assert_eq!(loaded[0].tracks_played, [1, 2, 1]);
+        assert_eq!(loaded[0].end_unix, session.end_unix);
+    }
+}
@@ -0,0 +1,32 @@
+//! 本模块用于读写谱面旁的元数据文件（sidecar）
+//!
+//! 每个谱面 `<stem>.<ext>` 旁的 `<stem>.cabinet.json` 记录解析得到的完整 [`Track`]，
+//! 重新导入时若 sidecar 与谱面内容一致（SHA256 相同）则直接使用，不再解析谱面。
+
+use std::path::{Path, PathBuf};
+
+use crate::Track;
+
+/// sidecar 文件的后缀
+const SIDECAR_SUFFIX: &str = ".cabinet.json";
+
+/// 谱面对应的 sidecar 路径
+pub fn sidecar_path(chart_path: &Path) -> Option<PathBuf> {
+    let stem = chart_path.file_stem()?.to_str()?;
+    Some(chart_path.with_file_name(format!("{}{}", stem, SIDECAR_SUFFIX)))
+}
+
+/// 读取谱面对应的 sidecar，不存在或内容无效时返回 `None`
+pub fn read_sidecar(path: &Path) -> Option<Track> {
+    let content = std::fs::read(sidecar_path(path)?).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// 将曲目写入其谱面对应的 sidecar
+pub async fn write_sidecar(track: &Track) -> Result<(), String> {
+    let path = sidecar_path(&track.absolute_path).ok_or("Invalid chart file name")?;
+    let content = serde_json::to_vec_pretty(track).map_err(|e| e.to_string())?;
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| e.to_string())
+}
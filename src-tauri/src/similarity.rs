@@ -0,0 +1,285 @@
+//! 本模块用于计算谱面之间的相似度，为“相似曲目”推荐提供依据
+//!
+//! 相似度由键位模式、最低 BPM、音符数、难度分类与难度等级加权得到，
+//! 取值范围为 `0.0`（完全不同）到 `1.0`（完全相同），且 `a`、`b` 交换后结果不变。
+//! 另提供两个谱面版本之间的差异比较（[`compare_tracks`]），以及基于游玩次数的推荐
+//! （[`recommend_from_history`]）。
+
+use std::collections::{HashMap, HashSet};
+
+use crate::Track;
+
+/// 键位模式一致时的权重
+const KEY_MODE_WEIGHT: f64 = 0.30;
+/// 最低 BPM 接近程度的权重
+const BPM_WEIGHT: f64 = 0.20;
+/// 音符数接近程度的权重
+const NOTE_COUNT_WEIGHT: f64 = 0.20;
+/// 难度分类一致时的权重
+const CHART_TYPE_WEIGHT: f64 = 0.15;
+/// 难度等级接近程度的权重
+const PLAY_LEVEL_WEIGHT: f64 = 0.15;
+
+/// 计算两个曲目的相似度（`0.0..=1.0`）
+pub fn compute_similarity(a: &Track, b: &Track) -> f64 {
+    let key_mode = if a.key_mode == b.key_mode { 1.0 } else { 0.0 };
+    let chart_type = if a.chart_type == b.chart_type {
+        1.0
+    } else {
+        0.0
+    };
+
+    KEY_MODE_WEIGHT * key_mode
+        + BPM_WEIGHT * closeness(a.bpm_min, b.bpm_min)
+        + NOTE_COUNT_WEIGHT * closeness(a.note_count as f64, b.note_count as f64)
+        + CHART_TYPE_WEIGHT * chart_type
+        + PLAY_LEVEL_WEIGHT * closeness(a.play_level as f64, b.play_level as f64)
+}
+
+// 辅助函数：两个非负数的接近程度，1 - |a - b| / max(a, b)，两者均为 0 时视为相同
+fn closeness(a: f64, b: f64) -> f64 {
+    let max = a.max(b);
+    if max <= 0.0 {
+        return 1.0;
+    }
+    (1.0 - (a - b).abs() / max).clamp(0.0, 1.0)
+}
+
+/// 两个谱面之间的差异，差值均为 `b - a`（正数表示 B 更大 / 更高）
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChartDiff {
+    pub note_count_delta: i32,
+    pub bpm_delta: f64,
+    pub play_level_delta: i8,
+    pub ln_ratio_delta: f32,
+    pub sha256_changed: bool,
+    pub title_changed: bool,
+    pub artist_changed: bool,
+    pub power_factor_delta: f64,
+    /// 见 [`compute_similarity`]
+    pub similarity_score: f64,
+}
+
+/// 比较两个曲目的元数据与音符统计
+pub fn compare_tracks(a: &Track, b: &Track) -> ChartDiff {
+    let play_level_delta = b.play_level as i16 - a.play_level as i16;
+    ChartDiff {
+        note_count_delta: (b.note_count as i64 - a.note_count as i64)
+            .clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+        bpm_delta: b.bpm - a.bpm,
+        play_level_delta: play_level_delta.clamp(i8::MIN as i16, i8::MAX as i16) as i8,
+        ln_ratio_delta: ln_ratio(b) - ln_ratio(a),
+        sha256_changed: a.sha256 != b.sha256,
+        title_changed: a.title != b.title,
+        artist_changed: a.artist != b.artist,
+        power_factor_delta: b.power_factor - a.power_factor,
+        similarity_score: compute_similarity(a, b),
+    }
+}
+
+// 辅助函数：长条占全部音符的比例
+fn ln_ratio(track: &Track) -> f32 {
+    if track.note_count == 0 {
+        return 0.0;
+    }
+    track.ln_count as f32 / track.note_count as f32
+}
+
+/// 历史推荐中参考的标签数（按关联的游玩次数取前若干个）
+const HISTORY_TOP_TAGS: usize = 5;
+/// 参考 BPM 取自游玩次数最多的若干曲目
+const HISTORY_TOP_TRACKS: usize = 10;
+/// 游玩次数不超过该值的曲目可被推荐
+const HISTORY_MAX_PLAY_COUNT: u32 = 1;
+
+/// 基于游玩历史推荐的曲目
+pub struct HistoryRecommendation<'a> {
+    pub track: &'a Track,
+    pub score: f64,
+    /// 与常玩曲目共有的用户标签 ID（升序）
+    pub shared_tags: Vec<u32>,
+    /// 推荐理由，如 `Shares tag 3, tag 7 with your most played tracks`
+    pub reason: String,
+}
+
+/// 根据游玩次数推荐曲目，按推荐分数降序返回最多 `count` 个
+///
+/// `track_tags` 为用户标签（标签 ID → 打上该标签的曲目 ID），与 `AppState.track_tags` 相同。
+/// 先按游玩次数累计各标签的权重，取权重最高的
+/// [`HISTORY_TOP_TAGS`] 个；未玩或较少游玩的曲目按共有标签数 × `1 / (游玩次数 + 1)` 计分，
+/// 再乘以与常玩曲目（按游玩次数加权）平均 `effective_bpm` 的接近程度（`0.5..=1.0`）。
+/// 没有游玩记录或常玩曲目没有标签时返回空列表。
+pub fn recommend_from_history<'a>(
+    tracks: &'a [Track],
+    play_counts: &HashMap<u32, u32>,
+    track_tags: &HashMap<u32, HashSet<u32>>,
+    count: usize,
+) -> Vec<HistoryRecommendation<'a>> {
+    let play_count = |track: &Track| play_counts.get(&track.id).copied().unwrap_or(0);
+    let mut played: Vec<&Track> = tracks
+        .iter()
+        .filter(|track| play_count(track) > 0)
+        .collect();
+    if played.is_empty() {
+        return Vec::new();
+    }
+
+    // 曲目 ID → 该曲目的标签 ID（升序）
+    let mut tags_by_track: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (&tag_id, track_ids) in track_tags {
+        for &track_id in track_ids {
+            tags_by_track.entry(track_id).or_default().push(tag_id);
+        }
+    }
+    for tags in tags_by_track.values_mut() {
+        tags.sort_unstable();
+    }
+    let tags_of = |track: &Track| {
+        tags_by_track
+            .get(&track.id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    };
+
+    let mut tag_weights: HashMap<u32, u32> = HashMap::new();
+    for track in &played {
+        for &tag in tags_of(track) {
+            *tag_weights.entry(tag).or_default() += play_count(track);
+        }
+    }
+    let mut tag_weights: Vec<_> = tag_weights.into_iter().collect();
+    tag_weights.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let top_tags: HashSet<u32> = tag_weights
+        .into_iter()
+        .take(HISTORY_TOP_TAGS)
+        .map(|(tag, _)| tag)
+        .collect();
+
+    played.sort_by_key(|track| std::cmp::Reverse(play_count(track)));
+    let most_played = &played[..played.len().min(HISTORY_TOP_TRACKS)];
+    let total_plays: u32 = most_played.iter().map(|track| play_count(track)).sum();
+    let reference_bpm = most_played
+        .iter()
+        .map(|track| track.effective_bpm * play_count(track) as f64)
+        .sum::<f64>()
+        / total_plays as f64;
+
+    let mut recommendations: Vec<HistoryRecommendation> = tracks
+        .iter()
+        .filter(|track| play_count(track) <= HISTORY_MAX_PLAY_COUNT)
+        .filter_map(|track| {
+            let shared_tags: Vec<u32> = tags_of(track)
+                .iter()
+                .copied()
+                .filter(|tag| top_tags.contains(tag))
+                .collect();
+            if shared_tags.is_empty() {
+                return None;
+            }
+            let bpm_factor = 0.5 + 0.5 * closeness(track.effective_bpm, reference_bpm);
+            let score = shared_tags.len() as f64 / (play_count(track) + 1) as f64 * bpm_factor;
+            let reason = shared_tags
+                .iter()
+                .map(|tag| format!("tag {}", tag))
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some(HistoryRecommendation {
+                track,
+                score,
+                shared_tags,
+                reason: format!("Shares {} with your most played tracks", reason),
+            })
+        })
+        .collect();
+    recommendations.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.track.id.cmp(&b.track.id))
+    });
+    recommendations.truncate(count);
+    recommendations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bms_parse::KeyMode, testing::TrackBuilder};
+
+    fn chart(sha256: &str, key_mode: KeyMode, bpm: f64, notes: u32, level: u8) -> Track {
+        TrackBuilder::new(sha256)
+            .key_mode(key_mode)
+            .bpm_min(bpm)
+            .note_count(notes)
+            .play_level(level)
+            .build()
+    }
+
+    #[test]
+    fn identical_charts_are_fully_similar() {
+        let a = chart("a", KeyMode::Key7, 150.0, 1000, 12);
+        assert!((compute_similarity(&a, &a.clone()) - 1.0).abs() < 1e-9);
+        // 数值均为 0 时视为相同
+        let empty = TrackBuilder::new("b").build();
+        assert!((compute_similarity(&empty, &empty.clone()) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn similarity_is_symmetric_and_bounded() {
+        let charts = [
+            chart("a", KeyMode::Key7, 150.0, 1000, 12),
+            chart("b", KeyMode::Key7, 180.0, 1500, 10),
+            chart("c", KeyMode::Key14, 90.0, 3000, 1),
+            chart("d", KeyMode::Key5, 0.0, 0, 0),
+        ];
+        for a in &charts {
+            for b in &charts {
+                let ab = compute_similarity(a, b);
+                assert!((0.0..=1.0).contains(&ab));
+                assert_eq!(ab, compute_similarity(b, a));
+            }
+        }
+    }
+
+    #[test]
+    fn key_mode_mismatch_costs_its_weight() {
+        let a = chart("a", KeyMode::Key7, 150.0, 1000, 12);
+        let b = chart("b", KeyMode::Key14, 150.0, 1000, 12);
+        assert!((compute_similarity(&a, &b) - (1.0 - KEY_MODE_WEIGHT)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn history_recommends_unplayed_tracks_sharing_tags() {
+        let tracks: Vec<Track> = (1..=5)
+            .map(|id| TrackBuilder::new(&id.to_string()).id(id).bpm(150.0).build())
+            .collect();
+        let play_counts = HashMap::from([(1, 5), (2, 1)]);
+        // 标签 10：曲目 1、3、4；标签 20：曲目 1、4；标签 30：只有未玩的曲目 5
+        let track_tags = HashMap::from([
+            (10, HashSet::from([1, 3, 4])),
+            (20, HashSet::from([1, 4])),
+            (30, HashSet::from([5])),
+        ]);
+
+        let recommendations = recommend_from_history(&tracks, &play_counts, &track_tags, 10);
+        let ids: Vec<u32> = recommendations.iter().map(|r| r.track.id).collect();
+        assert_eq!(ids, [4, 3]);
+        assert_eq!(recommendations[0].shared_tags, [10, 20]);
+        assert_eq!(
+            recommendations[0].reason,
+            "Shares tag 10, tag 20 with your most played tracks"
+        );
+        assert!((recommendations[0].score - 2.0).abs() < 1e-9);
+
+        let top = recommend_from_history(&tracks, &play_counts, &track_tags, 1);
+        assert_eq!(top.len(), 1);
+    }
+
+    #[test]
+    fn history_without_plays_or_tags_recommends_nothing() {
+        let tracks = vec![TrackBuilder::new("a").id(1).build()];
+        let track_tags = HashMap::from([(10, HashSet::from([1]))]);
+        assert!(recommend_from_history(&tracks, &HashMap::new(), &track_tags, 10).is_empty());
+        let play_counts = HashMap::from([(1, 3)]);
+        assert!(recommend_from_history(&tracks, &play_counts, &HashMap::new(), 10).is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+//! 本模块用于导出与恢复曲库快照
+//!
+//! 快照包含全部目录及其曲目，扩展名为 `.msgpack` / `.mpk` 时使用 MessagePack，
+//! 否则使用 JSON。`snapshot_version` 用于今后调整格式时区分旧快照。
+//!
+//! 同样的结构也以 bincode 写入应用数据目录下的二进制索引 [`BINARY_INDEX_FILE_NAME`]，
+//! 启动时直接从中载入曲库，省去重新扫描。
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Track;
+
+/// 当前的快照格式版本
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// 二进制索引文件名（位于应用数据目录）
+pub const BINARY_INDEX_FILE_NAME: &str = "library.bincab";
+
+/// 一个目录的快照
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DirectorySnapshot {
+    pub name: String,
+    /// 导入时的根路径
+    pub root: Option<PathBuf>,
+    pub tracks: Vec<Track>,
+}
+
+/// 曲库快照
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub snapshot_version: u32,
+    pub directories: Vec<DirectorySnapshot>,
+}
+
+/// 将快照写入文件
+pub async fn write_snapshot(path: &Path, snapshot: &StateSnapshot) -> Result<(), String> {
+    let content = if is_msgpack(path) {
+        rmp_serde::to_vec_named(snapshot).map_err(|e| e.to_string())?
+    } else {
+        serde_json::to_vec(snapshot).map_err(|e| e.to_string())?
+    };
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 从文件读取快照，拒绝比当前程序更新的格式版本
+pub async fn read_snapshot(path: &Path) -> Result<StateSnapshot, String> {
+    let content = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+    let snapshot: StateSnapshot = if is_msgpack(path) {
+        rmp_serde::from_slice(&content).map_err(|e| e.to_string())?
+    } else {
+        serde_json::from_slice(&content).map_err(|e| e.to_string())?
+    };
+    check_version(snapshot)
+}
+
+/// 将快照以 bincode 写入二进制索引，返回文件大小（字节）
+pub async fn write_binary_index(path: &Path, snapshot: &StateSnapshot) -> Result<u64, String> {
+    let content = bincode::serialize(snapshot).map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tokio::fs::write(path, &content)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(content.len() as u64)
+}
+
+/// 读取二进制索引，拒绝比当前程序更新的格式版本
+pub async fn read_binary_index(path: &Path) -> Result<StateSnapshot, String> {
+    let content = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+    let snapshot: StateSnapshot = bincode::deserialize(&content).map_err(|e| e.to_string())?;
+    check_version(snapshot)
+}
+
+// 辅助函数：拒绝比当前程序更新的格式版本
+fn check_version(snapshot: StateSnapshot) -> Result<StateSnapshot, String> {
+    if snapshot.snapshot_version > SNAPSHOT_VERSION {
+        return Err(format!(
+            "Unsupported snapshot version {}",
+            snapshot.snapshot_version
+        ));
+    }
+    Ok(snapshot)
+}
+
+// 辅助函数：按扩展名判断是否使用 MessagePack
+fn is_msgpack(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("msgpack") || ext.eq_ignore_ascii_case("mpk"))
+}
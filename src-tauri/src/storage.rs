@@ -0,0 +1,255 @@
+//! 本模块用于检测目录所在存储介质的类型
+//!
+//! 扫描器根据介质类型决定并发读取的数量（SSD 可以高并发，HDD 应顺序读取）。
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{DiskKind, Disks};
+
+/// 存储介质类型枚举
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum StorageType {
+    SSD,
+    HDD,
+    Unknown(isize),
+    /// 网络文件系统（NFS、SMB 等）
+    Network,
+    Failed,
+}
+
+/// 网络文件系统的类型名（`/proc/mounts` 的第三列，macOS 的 `f_fstypename`）
+const NETWORK_FS_TYPES: [&str; 9] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smbfs",
+    "smb3",
+    "fuse.sshfs",
+    "afpfs",
+    "webdav",
+    "9p",
+];
+
+/// 文件系统类型是否为网络文件系统
+pub fn is_network_fs(fs_type: &str) -> bool {
+    NETWORK_FS_TYPES.contains(&fs_type.to_ascii_lowercase().as_str())
+}
+
+/// 根据 `/proc/mounts` 格式的内容判断路径是否位于网络挂载点
+///
+/// 取包含该路径的最深挂载点，其类型为网络文件系统时返回 [`StorageType::Network`]。
+pub fn storage_type_from_mounts(path: &Path, mounts: &str) -> Option<StorageType> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = unescape_mount_field(fields.next()?);
+            let fs_type = fields.next()?;
+            Some((mount_point, fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| Path::new(mount_point).components().count())
+        .and_then(|(_, fs_type)| is_network_fs(fs_type).then_some(StorageType::Network))
+}
+
+/// 检测指定路径所在存储介质的类型
+pub async fn detect_storage_type(path: &Path) -> StorageType {
+    let canonical_path = tokio::fs::canonicalize(path)
+        .await
+        .unwrap_or_else(|_| path.to_path_buf());
+
+    #[cfg(target_os = "linux")]
+    if let Ok(mounts) = tokio::fs::read_to_string("/proc/mounts").await {
+        if let Some(storage_type) = storage_type_from_mounts(&canonical_path, &mounts) {
+            return storage_type;
+        }
+    }
+    #[cfg(windows)]
+    if is_remote_drive(&canonical_path) {
+        return StorageType::Network;
+    }
+
+    storage_type_from_disks(&canonical_path, &SystemDisks)
+}
+
+/// 磁盘信息
+#[derive(Debug, Clone)]
+pub struct DiskEntry {
+    pub mount_point: PathBuf,
+    /// 文件系统类型（macOS 上即 statfs 的 `f_fstypename`）
+    pub file_system: String,
+    pub kind: DiskKind,
+}
+
+/// 磁盘列表的来源，测试中可以替换 [`Disks`]
+pub trait DiskSource {
+    fn disks(&self) -> Vec<DiskEntry>;
+}
+
+/// 由 [`Disks`] 读取系统的磁盘列表
+struct SystemDisks;
+
+impl DiskSource for SystemDisks {
+    fn disks(&self) -> Vec<DiskEntry> {
+        Disks::new_with_refreshed_list()
+            .iter()
+            .map(|d| DiskEntry {
+                mount_point: d.mount_point().to_path_buf(),
+                file_system: d.file_system().to_string_lossy().into_owned(),
+                kind: d.kind(),
+            })
+            .collect()
+    }
+}
+
+/// 根据磁盘列表判断路径所在存储介质的类型
+///
+/// 取包含该路径的最深挂载点；没有挂载点包含该路径时返回 [`StorageType::Failed`]。
+pub fn storage_type_from_disks(path: &Path, source: &impl DiskSource) -> StorageType {
+    source
+        .disks()
+        .into_iter()
+        .filter(|d| {
+            path.starts_with(
+                d.mount_point
+                    .canonicalize()
+                    .unwrap_or_else(|_| d.mount_point.clone()),
+            )
+        })
+        .max_by_key(|d| d.mount_point.components().count())
+        .map(|d| match d.kind {
+            _ if is_network_fs(&d.file_system) => StorageType::Network,
+            DiskKind::HDD => StorageType::HDD,
+            DiskKind::SSD => StorageType::SSD,
+            DiskKind::Unknown(t) => StorageType::Unknown(t),
+        })
+        .unwrap_or(StorageType::Failed)
+}
+
+// 辅助函数：还原 /proc/mounts 中的八进制转义（空格、制表符、反斜杠）
+fn unescape_mount_field(field: &str) -> String {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\134", "\\")
+}
+
+// 辅助函数：通过 GetDriveTypeW 判断路径所在驱动器是否为网络驱动器
+#[cfg(windows)]
+fn is_remote_drive(path: &Path) -> bool {
+    use std::{os::windows::ffi::OsStrExt, path::Component};
+    use windows_sys::Win32::{
+        Storage::FileSystem::GetDriveTypeW, System::WindowsProgramming::DRIVE_REMOTE,
+    };
+
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return false;
+    };
+    let root: Vec<u16> = prefix
+        .as_os_str()
+        .encode_wide()
+        .chain("\\".encode_utf16())
+        .chain(Some(0))
+        .collect();
+    // SAFETY: root 为以 0 结尾的 UTF-16 字符串
+    unsafe { GetDriveTypeW(root.as_ptr()) == DRIVE_REMOTE }
+}
+
+/// [`detect_storage_type`] 的同步版本
+///
+/// 需要在 tokio 运行时内、但不在异步任务中调用（如 `spawn_blocking` 的线程），
+/// 否则会 panic。
+pub fn detect_storage_type_blocking(path: &Path) -> StorageType {
+    tokio::runtime::Handle::current().block_on(detect_storage_type(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+server:/export /mnt/nas nfs4 rw,relatime 0 0
+/dev/sdb1 /mnt/nas/local ext4 rw,relatime 0 0
+//host/share /mnt/my\\040share cifs rw 0 0
+";
+
+    #[test]
+    fn network_mount_is_detected() {
+        assert_eq!(
+            storage_type_from_mounts(Path::new("/mnt/nas/bms"), MOUNTS),
+            Some(StorageType::Network)
+        );
+        assert_eq!(
+            storage_type_from_mounts(Path::new("/mnt/my share/bms"), MOUNTS),
+            Some(StorageType::Network)
+        );
+    }
+
+    #[test]
+    fn deepest_mount_point_wins() {
+        assert_eq!(
+            storage_type_from_mounts(Path::new("/mnt/nas/local/bms"), MOUNTS),
+            None
+        );
+        assert_eq!(
+            storage_type_from_mounts(Path::new("/home/bms"), MOUNTS),
+            None
+        );
+    }
+
+    #[test]
+    fn mount_point_matches_whole_components() {
+        assert_eq!(
+            storage_type_from_mounts(Path::new("/mnt/nas2/bms"), MOUNTS),
+            None
+        );
+    }
+
+    struct FakeDisks(Vec<DiskEntry>);
+
+    impl DiskSource for FakeDisks {
+        fn disks(&self) -> Vec<DiskEntry> {
+            self.0.clone()
+        }
+    }
+
+    fn disk(mount_point: &str, file_system: &str, kind: DiskKind) -> DiskEntry {
+        DiskEntry {
+            mount_point: PathBuf::from(mount_point),
+            file_system: file_system.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn disk_kind_maps_to_storage_type() {
+        let disks = FakeDisks(vec![
+            disk("/", "ext4", DiskKind::SSD),
+            disk("/nonexistent-hdd", "ext4", DiskKind::HDD),
+            disk("/nonexistent-usb", "exfat", DiskKind::Unknown(-1)),
+            disk("/nonexistent-nas", "smbfs", DiskKind::HDD),
+        ]);
+        let detect = |path: &str| storage_type_from_disks(Path::new(path), &disks);
+        assert_eq!(detect("/home/bms"), StorageType::SSD);
+        assert_eq!(detect("/nonexistent-hdd/bms"), StorageType::HDD);
+        assert_eq!(detect("/nonexistent-usb/bms"), StorageType::Unknown(-1));
+        assert_eq!(detect("/nonexistent-nas/bms"), StorageType::Network);
+        // 没有挂载点包含该路径
+        assert_eq!(detect("relative/bms"), StorageType::Failed);
+        assert_eq!(
+            storage_type_from_disks(Path::new("/home/bms"), &FakeDisks(Vec::new())),
+            StorageType::Failed
+        );
+    }
+
+    #[test]
+    fn network_fs_type_ignores_case() {
+        assert!(is_network_fs("NFS"));
+        assert!(is_network_fs("fuse.sshfs"));
+        assert!(!is_network_fs("ext4"));
+    }
+}
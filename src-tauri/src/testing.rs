@@ -0,0 +1,153 @@
+//! 本模块提供测试中使用的辅助类型
+//!
+//! 直接写出 `Track` 的全部字段在新增字段时需要逐处修改；[`TrackBuilder`] 只设置测试关心的
+//! 字段，其余字段使用 [`Track::default`] 的值（`id` 为 0、数值为 0、字符串为空等）。
+//! [`TempDir`] 用于需要真实文件的扫描与导入测试。
+
+use std::path::{Path, PathBuf};
+
+use crate::{bms_parse::KeyMode, Track};
+
+/// [`Track`] 的构造器
+#[derive(Clone)]
+pub struct TrackBuilder {
+    track: Track,
+}
+
+impl TrackBuilder {
+    /// 以指定的 SHA256 开始构造，其余字段为默认值
+    pub fn new(sha256: &str) -> Self {
+        Self {
+            track: Track {
+                sha256: sha256.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn id(mut self, id: u32) -> Self {
+        self.track.id = id;
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.track.title = title.to_string();
+        self
+    }
+
+    pub fn artist(mut self, artist: &str) -> Self {
+        self.track.artist = artist.to_string();
+        self
+    }
+
+    pub fn genre(mut self, genre: &str) -> Self {
+        self.track.genre = genre.to_string();
+        self
+    }
+
+    /// 同时设置 `bpm` 与 `effective_bpm`
+    pub fn bpm(mut self, bpm: f64) -> Self {
+        self.track.bpm = bpm;
+        self.track.effective_bpm = bpm;
+        self
+    }
+
+    pub fn bpm_min(mut self, bpm_min: f64) -> Self {
+        self.track.bpm_min = bpm_min;
+        self
+    }
+
+    pub fn bpm_max(mut self, bpm_max: f64) -> Self {
+        self.track.bpm_max = bpm_max;
+        self
+    }
+
+    pub fn play_level(mut self, play_level: u8) -> Self {
+        self.track.play_level = play_level;
+        self
+    }
+
+    pub fn key_mode(mut self, key_mode: KeyMode) -> Self {
+        self.track.key_mode = key_mode;
+        self
+    }
+
+    pub fn note_count(mut self, note_count: u32) -> Self {
+        self.track.note_count = note_count;
+        self
+    }
+
+    pub fn ln_count(mut self, ln_count: u32) -> Self {
+        self.track.ln_count = ln_count;
+        self
+    }
+
+    pub fn scratch_count(mut self, scratch_count: u32) -> Self {
+        self.track.scratch_count = scratch_count;
+        self
+    }
+
+    pub fn absolute_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.track.absolute_path = path.into();
+        self
+    }
+
+    /// 完成构造
+    pub fn build(self) -> Track {
+        self.track
+    }
+}
+
+/// 测试用的临时目录，离开作用域时连同其中的文件一起删除
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// 在系统临时目录下创建一个新的空目录
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("be-music-cabinet-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 写入文件（按需创建上级目录），`relative` 为相对于临时目录的路径，返回文件路径
+    pub fn write(&self, relative: &str, content: &[u8]) -> PathBuf {
+        let path = self.path.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_only_requested_fields() {
+        let track = TrackBuilder::new("abc").title("T").build();
+        assert_eq!(track.sha256, "abc");
+        assert_eq!(track.title, "T");
+
+        let expected = Track {
+            sha256: "abc".to_string(),
+            title: "T".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&track).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+    }
+}
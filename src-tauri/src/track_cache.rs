@@ -0,0 +1,76 @@
+//! 本模块提供按曲目 ID 查询的缓存
+//!
+//! `get_track_by_id` 被频繁调用时，每次都要获取曲目表与全部索引的锁；命中缓存时则无需加锁。
+//! 缓存最多保留 [`CAPACITY`] 个曲目，每项在写入 [`TIME_TO_LIVE`] 后过期。
+//! 写入与失效都在持有曲目表锁时进行，因此不会在失效之后重新写入旧版本。
+
+use std::time::Duration;
+
+use moka::sync::Cache;
+
+use crate::Track;
+
+/// 最多缓存的曲目数
+pub const CAPACITY: u64 = 1000;
+
+/// 缓存项的存活时间
+pub const TIME_TO_LIVE: Duration = Duration::from_secs(10);
+
+/// 曲目缓存
+pub struct TrackCache {
+    cache: Cache<u32, Track>,
+}
+
+impl Default for TrackCache {
+    fn default() -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(CAPACITY)
+                .time_to_live(TIME_TO_LIVE)
+                .build(),
+        }
+    }
+}
+
+impl TrackCache {
+    /// 取出缓存的曲目，不存在或已过期时返回 `None`
+    pub fn get(&self, id: u32) -> Option<Track> {
+        self.cache.get(&id)
+    }
+
+    /// 缓存曲目（以曲目 ID 为键）
+    pub fn insert(&self, track: Track) {
+        self.cache.insert(track.id, track);
+    }
+
+    /// 使一个曲目的缓存失效
+    pub fn invalidate(&self, id: u32) {
+        self.cache.invalidate(&id);
+    }
+
+    /// 使全部缓存失效
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TrackBuilder;
+
+    #[test]
+    fn invalidates_single_and_all_tracks() {
+        let cache = TrackCache::default();
+        cache.insert(TrackBuilder::new("a").id(1).build());
+        cache.insert(TrackBuilder::new("b").id(2).build());
+        assert_eq!(cache.get(1).unwrap().sha256, "a");
+
+        cache.invalidate(1);
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(2).is_some());
+
+        cache.invalidate_all();
+        assert!(cache.get(2).is_none());
+    }
+}
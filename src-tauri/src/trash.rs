@@ -0,0 +1,124 @@
+//! 本模块定义回收站及其读写
+//!
+//! `soft_delete_track` 将曲目从曲库移入回收站，可由 `restore_from_trash` 放回原目录。
+//! 回收站保存在应用数据目录下的 [`TRASH_FILE_NAME`] 中（JSON），启动时读回并丢弃
+//! 超过 [`TRASH_RETENTION_SECS`] 的条目。
+
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Track;
+
+/// 回收站文件名（位于应用数据目录）
+pub const TRASH_FILE_NAME: &str = "trash.json";
+
+/// 回收站条目的保留时间（30 天）
+pub const TRASH_RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// 回收站中的曲目
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrashedTrack {
+    pub track: Track,
+    /// 删除前所在的目录，恢复时放回（目录已不存在时重新创建）
+    pub directory: String,
+    /// 删除时间（Unix 时间戳，秒）
+    pub deleted_at: u64,
+}
+
+impl TrashedTrack {
+    /// 以当前时间记录一个被删除的曲目
+    pub fn new(track: Track, directory: String) -> Self {
+        Self {
+            track,
+            directory,
+            deleted_at: now_unix(),
+        }
+    }
+
+    /// 是否已超过保留时间
+    pub fn is_expired(&self) -> bool {
+        now_unix().saturating_sub(self.deleted_at) > TRASH_RETENTION_SECS
+    }
+}
+
+/// 移除超过保留时间的条目
+pub fn prune_expired(trash: &mut Vec<TrashedTrack>) {
+    trash.retain(|trashed| !trashed.is_expired());
+}
+
+/// 读取回收站，文件不存在或内容无效时返回空列表
+pub fn load_trash(path: &Path) -> Vec<TrashedTrack> {
+    std::fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+/// 写入回收站（覆盖原文件）
+pub async fn save_trash(path: &Path, trash: &[TrashedTrack]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let content = serde_json::to_vec(trash).map_err(|e| e.to_string())?;
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 辅助函数：当前时间（Unix 时间戳，秒）
+fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TrackBuilder};
+
+    fn trashed(sha256: &str, age_secs: u64) -> TrashedTrack {
+        TrashedTrack {
+            track: TrackBuilder::new(sha256).build(),
+            directory: "pack".to_string(),
+            deleted_at: now_unix() - age_secs,
+        }
+    }
+
+    #[test]
+    fn entries_expire_after_retention() {
+        assert!(!TrashedTrack::new(TrackBuilder::new("a").build(), "pack".into()).is_expired());
+        assert!(!trashed("a", TRASH_RETENTION_SECS - 60).is_expired());
+        assert!(trashed("a", TRASH_RETENTION_SECS + 60).is_expired());
+
+        let mut trash = vec![
+            trashed("old", TRASH_RETENTION_SECS + 60),
+            trashed("new", 60),
+        ];
+        prune_expired(&mut trash);
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].track.sha256, "new");
+    }
+
+    #[tokio::test]
+    async fn trash_file_round_trips() {
+        let dir = TempDir::new();
+        let path = dir.path().join("data").join(TRASH_FILE_NAME);
+        assert!(load_trash(&path).is_empty());
+
+        save_trash(&path, &[trashed("a", 0), trashed("b", 10)])
+            .await
+            .unwrap();
+        let loaded = load_trash(&path);
+        let hashes: Vec<&str> = loaded.iter().map(|t| t.track.sha256.as_str()).collect();
+        assert_eq!(hashes, ["a", "b"]);
+        assert_eq!(loaded[0].directory, "pack");
+    }
+}
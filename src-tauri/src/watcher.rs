@@ -0,0 +1,87 @@
+//! 本模块用于监视目录中新增或修改的文件
+//!
+//! 基于 notify 递归监视目录，变更的路径经由通道送出；[`next_batch`] 在收到第一个路径后
+//! 再等待一小段时间收集其余变更，避免复制文件时的多次写入事件被逐个处理。
+//! 部分编辑器保存时即使内容未变也会产生修改事件，可用 [`detect_real_change`] 过滤。
+
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+/// 目录监视器，被丢弃时停止监视
+pub struct DirectoryWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+/// 开始递归监视目录，返回监视器与新增 / 修改文件路径的接收端
+pub fn watch(path: &Path) -> Result<(DirectoryWatcher, mpsc::UnboundedReceiver<PathBuf>), String> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        let Ok(event) = result else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            for path in event.paths {
+                let _ = sender.send(path);
+            }
+        }
+    })
+    .map_err(|e| e.to_string())?;
+    watcher
+        .watch(path, RecursiveMode::Recursive)
+        .map_err(|e| e.to_string())?;
+    Ok((DirectoryWatcher { _watcher: watcher }, receiver))
+}
+
+/// 等待下一批变更：收到第一个路径后继续收集 `debounce` 时间内的其余路径，去重后返回。
+/// 监视器已被丢弃时返回 `None`
+pub async fn next_batch(
+    receiver: &mut mpsc::UnboundedReceiver<PathBuf>,
+    debounce: Duration,
+) -> Option<Vec<PathBuf>> {
+    let first = receiver.recv().await?;
+    let mut seen = HashSet::from([first.clone()]);
+    let mut batch = vec![first];
+
+    let deadline = tokio::time::sleep(debounce);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            path = receiver.recv() => match path {
+                Some(path) => {
+                    if seen.insert(path.clone()) {
+                        batch.push(path);
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+    Some(batch)
+}
+
+/// 重新计算文件的 SHA256，与 `old_sha256` 不同时返回 `true`
+pub async fn detect_real_change(old_sha256: [u8; 32], path: &Path) -> io::Result<bool> {
+    let content = tokio::fs::read(path).await?;
+    Ok(<[u8; 32]>::from(Sha256::digest(&content)) != old_sha256)
+}
+
+/// 将十六进制字符串形式的 SHA256 转换为字节数组，格式无效时返回 `None`
+pub fn sha256_from_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}